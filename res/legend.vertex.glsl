@@ -4,10 +4,13 @@ attribute vec2 v_coord;
 uniform float width;
 uniform float height;
 uniform float margin;
+// scale and offset from the normalized device coordinates of the frame to those of the tile
+uniform vec4 tile;
 varying float z;
 
 void main(void) {
-    gl_Position = vec4(v_coord.x * (width - 2.0 * margin) / width, v_coord.y * (margin / 5.0) / height - (height - margin / 5.0) / height, 0.0, 1.0);
+    vec2 pos = vec2(v_coord.x * (width - 2.0 * margin) / width, v_coord.y * (margin / 5.0) / height - (height - margin / 5.0) / height);
+    gl_Position = vec4(pos * tile.xy + tile.zw, 0.0, 1.0);
     z = v_coord.x;
 }
 
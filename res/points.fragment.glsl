@@ -4,22 +4,31 @@ uniform float pointScale;
 uniform float width;
 uniform float height;
 uniform float margin;
+// part of the frame covered by the framebuffer, in pixels of the frame
+uniform vec2 tileOrigin;
+uniform vec2 tileSize;
 
 in vec4 Color;
 in vec2 Position;
+in float Visible;
 out vec4 out_color;
 
 void main() {
+    if (Visible < 0.5) {
+        discard;
+    }
+
+    vec2 frag = gl_FragCoord.xy + tileOrigin;
     if (
-               (gl_FragCoord.x < margin)
-            || (gl_FragCoord.x >= width - margin)
-            || (gl_FragCoord.y < margin)
-            || (gl_FragCoord.y >= height - margin)) {
+               (frag.x < margin)
+            || (frag.x >= width - margin)
+            || (frag.y < margin)
+            || (frag.y >= height - margin)) {
         discard;
     }
 
-    float x = (Position.x + 1.0) / 2.0 * width;
-    float y = (Position.y + 1.0) / 2.0 * height;
+    float x = (Position.x + 1.0) / 2.0 * tileSize.x;
+    float y = (Position.y + 1.0) / 2.0 * tileSize.y;
     float dx = x - gl_FragCoord.x;
     float dy = y - gl_FragCoord.y;
     float step1 = 0.5 * pointScale;
@@ -6,7 +6,9 @@ uniform mat4 transformation;
 in float position_x;
 in float position_y;
 in float position_z;
+in float visible;
 out vec4 Color;
+out float Visible;
 out vec2 Position;
 
 vec4 z2rgba(float z);
@@ -20,5 +22,6 @@ void main() {
     Position = vec2(gl_Position.x, gl_Position.y);
 
     gl_PointSize = pointScale;
+    Visible = visible;
 }
 
@@ -0,0 +1,422 @@
+use data;
+use std::comm;
+use std::num::Float;
+use std::rand::{Rng, SeedableRng, XorShiftRng};
+use vptree;
+
+static DEFAULT_K: uint = 3;
+static DEFAULT_MIN_POINTS: uint = 5;
+static DEFAULT_SEED: u32 = 1;
+static MAX_ITERATIONS: uint = 100;
+
+pub static COLUMN: &'static str = "cluster";
+
+// label of DBSCAN points that do not belong to any cluster
+pub static NOISE: f64 = -1f64;
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Method {
+    // number of clusters
+    MethodKMeans(uint),
+    // radius and minimum number of neighbors of core points
+    MethodDbscan(f64, uint),
+}
+
+#[deriving(Clone, Show)]
+pub struct Spec {
+    // empty for all numeric columns
+    pub columns: Vec<String>,
+    pub method: Method,
+    pub seed: u32,
+    pub standardize: bool,
+}
+
+/// Parses a comma-separated list of column names and options, e.g. `k=5, seed=2, std, a, b` or
+/// `dbscan, eps=0.5, min=10, a, b`.
+pub fn parse_spec(s: &str) -> Result<Spec, String> {
+    let mut columns = Vec::new();
+    let mut dbscan = false;
+    let mut k = DEFAULT_K;
+    let mut eps = None;
+    let mut minPoints = DEFAULT_MIN_POINTS;
+    let mut seed = DEFAULT_SEED;
+    let mut standardize = false;
+
+    for part in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let kv: Vec<&str> = part.splitn('=', 1).map(|x| x.trim()).collect();
+        if kv.len() == 1 {
+            if part == "std" || part == "standardize" {
+                standardize = true;
+            } else if part == "dbscan" {
+                dbscan = true;
+            } else if part == "kmeans" {
+                dbscan = false;
+            } else {
+                columns.push(part.to_string());
+            }
+            continue;
+        }
+
+        let invalid = format!("invalid value for {}: {}", kv[0], kv[1]);
+        match kv[0] {
+            "k" => match from_str::<uint>(kv[1]) {
+                Some(x) if x > 0 => k = x,
+                _ => return Err(invalid)
+            },
+            "eps" => match from_str::<f64>(kv[1]) {
+                Some(x) if x > 0f64 => eps = Some(x),
+                _ => return Err(invalid)
+            },
+            "min" => match from_str::<uint>(kv[1]) {
+                Some(x) if x > 0 => minPoints = x,
+                _ => return Err(invalid)
+            },
+            "seed" => match from_str::<u32>(kv[1]) {
+                Some(x) => seed = x,
+                None => return Err(invalid)
+            },
+            _ => return Err(format!("unknown option {}", kv[0]))
+        }
+    }
+
+    let method = if dbscan {
+        match eps {
+            Some(e) => MethodDbscan(e, minPoints),
+            None => return Err("dbscan needs a radius, e.g. eps=0.5".to_string())
+        }
+    } else {
+        MethodKMeans(k)
+    };
+    Ok(Spec {
+        columns: columns,
+        method: method,
+        seed: seed,
+        standardize: standardize,
+    })
+}
+
+fn distance2(points: &[f64], dim: uint, i: uint, center: &[f64]) -> f64 {
+    let mut sum = 0f64;
+    for j in range(0, dim) {
+        let d = points[i * dim + j] - center[j];
+        sum += d * d;
+    }
+    sum
+}
+
+fn nearest(points: &[f64], dim: uint, i: uint, centers: &Vec<f64>) -> uint {
+    let k = centers.len() / dim;
+    let mut best = 0u;
+    let mut bestDistance = Float::infinity();
+    for c in range(0, k) {
+        let d = distance2(points, dim, i, centers.slice(c * dim, (c + 1) * dim));
+        if d < bestDistance {
+            best = c;
+            bestDistance = d;
+        }
+    }
+    best
+}
+
+fn kmeans<R: Rng>(points: &[f64], dim: uint, k: uint, rng: &mut R) -> Result<Vec<uint>, String> {
+    let n = points.len() / dim;
+    if k > n {
+        return Err(format!("cannot build {} clusters from {} rows", k, n));
+    }
+
+    // k-means++ seeding, new centers are picked with probability proportional to the squared
+    // distance to the closest existing one
+    let first = rng.gen_range(0, n);
+    let mut centers: Vec<f64> = points.slice(first * dim, (first + 1) * dim).to_vec();
+    let mut closest: Vec<f64> = range(0, n).map(|i| distance2(points, dim, i, centers.as_slice())).collect();
+    for _ in range(1, k) {
+        let total = closest.iter().fold(0f64, |a, &b| a + b);
+        let target = rng.gen::<f64>() * total;
+        let mut sum = 0f64;
+        let mut pick = n - 1;
+        for (i, &d) in closest.iter().enumerate() {
+            sum += d;
+            if sum >= target && d > 0f64 {
+                pick = i;
+                break;
+            }
+        }
+        let start = centers.len();
+        centers.push_all(points.slice(pick * dim, (pick + 1) * dim));
+        for i in range(0, n) {
+            let d = distance2(points, dim, i, centers.slice(start, start + dim));
+            if d < closest[i] {
+                *closest.get_mut(i) = d;
+            }
+        }
+    }
+
+    // Lloyd iterations
+    let mut assignment: Vec<uint> = range(0, n).map(|i| nearest(points, dim, i, &centers)).collect();
+    for _ in range(0, MAX_ITERATIONS) {
+        let mut sums = Vec::from_elem(k * dim, 0f64);
+        let mut counts = Vec::from_elem(k, 0u);
+        for (i, &c) in assignment.iter().enumerate() {
+            *counts.get_mut(c) += 1;
+            for j in range(0, dim) {
+                *sums.get_mut(c * dim + j) += points[i * dim + j];
+            }
+        }
+        for c in range(0, k) {
+            // empty clusters keep their center
+            if counts[c] > 0 {
+                for j in range(0, dim) {
+                    *centers.get_mut(c * dim + j) = sums[c * dim + j] / counts[c] as f64;
+                }
+            }
+        }
+
+        let mut changed = false;
+        for i in range(0, n) {
+            let c = nearest(points, dim, i, &centers);
+            if c != assignment[i] {
+                *assignment.get_mut(i) = c;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    Ok(assignment)
+}
+
+// labels of points that are not visited yet
+static UNVISITED: int = -2;
+
+fn dbscan<R: Rng>(points: &[f64], dim: uint, eps: f64, minPoints: uint, rng: &mut R) -> Vec<int> {
+    let n = points.len() / dim;
+    let tree = vptree::VpTree::new(points, dim, rng);
+    let mut labels = Vec::from_elem(n, UNVISITED);
+    let mut next = 0i;
+
+    for i in range(0, n) {
+        if labels[i] != UNVISITED {
+            continue;
+        }
+        let neighbors = tree.within(i, eps);
+        if neighbors.len() < minPoints {
+            *labels.get_mut(i) = NOISE as int;
+            continue;
+        }
+
+        let cluster = next;
+        next += 1;
+        *labels.get_mut(i) = cluster;
+        let mut queue = neighbors;
+        while !queue.is_empty() {
+            let j = queue.pop().unwrap();
+            if labels[j] == NOISE as int {
+                // border point
+                *labels.get_mut(j) = cluster;
+            }
+            if labels[j] != UNVISITED {
+                continue;
+            }
+            *labels.get_mut(j) = cluster;
+            let more = tree.within(j, eps);
+            if more.len() >= minPoints {
+                queue.push_all(more.as_slice());
+            }
+        }
+    }
+    labels
+}
+
+/// Rows without NaN values in the selected columns copied from the table, so the clustering can run
+/// in a background task.
+pub struct Input {
+    spec: Spec,
+    // row by row
+    points: Vec<f64>,
+    dim: uint,
+    // table row of each point
+    rows: Vec<uint>,
+    nrows: uint,
+}
+
+pub fn prepare(spec: &Spec, table: &data::Table) -> Result<Input, String> {
+    let names: Vec<String> = if spec.columns.is_empty() {
+        table.columns().iter()
+            .filter(|c| table.kind(*c) == data::KindNumeric)
+            .map(|c| c.clone())
+            .collect()
+    } else {
+        spec.columns.clone()
+    };
+    if names.is_empty() {
+        return Err("no columns to cluster".to_string());
+    }
+    let mut columns = Vec::new();
+    for name in names.iter() {
+        match table.get(name) {
+            Some(d) => columns.push(d),
+            None => return Err(format!("unknown column {}", name))
+        }
+    }
+
+    let dim = columns.len();
+    let rows: Vec<uint> = range(0, table.len()).filter(|&r| columns.iter().all(|c| c[r].is_finite())).collect();
+    let n = rows.len();
+    if n == 0 {
+        return Err("no rows without NaN values".to_string());
+    }
+    let mut points = Vec::with_capacity(n * dim);
+    for &r in rows.iter() {
+        for column in columns.iter() {
+            points.push(column[r]);
+        }
+    }
+    Ok(Input {
+        spec: spec.clone(),
+        points: points,
+        dim: dim,
+        rows: rows,
+        nrows: table.len(),
+    })
+}
+
+/// Clusters the prepared rows, the result contains the cluster of each table row, NaN for skipped
+/// rows and `NOISE` for DBSCAN outliers.
+pub fn compute(input: Input) -> Result<Vec<f64>, String> {
+    let Input{spec, mut points, dim, rows, nrows} = input;
+    let n = rows.len();
+    if spec.standardize {
+        for j in range(0, dim) {
+            let mean = range(0, n).fold(0f64, |a, i| a + points[i * dim + j]) / n as f64;
+            let var = range(0, n).fold(0f64, |a, i| a + (points[i * dim + j] - mean).powi(2)) / n as f64;
+            let scale = if var > 0f64 { var.sqrt() } else { 1f64 };
+            for i in range(0, n) {
+                *points.get_mut(i * dim + j) = (points[i * dim + j] - mean) / scale;
+            }
+        }
+    }
+
+    let mut rng: XorShiftRng = SeedableRng::from_seed([spec.seed, 0x9e3779b9, 0x243f6a88, 0xb7e15162]);
+    let labels: Vec<f64> = match spec.method {
+        MethodKMeans(k) => try!(kmeans(points.as_slice(), dim, k, &mut rng)).iter().map(|&c| c as f64).collect(),
+        MethodDbscan(eps, minPoints) => dbscan(points.as_slice(), dim, eps, minPoints, &mut rng).iter().map(|&c| c as f64).collect(),
+    };
+
+    let mut result = Vec::from_elem(nrows, Float::nan());
+    for (&r, &label) in rows.iter().zip(labels.iter()) {
+        *result.get_mut(r) = label;
+    }
+    Ok(result)
+}
+
+/// Runs `compute` in a background task, the result is sent once it is done.
+pub fn start(input: Input) -> comm::Receiver<Result<Vec<f64>, String>> {
+    let (tx, rx) = comm::channel();
+    spawn(proc() {
+        let _ = tx.send_opt(compute(input));
+    });
+    rx
+}
+
+/// Adds or replaces the cluster column.
+pub fn apply(labels: Vec<f64>, table: &mut data::Table) {
+    let name = COLUMN.to_string();
+    table.add_column(&name, labels);
+    table.set_kind(&name, data::KindCategorical);
+}
+
+/// Mean of `x` and `y` for every cluster as (label, x, y), skipping NaN values and noise.
+pub fn centroids(labels: &Vec<f64>, x: &Vec<f64>, y: &Vec<f64>) -> Vec<(uint, f64, f64)> {
+    let mut sums: Vec<(f64, f64, uint)> = Vec::new();
+    for ((&label, &vx), &vy) in labels.iter().zip(x.iter()).zip(y.iter()) {
+        if !(label >= 0f64) || !vx.is_finite() || !vy.is_finite() {
+            continue;
+        }
+        let c = label as uint;
+        while sums.len() <= c {
+            sums.push((0f64, 0f64, 0u));
+        }
+        let (sx, sy, count) = sums[c];
+        *sums.get_mut(c) = (sx + vx, sy + vy, count + 1);
+    }
+    sums.iter().enumerate()
+        .filter(|&(_, &(_, _, count))| count > 0)
+        .map(|(c, &(sx, sy, count))| (c, sx / count as f64, sy / count as f64))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use data;
+    use std::collections::TreeSet;
+    use std::iter::FromIterator;
+    use std::num::Float;
+    use super::{centroids, compute, parse_spec, prepare, MethodDbscan, MethodKMeans, NOISE};
+
+    // two square blobs of 25 points around (0, 0) and (10, 10), plus `outliers` far away
+    fn blobs(outliers: &[(f64, f64)]) -> data::Table {
+        let columns: TreeSet<String> = FromIterator::from_iter(vec!["a".to_string(), "b".to_string()].move_iter());
+        let mut table = data::Table::new("test".to_string(), columns);
+        for &offset in [0f64, 10f64].iter() {
+            for i in range(0u, 5) {
+                for j in range(0u, 5) {
+                    table.push(&vec![offset + 0.1 * i as f64, offset + 0.1 * j as f64]);
+                }
+            }
+        }
+        for &(a, b) in outliers.iter() {
+            table.push(&vec![a, b]);
+        }
+        table
+    }
+
+    fn check_blobs(labels: &Vec<f64>) {
+        assert!(labels.slice(0, 25).iter().all(|&l| l == labels[0]));
+        assert!(labels.slice(25, 50).iter().all(|&l| l == labels[25]));
+        assert!(labels[0] >= 0f64 && labels[25] >= 0f64);
+        assert!(labels[0] != labels[25]);
+    }
+
+    #[test]
+    fn kmeans_separates_blobs() {
+        let spec = parse_spec("k=2, a, b").unwrap();
+        assert_eq!(spec.method, MethodKMeans(2));
+        let labels = compute(prepare(&spec, &blobs(&[])).unwrap()).unwrap();
+        check_blobs(&labels);
+    }
+
+    #[test]
+    fn dbscan_separates_blobs() {
+        let spec = parse_spec("dbscan, eps=0.5, min=3, a, b").unwrap();
+        assert_eq!(spec.method, MethodDbscan(0.5, 3));
+        let labels = compute(prepare(&spec, &blobs(&[])).unwrap()).unwrap();
+        check_blobs(&labels);
+    }
+
+    #[test]
+    fn dbscan_labels_noise() {
+        let spec = parse_spec("dbscan, eps=0.5, min=3, a, b").unwrap();
+        let labels = compute(prepare(&spec, &blobs(&[(5f64, 5f64), (-20f64, 3f64)])).unwrap()).unwrap();
+        check_blobs(&labels);
+        assert_eq!(labels[50], NOISE);
+        assert_eq!(labels[51], NOISE);
+    }
+
+    #[test]
+    fn parse_spec_errors() {
+        assert!(parse_spec("dbscan, a, b").is_err());
+        assert!(parse_spec("k=0").is_err());
+        assert!(parse_spec("eps=-1, dbscan").is_err());
+        assert!(parse_spec("size=3").is_err());
+    }
+
+    #[test]
+    fn centroids_skip_noise_and_nan() {
+        let nan: f64 = Float::nan();
+        let labels = vec![0f64, 0f64, 1f64, NOISE, nan, 1f64];
+        let x = vec![1f64, 3f64, 10f64, 100f64, 100f64, nan];
+        let y = vec![2f64, 4f64, 20f64, 100f64, 100f64, 30f64];
+        assert_eq!(centroids(&labels, &x, &y), vec![(0u, 2f64, 3f64), (1u, 10f64, 20f64)]);
+    }
+}
@@ -0,0 +1,172 @@
+use keymap;
+use std::io;
+use std::os;
+use std::path::Path;
+use toml;
+
+/// Defaults of the renderer, read from `~/.config/fluxcore/config.toml`.
+pub struct Config {
+    pub width: i32,
+    pub height: i32,
+    // space for the axes around the plot in pixels
+    pub margin: f32,
+    pub fontSize: u32,
+    // minimal distance of axis ticks in pixels
+    pub tickDistance: i32,
+    pub pointScale: f32,
+    pub alphaScale: f32,
+    pub background: [f32, ..3],
+    // axes, labels and text
+    pub foreground: [f32, ..3],
+    pub keys: keymap::KeyMap,
+}
+
+impl Config {
+    pub fn default() -> Config {
+        Config {
+            width: 800,
+            height: 600,
+            margin: 130f32,
+            fontSize: 16,
+            tickDistance: 60,
+            pointScale: 4f32,
+            alphaScale: 1f32,
+            background: [0.1, 0.1, 0.1],
+            foreground: [0.23, 0.80, 0.62],
+            keys: keymap::KeyMap::default(),
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME/fluxcore/config.toml`, falling back to `~/.config`.
+    pub fn path() -> Option<Path> {
+        let base = match os::getenv("XDG_CONFIG_HOME") {
+            Some(ref dir) if !dir.is_empty() => Path::new(dir.as_slice()),
+            _ => match os::homedir() {
+                Some(home) => home.join(".config"),
+                None => return None
+            }
+        };
+        Some(base.join("fluxcore").join("config.toml"))
+    }
+
+    /// Reads the config file, settings that are not given keep their defaults.
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let content = match io::File::open(path).read_to_string() {
+            Ok(c) => c,
+            Err(e) => return Err(format!("{}: {}", path.display(), e))
+        };
+        let mut parser = toml::Parser::new(content.as_slice());
+        let root = match parser.parse() {
+            Some(t) => toml::Table(t),
+            None => {
+                let e = &parser.errors[0];
+                let (line, col) = parser.to_linecol(e.lo);
+                return Err(format!("{}:{}:{}: {}", path.display(), line + 1, col + 1, e.desc))
+            }
+        };
+        let mut config = Config::default();
+        try!(config.apply(&root).map_err(|e| format!("{}: {}", path.display(), e)));
+        Ok(config)
+    }
+
+    fn apply(&mut self, root: &toml::Value) -> Result<(), String> {
+        match try!(integer(root, "window.width")) {
+            Some(x) => self.width = x as i32,
+            None => ()
+        }
+        match try!(integer(root, "window.height")) {
+            Some(x) => self.height = x as i32,
+            None => ()
+        }
+        match try!(integer(root, "window.margin")) {
+            Some(x) => self.margin = x as f32,
+            None => ()
+        }
+        match try!(integer(root, "window.font_size")) {
+            Some(x) => self.fontSize = x as u32,
+            None => ()
+        }
+        match try!(integer(root, "window.tick_distance")) {
+            Some(x) => self.tickDistance = x as i32,
+            None => ()
+        }
+        match try!(float(root, "view.point_size")) {
+            Some(x) => self.pointScale = x as f32,
+            None => ()
+        }
+        match try!(float(root, "view.alpha")) {
+            Some(x) => self.alphaScale = x as f32,
+            None => ()
+        }
+        match try!(color(root, "view.background")) {
+            Some(c) => self.background = c,
+            None => ()
+        }
+        match try!(color(root, "view.foreground")) {
+            Some(c) => self.foreground = c,
+            None => ()
+        }
+
+        match root.lookup("keys") {
+            Some(&toml::Table(ref keys)) => for (name, value) in keys.iter() {
+                let action = try!(keymap::parse_action(name.as_slice()));
+                // a single key or a list of keys, an empty list unbinds the action
+                let specs: Vec<&toml::Value> = match *value {
+                    toml::Array(ref a) => a.iter().collect(),
+                    ref v => vec![v]
+                };
+                let mut bindings = Vec::new();
+                for spec in specs.iter() {
+                    match spec.as_str() {
+                        Some(s) => bindings.push(try!(keymap::parse_binding(s))),
+                        None => return Err(format!("keys.{}: expected a key name", name))
+                    }
+                }
+                self.keys.bind(action, bindings);
+            },
+            Some(_) => return Err("keys must be a table".to_string()),
+            None => ()
+        }
+        Ok(())
+    }
+}
+
+fn integer(root: &toml::Value, key: &str) -> Result<Option<i64>, String> {
+    match root.lookup(key) {
+        Some(v) => match v.as_integer() {
+            Some(x) if x > 0 => Ok(Some(x)),
+            _ => Err(format!("{} must be a positive integer", key))
+        },
+        None => Ok(None)
+    }
+}
+
+// integers are accepted as well
+fn float(root: &toml::Value, key: &str) -> Result<Option<f64>, String> {
+    match root.lookup(key) {
+        Some(v) => match v.as_float().or_else(|| v.as_integer().map(|x| x as f64)) {
+            Some(x) if x > 0f64 => Ok(Some(x)),
+            _ => Err(format!("{} must be a positive number", key))
+        },
+        None => Ok(None)
+    }
+}
+
+// `[r, g, b]` with components in 0..1
+fn color(root: &toml::Value, key: &str) -> Result<Option<[f32, ..3]>, String> {
+    let values = match root.lookup(key) {
+        Some(v) => match v.as_slice() {
+            Some(a) if a.len() == 3 => a,
+            _ => return Err(format!("{} must be a list of red, green and blue", key))
+        },
+        None => return Ok(None)
+    };
+    let mut c = [0f32, ..3];
+    for (i, v) in values.iter().enumerate() {
+        match v.as_float().or_else(|| v.as_integer().map(|x| x as f64)) {
+            Some(x) if x >= 0f64 && x <= 1f64 => c[i] = x as f32,
+            _ => return Err(format!("{} components must be between 0.0 and 1.0", key))
+        }
+    }
+    Ok(Some(c))
+}
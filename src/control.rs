@@ -0,0 +1,166 @@
+use serialize::json;
+use serialize::json::ToJson;
+use std::collections::TreeMap;
+use std::comm;
+use std::io;
+use std::io::{Acceptor, Listener};
+use std::io::net::unix::{UnixListener, UnixStream};
+use std::path::Path;
+use stream;
+
+// JSON-RPC 2.0 error codes
+static PARSE_ERROR: int = -32700;
+static INVALID_REQUEST: int = -32600;
+static SERVER_ERROR: int = -32000;
+
+/// A request forwarded to the renderer, the result is sent back to the client.
+pub struct Command {
+    pub method: String,
+    pub params: json::Json,
+    pub reply: comm::Sender<Result<json::Json, String>>,
+}
+
+/// String parameter of a request.
+pub fn param_string(params: &json::Json, key: &str) -> Option<String> {
+    params.find(&key.to_string()).and_then(|j| j.as_string()).map(|s| s.to_string())
+}
+
+/// Numeric parameter of a request.
+pub fn param_f64(params: &json::Json, key: &str) -> Option<f64> {
+    params.find(&key.to_string()).and_then(|j| j.as_f64())
+}
+
+fn response(id: json::Json, key: &str, value: json::Json) -> String {
+    let mut o = TreeMap::new();
+    o.insert("jsonrpc".to_string(), "2.0".to_string().to_json());
+    o.insert("id".to_string(), id);
+    o.insert(key.to_string(), value);
+    o.to_json().to_string()
+}
+
+fn error(id: json::Json, code: int, message: String) -> String {
+    let mut o = TreeMap::new();
+    o.insert("code".to_string(), code.to_json());
+    o.insert("message".to_string(), message.to_json());
+    response(id, "error", o.to_json())
+}
+
+// runs a request, None once the renderer is gone
+fn handle(line: &str, tx: &comm::Sender<Option<Command>>) -> Option<String> {
+    let request = match json::from_str(line) {
+        Ok(r) => r,
+        Err(e) => return Some(error(json::Null, PARSE_ERROR, format!("{}", e)))
+    };
+    let id = request.find(&"id".to_string()).map(|j| j.clone()).unwrap_or(json::Null);
+    let method = match param_string(&request, "method") {
+        Some(m) => m,
+        None => return Some(error(id, INVALID_REQUEST, "missing method".to_string()))
+    };
+    let params = request.find(&"params".to_string()).map(|j| j.clone()).unwrap_or(json::Null);
+
+    let (replyTx, replyRx) = comm::channel();
+    let command = Command {
+        method: method,
+        params: params,
+        reply: replyTx,
+    };
+    if tx.send_opt(Some(command)).is_err() {
+        return None;
+    }
+    match replyRx.recv_opt() {
+        Ok(Ok(result)) => Some(response(id, "result", result)),
+        Ok(Err(e)) => Some(error(id, SERVER_ERROR, e)),
+        Err(()) => None
+    }
+}
+
+// answers one request per line until the client disconnects
+fn serve(conn: UnixStream, tx: comm::Sender<Option<Command>>) {
+    let mut writer = conn.clone();
+    let (linesTx, linesRx) = comm::channel();
+    spawn(proc() {
+        let mut conn = conn;
+        conn.set_read_timeout(Some(stream::POLL_MS as u64));
+        stream::pump(&mut conn, &linesTx);
+    });
+
+    for line in linesRx.iter() {
+        if line.as_slice().trim().is_empty() {
+            if tx.send_opt(None).is_err() {
+                return;
+            }
+            continue;
+        }
+        let answer = match handle(line.as_slice(), &tx) {
+            Some(a) => a,
+            None => return
+        };
+        if writer.write_line(answer.as_slice()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Accepts JSON-RPC 2.0 requests, one per line, on a Unix socket.
+pub struct Server {
+    path: Path,
+    // None only checks that the renderer is still there
+    rx: comm::Receiver<Option<Command>>,
+}
+
+impl Server {
+    pub fn listen(path: &Path) -> Result<Server, String> {
+        try!(stream::remove_stale_socket(path));
+        let mut acceptor = match UnixListener::bind(path).listen() {
+            Ok(a) => a,
+            Err(e) => return Err(format!("{}", e))
+        };
+        acceptor.set_timeout(Some(stream::POLL_MS as u64));
+
+        let (tx, rx) = comm::channel();
+        spawn(proc() {
+            let mut acceptor = acceptor;
+            loop {
+                match acceptor.accept() {
+                    Ok(conn) => {
+                        let tx = tx.clone();
+                        spawn(proc() {
+                            serve(conn, tx);
+                        });
+                    },
+                    Err(ref e) if e.kind == io::TimedOut => {
+                        if tx.send_opt(None).is_err() {
+                            return;
+                        }
+                    },
+                    Err(_) => return
+                }
+            }
+        });
+
+        Ok(Server {
+            path: path.clone(),
+            rx: rx,
+        })
+    }
+
+    /// The next pending request, never blocks.
+    pub fn next(&self) -> Option<Command> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(Some(command)) => return Some(command),
+                Ok(None) => continue,
+                Err(_) => return None
+            }
+        }
+    }
+}
+
+impl Drop for Server {
+    // the socket was created by `listen`, unless something else took its place since
+    fn drop(&mut self) {
+        if stream::is_socket(&self.path) {
+            let _ = io::fs::unlink(&self.path);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use data;
+use std::f64;
+use std::io;
+use std::num::Float;
+use std::path::Path;
+
+static HISTOGRAM_BINS: uint = 32u;
+
+pub struct ColumnRange {
+    pub name: String,
+    pub min: f64,
+    pub max: f64,
+    pub lo: f64,
+    pub hi: f64,
+    pub histogram: Vec<uint>,
+}
+
+impl ColumnRange {
+    fn new(name: &String, data: &Vec<f64>) -> ColumnRange {
+        let mut min = Float::infinity();
+        let mut max = Float::neg_infinity();
+        for &x in data.iter().filter(|x| x.is_finite()) {
+            min = min.min(x);
+            max = max.max(x);
+        }
+        if min > max {
+            min = 0f64;
+            max = 0f64;
+        }
+
+        let mut histogram = Vec::from_elem(HISTOGRAM_BINS, 0u);
+        for &x in data.iter().filter(|x| x.is_finite()) {
+            let bin = if max > min {
+                (((x - min) / (max - min)) * HISTOGRAM_BINS as f64).floor() as uint
+            } else {
+                0
+            };
+            *histogram.get_mut(bin.min(HISTOGRAM_BINS - 1)) += 1;
+        }
+
+        ColumnRange {
+            name: name.clone(),
+            min: min,
+            max: max,
+            lo: min,
+            hi: max,
+            histogram: histogram,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.lo > self.min || self.hi < self.max
+    }
+
+    pub fn reset(&mut self) {
+        self.lo = self.min;
+        self.hi = self.max;
+    }
+
+    /// Sets one end of the range, `pos` is relative to the column range (0 = min, 1 = max).
+    pub fn set_relative(&mut self, pos: f64, upper: bool) {
+        let value = self.min + pos.max(0f64).min(1f64) * (self.max - self.min);
+        if upper {
+            self.hi = value.max(self.lo);
+        } else {
+            self.lo = value.min(self.hi);
+        }
+    }
+
+    pub fn relative(&self, value: f64) -> f64 {
+        if self.max > self.min {
+            (value - self.min) / (self.max - self.min)
+        } else {
+            0f64
+        }
+    }
+}
+
+pub struct Crossfilter {
+    pub ranges: Vec<ColumnRange>,
+}
+
+impl Crossfilter {
+    pub fn new(table: &data::Table) -> Crossfilter {
+        let mut result = Crossfilter {
+            ranges: Vec::new(),
+        };
+        result.sync(table);
+        result
+    }
+
+    /// Adds ranges for new columns and recalculates all histograms, active ranges are kept.
+    pub fn sync(&mut self, table: &data::Table) {
+        let mut ranges = Vec::new();
+        for name in table.columns().iter() {
+            let old = match self.ranges.iter().position(|r| &r.name == name) {
+                Some(i) => Some(self.ranges.swap_remove(i).unwrap()),
+                None => None
+            };
+            let mut range = ColumnRange::new(name, table.get(name).unwrap());
+            match old {
+                Some(o) => {
+                    if o.is_active() {
+                        range.lo = o.lo.max(range.min);
+                        range.hi = o.hi.min(range.max);
+                    }
+                },
+                None => ()
+            }
+            ranges.push(range);
+        }
+        self.ranges = ranges;
+    }
+
+    pub fn find_mut<'a>(&'a mut self, name: &String) -> Option<&'a mut ColumnRange> {
+        self.ranges.mut_iter().find(|r| &r.name == name)
+    }
+
+    /// Clears `mask` for all rows outside of an active range.
+    pub fn apply(&self, table: &data::Table, mask: &mut Vec<bool>) {
+        for range in self.ranges.iter().filter(|r| r.is_active()) {
+            let data = table.get(&range.name).unwrap();
+            for (m, &x) in mask.mut_iter().zip(data.iter()) {
+                if !(x >= range.lo && x <= range.hi) {
+                    *m = false;
+                }
+            }
+        }
+    }
+
+    pub fn summary(&self) -> Option<String> {
+        let active: Vec<String> = self.ranges.iter()
+            .filter(|r| r.is_active())
+            .map(|r| format!("{}: {}..{}", r.name, f64::to_str_digits(r.lo, 4), f64::to_str_digits(r.hi, 4)))
+            .collect();
+        if active.is_empty() {
+            None
+        } else {
+            Some(active.connect("\n"))
+        }
+    }
+
+    /// Writes active ranges as `name<TAB>lo<TAB>hi` lines.
+    pub fn save(&self, path: &Path) -> io::IoResult<()> {
+        let mut file = try!(io::File::create(path));
+        for r in self.ranges.iter().filter(|r| r.is_active()) {
+            try!(file.write_str(format!("{}\t{}\t{}\n", r.name, r.lo, r.hi).as_slice()));
+        }
+        Ok(())
+    }
+
+    pub fn load(&mut self, path: &Path) -> Result<(), String> {
+        let content = match io::File::open(path).read_to_string() {
+            Ok(c) => c,
+            Err(e) => return Err(format!("{}: {}", path.display(), e))
+        };
+        for (lineno, line) in content.as_slice().lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let parts: Vec<&str> = line.split('\t').collect();
+            if parts.len() != 3 {
+                return Err(format!("{}:{}: expected name, lower and upper bound", path.display(), lineno + 1));
+            }
+            let name = parts[0].to_string();
+            let (lo, hi) = match (from_str::<f64>(parts[1]), from_str::<f64>(parts[2])) {
+                (Some(lo), Some(hi)) => (lo, hi),
+                _ => return Err(format!("{}:{}: invalid bounds", path.display(), lineno + 1))
+            };
+            match self.find_mut(&name) {
+                Some(r) => {
+                    r.lo = lo.max(r.min);
+                    r.hi = hi.min(r.max);
+                },
+                None => return Err(format!("{}:{}: unknown column {}", path.display(), lineno + 1, name))
+            }
+        }
+        Ok(())
+    }
+}
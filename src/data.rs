@@ -1,8 +1,33 @@
+use stats;
 use std::collections::{TreeMap, TreeSet};
+use std::num::Float;
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum ColumnKind {
+    KindNumeric,
+    // seconds since the Unix epoch, UTC
+    KindTime,
+    // integer labels, e.g. cluster ids
+    KindCategorical,
+}
+
+impl ColumnKind {
+    pub fn label(&self) -> &'static str {
+        match *self {
+            KindNumeric => "numeric",
+            KindTime => "time",
+            KindCategorical => "categorical",
+        }
+    }
+}
 
 struct Column {
     name: String,
-    data: Vec<f32>,
+    unit: Option<String>,
+    kind: ColumnKind,
+    data: Vec<f64>,
+    // invalidated on every change, see `compute_stats`
+    stats: Option<stats::ColumnStats>,
 }
 
 pub struct Table {
@@ -17,7 +42,7 @@ impl Table {
 
         let mut columnMap = TreeMap::new();
         for c in columns.iter() {
-            columnMap.insert(c.clone(), Column{name: c.clone(), data: Vec::new()});
+            columnMap.insert(c.clone(), Column{name: c.clone(), unit: None, kind: KindNumeric, data: Vec::new(), stats: None});
         }
 
         Table {
@@ -27,20 +52,138 @@ impl Table {
         }
     }
 
-    pub fn push(&mut self, row: &Vec<f32>) {
+    pub fn push(&mut self, row: &Vec<f64>) {
         assert!(row.len() == self.columns.len());
         for ((_name, col), data) in self.columns.mut_iter().zip(row.iter()) {
-            col.data.push(data.clone())
+            col.data.push(data.clone());
+            col.stats = None;
         }
     }
 
-    pub fn get<'a>(&'a self, column: &String) -> Option<&'a Vec<f32>> {
+    /// Appends a row with values for the given columns, all others get NaN.
+    pub fn push_named(&mut self, names: &Vec<String>, row: &Vec<f64>) {
+        for (name, col) in self.columns.mut_iter() {
+            col.data.push(match names.iter().position(|n| n == name) {
+                Some(i) => row[i],
+                None => Float::nan()
+            });
+            col.stats = None;
+        }
+    }
+
+    /// Appends the rows of another table, columns missing there are filled with NaN.
+    pub fn append(&mut self, rows: &Table) {
+        let n = rows.len();
+        for (name, col) in self.columns.mut_iter() {
+            match rows.get(name) {
+                Some(d) => col.data.push_all(d.as_slice()),
+                None => col.data.grow(n, &Float::nan())
+            }
+            col.stats = None;
+        }
+    }
+
+    /// Overwrites row `index` like `push_named`, used to replace the oldest row of a full window.
+    pub fn set_row_named(&mut self, index: uint, names: &Vec<String>, row: &Vec<f64>) {
+        for (name, col) in self.columns.mut_iter() {
+            *col.data.get_mut(index) = match names.iter().position(|n| n == name) {
+                Some(i) => row[i],
+                None => Float::nan()
+            };
+            col.stats = None;
+        }
+    }
+
+    pub fn get<'a>(&'a self, column: &String) -> Option<&'a Vec<f64>> {
         match self.columns.find(column) {
             Some(c) => Some(&(c.data)),
             None => None
         }
     }
 
+    pub fn get_mut<'a>(&'a mut self, column: &String) -> Option<&'a mut Vec<f64>> {
+        match self.columns.find_mut(column) {
+            Some(c) => {
+                c.stats = None;
+                Some(&mut (c.data))
+            },
+            None => None
+        }
+    }
+
+    pub fn kind(&self, column: &String) -> ColumnKind {
+        match self.columns.find(column) {
+            Some(c) => c.kind,
+            None => KindNumeric
+        }
+    }
+
+    pub fn set_kind(&mut self, column: &String, kind: ColumnKind) {
+        match self.columns.find_mut(column) {
+            Some(c) => c.kind = kind,
+            None => ()
+        }
+    }
+
+    /// Adds a column, an existing column with the same name gets replaced.
+    pub fn add_column(&mut self, name: &String, data: Vec<f64>) {
+        assert!(data.len() == self.len());
+        self.colnames.insert(name.clone());
+        let stats = stats::compute(&data);
+        self.columns.insert(name.clone(), Column{name: name.clone(), unit: None, kind: KindNumeric, data: data, stats: Some(stats)});
+    }
+
+    /// Calculates statistics for all columns that changed since the last call.
+    pub fn compute_stats(&mut self) {
+        for (_name, col) in self.columns.mut_iter() {
+            if col.stats.is_none() {
+                col.stats = Some(stats::compute(&col.data));
+            }
+        }
+    }
+
+    /// Calculates statistics for one column if it changed since the last call.
+    pub fn compute_column_stats(&mut self, column: &String) {
+        match self.columns.find_mut(column) {
+            Some(col) => {
+                if col.stats.is_none() {
+                    col.stats = Some(stats::compute(&col.data));
+                }
+            },
+            None => ()
+        }
+    }
+
+    /// Statistics of a column, only available after `compute_stats` or `compute_column_stats`.
+    pub fn stats<'a>(&'a self, column: &String) -> Option<&'a stats::ColumnStats> {
+        match self.columns.find(column) {
+            Some(c) => c.stats.as_ref(),
+            None => None
+        }
+    }
+
+    pub fn unit<'a>(&'a self, column: &String) -> Option<&'a String> {
+        match self.columns.find(column) {
+            Some(c) => c.unit.as_ref(),
+            None => None
+        }
+    }
+
+    pub fn set_unit(&mut self, column: &String, unit: Option<String>) {
+        match self.columns.find_mut(column) {
+            Some(c) => c.unit = unit,
+            None => ()
+        }
+    }
+
+    /// Name of the column including its unit, e.g. `voltage [mV]`.
+    pub fn title(&self, column: &String) -> String {
+        match self.unit(column) {
+            Some(u) => format!("{} [{}]", column, u),
+            None => column.clone()
+        }
+    }
+
     pub fn len(&self) -> uint {
         self.columns.iter().next().unwrap().val1().data.len()
     }
@@ -0,0 +1,486 @@
+use std::f64;
+use std::num::Float;
+
+macro_rules! try_opt(
+    ($e:expr) => (match $e { Some(x) => x, None => return None })
+)
+
+static MINUTE: f64 = 60f64;
+static HOUR: f64 = 3600f64;
+static DAY: f64 = 86400f64;
+static MONTH: f64 = 2629746f64;
+static YEAR: f64 = 31556952f64;
+
+// Unix timestamps between 2000-01-01 and 2100-01-01
+static EPOCH_MIN: f64 = 946684800f64;
+static EPOCH_MAX: f64 = 4102444800f64;
+
+#[deriving(Clone, PartialEq, Show)]
+enum TimeUnit {
+    UnitSubSecond,
+    UnitSecond,
+    UnitMinute,
+    UnitHour,
+    UnitDay,
+    UnitMonth,
+    UnitYear,
+}
+
+static STEPS: [(TimeUnit, i64, f64), ..21] = [
+    (UnitSecond, 1, 1f64),
+    (UnitSecond, 2, 2f64),
+    (UnitSecond, 5, 5f64),
+    (UnitSecond, 10, 10f64),
+    (UnitSecond, 15, 15f64),
+    (UnitSecond, 30, 30f64),
+    (UnitMinute, 1, MINUTE),
+    (UnitMinute, 2, 2f64 * MINUTE),
+    (UnitMinute, 5, 5f64 * MINUTE),
+    (UnitMinute, 10, 10f64 * MINUTE),
+    (UnitMinute, 15, 15f64 * MINUTE),
+    (UnitMinute, 30, 30f64 * MINUTE),
+    (UnitHour, 1, HOUR),
+    (UnitHour, 3, 3f64 * HOUR),
+    (UnitHour, 6, 6f64 * HOUR),
+    (UnitHour, 12, 12f64 * HOUR),
+    (UnitDay, 1, DAY),
+    (UnitDay, 2, 2f64 * DAY),
+    (UnitDay, 7, 7f64 * DAY),
+    (UnitMonth, 1, MONTH),
+    (UnitMonth, 3, 3f64 * MONTH),
+];
+
+// see http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = (if y >= 0 { y } else { y - 399 }) / 400;
+    let yoe = y - era * 400;
+    let mp = if month > 2 { month - 3 } else { month + 9 };
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let z = days + 719468;
+    let era = (if z >= 0 { z } else { z - 146096 }) / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+fn split_timestamp(t: f64) -> (i64, i64, i64, i64, i64, f64) {
+    let days = (t / DAY).floor();
+    let (year, month, day) = civil_from_days(days as i64);
+    let secs = t - days * DAY;
+    let hour = (secs / HOUR).floor();
+    let minute = ((secs - hour * HOUR) / MINUTE).floor();
+    let second = secs - hour * HOUR - minute * MINUTE;
+    (year, month, day, hour as i64, minute as i64, second)
+}
+
+struct Scanner<'a> {
+    chars: &'a [u8],
+    pos: uint,
+}
+
+impl<'a> Scanner<'a> {
+    fn digits(&mut self, n: uint) -> Option<i64> {
+        if self.pos + n > self.chars.len() {
+            return None;
+        }
+        let mut result = 0i64;
+        for &c in self.chars.slice(self.pos, self.pos + n).iter() {
+            if c < b'0' || c > b'9' {
+                return None;
+            }
+            result = result * 10 + (c - b'0') as i64;
+        }
+        self.pos += n;
+        Some(result)
+    }
+
+    fn accept(&mut self, c: u8) -> bool {
+        if self.pos < self.chars.len() && self.chars[self.pos] == c {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.pos == self.chars.len()
+    }
+}
+
+/// Parses ISO-8601 dates (`2014-08-17`) and date times (`2014-08-17T12:30:00.5+02:00`) into seconds since
+/// the Unix epoch. Times without timezone are treated as UTC.
+pub fn parse_iso8601(s: &str) -> Option<f64> {
+    let mut sc = Scanner{chars: s.trim().as_bytes(), pos: 0};
+
+    let year = try_opt!(sc.digits(4));
+    if !sc.accept(b'-') {
+        return None;
+    }
+    let month = try_opt!(sc.digits(2));
+    if !sc.accept(b'-') {
+        return None;
+    }
+    let day = try_opt!(sc.digits(2));
+    if month < 1 || month > 12 || day < 1 || day > 31 {
+        return None;
+    }
+    let mut result = days_from_civil(year, month, day) as f64 * DAY;
+    if sc.done() {
+        return Some(result);
+    }
+
+    if !sc.accept(b'T') && !sc.accept(b' ') {
+        return None;
+    }
+    let hour = try_opt!(sc.digits(2));
+    if !sc.accept(b':') {
+        return None;
+    }
+    let minute = try_opt!(sc.digits(2));
+    let mut second = 0f64;
+    if sc.accept(b':') {
+        second = try_opt!(sc.digits(2)) as f64;
+        if sc.accept(b'.') || sc.accept(b',') {
+            let mut scale = 0.1f64;
+            let mut any = false;
+            loop {
+                match sc.digits(1) {
+                    Some(d) => {
+                        second += d as f64 * scale;
+                        scale /= 10f64;
+                        any = true;
+                    },
+                    None => break
+                }
+            }
+            if !any {
+                return None;
+            }
+        }
+    }
+    if hour > 24 || minute > 59 || second >= 61f64 {
+        return None;
+    }
+    result += hour as f64 * HOUR + minute as f64 * MINUTE + second;
+
+    if sc.accept(b'Z') {
+        // UTC
+    } else if sc.accept(b'+') || sc.accept(b'-') {
+        let sign = if sc.chars[sc.pos - 1] == b'+' { 1f64 } else { -1f64 };
+        let oh = try_opt!(sc.digits(2));
+        sc.accept(b':');
+        let om = sc.digits(2).unwrap_or(0);
+        result -= sign * (oh as f64 * HOUR + om as f64 * MINUTE);
+    }
+
+    if sc.done() {
+        Some(result)
+    } else {
+        None
+    }
+}
+
+// lower case words of a column name, split at `_`, `-`, other punctuation and camel case humps,
+// so `created_ts` and `startTime` match but `counts` and `update` do not
+fn name_tokens(name: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut prevLower = false;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            prevLower = false;
+            if !current.is_empty() {
+                tokens.push(current.clone());
+                current.truncate(0);
+            }
+            continue;
+        }
+        if c.is_uppercase() && prevLower && !current.is_empty() {
+            tokens.push(current.clone());
+            current.truncate(0);
+        }
+        prevLower = c.is_lowercase() || c.is_digit();
+        current.push_char(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Whether the name of a column suggests that it holds Unix timestamps.
+pub fn epoch_name(name: &String) -> bool {
+    let tokens = name_tokens(name.as_slice());
+    ["time", "date", "datetime", "epoch", "timestamp", "ts"].iter()
+        .any(|n| tokens.iter().any(|t| t.as_slice() == *n))
+}
+
+/// Checks if the values of a column look like Unix timestamps and returns the factor that converts
+/// them to seconds (1 for seconds, 0.001 for milliseconds).
+pub fn epoch_factor(data: &Vec<f64>) -> Option<f64> {
+    let mut scan = EpochScan::new();
+    for &x in data.iter() {
+        scan.add(x);
+    }
+    scan.factor()
+}
+
+/// `epoch_factor` one value at a time, for columns that are not kept in memory.
+pub struct EpochScan {
+    factor: Option<f64>,
+    // a value was no timestamp, or seconds and milliseconds were mixed
+    mismatch: bool,
+}
+
+impl EpochScan {
+    pub fn new() -> EpochScan {
+        EpochScan {
+            factor: None,
+            mismatch: false,
+        }
+    }
+
+    pub fn add(&mut self, x: f64) {
+        if self.mismatch || x.is_nan() {
+            return;
+        }
+        let f = if x >= EPOCH_MIN && x <= EPOCH_MAX {
+            1f64
+        } else if x >= EPOCH_MIN * 1000f64 && x <= EPOCH_MAX * 1000f64 {
+            0.001f64
+        } else {
+            self.mismatch = true;
+            return;
+        };
+        if self.factor.is_some() && self.factor != Some(f) {
+            self.mismatch = true;
+        }
+        self.factor = Some(f);
+    }
+
+    pub fn factor(&self) -> Option<f64> {
+        if self.mismatch {
+            None
+        } else {
+            self.factor
+        }
+    }
+}
+
+fn nice_subsecond(x: f64) -> f64 {
+    let exp = x.log10().floor();
+    let f = x / 10f64.powf(exp);
+    let nf = if f < 1.5f64 {
+        1f64
+    } else if f < 3f64 {
+        2f64
+    } else if f < 7f64 {
+        5f64
+    } else {
+        10f64
+    };
+    nf * 10f64.powf(exp)
+}
+
+fn format_time(t: f64, unit: TimeUnit, nfrac: uint) -> String {
+    let (year, month, day, hour, minute, second) = split_timestamp(t);
+    match unit {
+        UnitYear => format!("{:04}", year),
+        UnitMonth => format!("{:04}-{:02}", year, month),
+        UnitDay => format!("{:04}-{:02}-{:02}", year, month, day),
+        UnitHour => format!("{:02}-{:02} {:02}:{:02}", month, day, hour, minute),
+        UnitMinute => format!("{:02}:{:02}", hour, minute),
+        UnitSecond => format!("{:02}:{:02}:{:02}", hour, minute, second.round() as i64),
+        UnitSubSecond => {
+            let secText = f64::to_str_digits(second, nfrac);
+            let pad = if second < 10f64 { "0" } else { "" };
+            format!("{:02}:{:02}:{}{}", hour, minute, pad, secText)
+        }
+    }
+}
+
+/// Formats seconds since the epoch as ISO-8601 in UTC, fractional seconds are kept to milliseconds.
+pub fn format_iso8601(t: f64) -> String {
+    if !t.is_finite() {
+        return t.to_string();
+    }
+    let (year, month, day, hour, minute, second) = split_timestamp(t);
+    let millis = (second * 1000f64).round() as i64;
+    if millis % 1000 == 0 {
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, hour, minute, millis / 1000)
+    } else {
+        format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z", year, month, day, hour, minute, millis / 1000, millis % 1000)
+    }
+}
+
+/// Generates calendar aligned ticks between `min` and `max` (seconds since the epoch) for at most
+/// `ntick` ticks, together with labels.
+pub fn calc_time_markers(min: f64, max: f64, ntick: i32) -> Vec<(f64, String)> {
+    let mut markers = Vec::new();
+    if !min.is_finite() || !max.is_finite() {
+        return markers;
+    }
+    let span = max - min;
+    if !(span > 0f64) {
+        markers.push((min, format_time(min, UnitSecond, 0)));
+        return markers;
+    }
+    let target = span / ([ntick - 1, 1].iter().max().unwrap().clone() as f64);
+
+    if target < 1f64 {
+        let d = nice_subsecond(target);
+        let nfrac = [0i32, -d.log10().floor() as i32].iter().max().unwrap().clone() as uint;
+        let mut t = (min / d).ceil() * d;
+        while t <= max {
+            markers.push((t, format_time(t, UnitSubSecond, nfrac)));
+            // the step is lost in the rounding of large timestamps
+            if t + d == t {
+                break;
+            }
+            t += d;
+        }
+        return markers;
+    }
+
+    let step = STEPS.iter().find(|&&(_, _, approx)| approx >= target);
+    match step {
+        Some(&(UnitMonth, count, _)) => {
+            let (y, m, _, _, _, _) = split_timestamp(min);
+            let mut monthIdx = y * 12 + (m - 1);
+            monthIdx = (monthIdx + count - 1) / count * count;
+            loop {
+                let t = days_from_civil(monthIdx / 12, monthIdx % 12 + 1, 1) as f64 * DAY;
+                if t > max {
+                    break;
+                }
+                if t >= min {
+                    markers.push((t, format_time(t, UnitMonth, 0)));
+                }
+                monthIdx += count;
+            }
+        },
+        Some(&(unit, _, approx)) => {
+            let mut t = (min / approx).ceil() * approx;
+            while t <= max {
+                markers.push((t, format_time(t, unit, 0)));
+                if t + approx == t {
+                    break;
+                }
+                t += approx;
+            }
+        },
+        None => {
+            let count = nice_subsecond(target / YEAR).ceil().max(1f64) as i64;
+            let (y, _, _, _, _, _) = split_timestamp(min);
+            let mut year = (y + count - 1) / count * count;
+            loop {
+                let t = days_from_civil(year, 1, 1) as f64 * DAY;
+                if t > max {
+                    break;
+                }
+                if t >= min {
+                    markers.push((t, format_time(t, UnitYear, 0)));
+                }
+                year += count;
+            }
+        }
+    }
+
+    markers
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::Float;
+    use super::{calc_time_markers, days_from_civil, epoch_factor, epoch_name, format_iso8601, name_tokens, parse_iso8601, EpochScan, DAY};
+
+    #[test]
+    fn parse_dates_and_times() {
+        assert_eq!(parse_iso8601("1970-01-01"), Some(0f64));
+        assert_eq!(parse_iso8601("2014-08-17"), Some(1408233600f64));
+        assert_eq!(parse_iso8601(" 2014-08-17T10:30Z "), Some(1408271400f64));
+        assert_eq!(parse_iso8601("2014-08-17 12:30:00.5+02:00"), Some(1408271400.5f64));
+        assert_eq!(parse_iso8601("2014-08-17T07:00:00-0330"), Some(1408271400f64));
+        assert_eq!(parse_iso8601("1969-12-31T23:59:59"), Some(-1f64));
+    }
+
+    #[test]
+    fn reject_invalid() {
+        for s in ["", "2014", "2014-13-01", "2014-08-32", "2014-08-17T", "2014-08-17T25:00", "2014-08-17T10:30:00.", "17.08.2014", "2014-08-17x", "12345"].iter() {
+            assert_eq!(parse_iso8601(*s), None);
+        }
+    }
+
+    #[test]
+    fn format_round_trip() {
+        assert_eq!(format_iso8601(1408271400f64), "2014-08-17T10:30:00Z".to_string());
+        assert_eq!(format_iso8601(1408271400.25f64), "2014-08-17T10:30:00.250Z".to_string());
+        assert_eq!(format_iso8601(-1f64), "1969-12-31T23:59:59Z".to_string());
+        for &t in [0f64, 951782400f64, 4102444799f64].iter() {
+            assert_eq!(parse_iso8601(format_iso8601(t).as_slice()), Some(t));
+        }
+        assert_eq!(days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28), 2);
+    }
+
+    #[test]
+    fn tokens() {
+        assert_eq!(name_tokens("created_ts"), vec!["created".to_string(), "ts".to_string()]);
+        assert_eq!(name_tokens("startTime"), vec!["start".to_string(), "time".to_string()]);
+        assert_eq!(name_tokens("Event-Date (UTC)"), vec!["event".to_string(), "date".to_string(), "utc".to_string()]);
+        assert_eq!(name_tokens("counts"), vec!["counts".to_string()]);
+    }
+
+    #[test]
+    fn epochs() {
+        let seconds = vec![1408271400f64, Float::nan(), 1408271460f64];
+        let millis = vec![1408271400000f64, 1408271460000f64];
+        assert!(epoch_name(&"ts".to_string()) && epoch_name(&"eventTime".to_string()));
+        assert!(!epoch_name(&"counts".to_string()) && !epoch_name(&"update".to_string()));
+        assert_eq!(epoch_factor(&seconds), Some(1f64));
+        assert_eq!(epoch_factor(&millis), Some(0.001f64));
+        assert_eq!(epoch_factor(&vec![1f64, 2f64]), None);
+        assert_eq!(epoch_factor(&vec![1408271400f64, 1408271400000f64]), None);
+        assert_eq!(epoch_factor(&vec![Float::nan()]), None);
+
+        // a mismatch is final, like in the whole column
+        let mut scan = EpochScan::new();
+        for &x in [1408271400f64, 5f64, 1408271460f64].iter() {
+            scan.add(x);
+        }
+        assert_eq!(scan.factor(), None);
+    }
+
+    #[test]
+    fn markers_align_to_calendar() {
+        let start = 1408233600f64;
+        let hours: Vec<String> = calc_time_markers(start, start + DAY, 5).move_iter().map(|(_, label)| label).collect();
+        assert_eq!(hours, vec!["08-17 00:00".to_string(), "08-17 06:00".to_string(), "08-17 12:00".to_string(), "08-17 18:00".to_string(), "08-18 00:00".to_string()]);
+
+        let months: Vec<String> = calc_time_markers(parse_iso8601("2014-01-15").unwrap(), parse_iso8601("2014-12-15").unwrap(), 5).move_iter().map(|(_, label)| label).collect();
+        assert_eq!(months, vec!["2014-04".to_string(), "2014-07".to_string(), "2014-10".to_string()]);
+
+        assert_eq!(calc_time_markers(start, start, 5).len(), 1);
+    }
+
+    #[test]
+    fn markers_of_odd_ranges() {
+        let nan: f64 = Float::nan();
+        let inf: f64 = Float::infinity();
+        assert!(calc_time_markers(nan, 1f64, 5).is_empty());
+        assert!(calc_time_markers(0f64, inf, 5).is_empty());
+        // steps of half a second vanish next to a timestamp this large
+        assert_eq!(calc_time_markers(1e16f64, 1e16f64 + 2f64, 5).len(), 1);
+    }
+}
@@ -0,0 +1,432 @@
+use datetime;
+
+static SNIFF_LINES: uint = 64u;
+static SEPARATOR_CANDIDATES: [char, ..4] = [',', ';', '\t', '|'];
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Separator {
+    SeparatorChar(char),
+    SeparatorWhitespace,
+}
+
+#[deriving(Clone, Show)]
+pub struct Dialect {
+    pub separator: Separator,
+    pub quote: Option<char>,
+    pub comment: Option<char>,
+    pub decimalComma: bool,
+    pub hasHeader: bool,
+    pub hasUnits: bool,
+}
+
+/// Settings forced by the user, everything that is `None` gets sniffed.
+#[deriving(Clone)]
+pub struct DialectHints {
+    pub separator: Option<Separator>,
+    pub quote: Option<char>,
+    pub comment: Option<char>,
+    pub decimalComma: Option<bool>,
+    pub hasHeader: Option<bool>,
+    pub hasUnits: Option<bool>,
+}
+
+impl DialectHints {
+    pub fn none() -> DialectHints {
+        DialectHints {
+            separator: None,
+            quote: None,
+            comment: None,
+            decimalComma: None,
+            hasHeader: None,
+            hasUnits: None,
+        }
+    }
+}
+
+pub fn parse_separator(s: &str) -> Option<Separator> {
+    match s {
+        "tab" | "\\t" => Some(SeparatorChar('\t')),
+        "whitespace" | "space" => Some(SeparatorWhitespace),
+        _ => s.chars().next().map(|c| SeparatorChar(c))
+    }
+}
+
+fn parse_number_raw(field: &str, decimalComma: bool) -> Option<f64> {
+    let trimmed = field.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if decimalComma {
+        if trimmed.contains_char('.') {
+            return None;
+        }
+        from_str::<f64>(trimmed.replace(",", ".").as_slice())
+    } else {
+        from_str::<f64>(trimmed)
+    }
+}
+
+fn looks_like_decimal_comma(field: &str) -> bool {
+    let trimmed = field.trim();
+    let mut digitsBefore = 0u;
+    let mut digitsAfter = 0u;
+    let mut commas = 0u;
+    for (i, c) in trimmed.chars().enumerate() {
+        match c {
+            '-' | '+' if i == 0 => (),
+            ',' => commas += 1,
+            _ if c.is_digit() => {
+                if commas == 0 {
+                    digitsBefore += 1;
+                } else {
+                    digitsAfter += 1;
+                }
+            },
+            'e' | 'E' if commas == 1 && digitsAfter > 0 => return from_str::<f64>(trimmed.replace(",", ".").as_slice()).is_some(),
+            _ => return false
+        }
+    }
+    commas == 1 && digitsBefore > 0 && digitsAfter > 0
+}
+
+impl Dialect {
+    /// Guesses the dialect from the first lines of a file.
+    pub fn sniff(sample: &str, hints: &DialectHints) -> Dialect {
+        let comment = match hints.comment {
+            Some(c) => Some(c),
+            None => {
+                if sample.lines().any(|l| l.trim_left().starts_with("#")) {
+                    Some('#')
+                } else {
+                    None
+                }
+            }
+        };
+
+        let lines: Vec<&str> = sample.lines()
+            .map(|l| l.trim_right_chars('\r'))
+            .filter(|l| !l.trim().is_empty())
+            .filter(|l| match comment {
+                Some(c) => !l.trim_left().starts_with(String::from_char(1, c).as_slice()),
+                None => true
+            })
+            .take(SNIFF_LINES)
+            .collect();
+
+        let quote = match hints.quote {
+            Some(q) => Some(q),
+            None => {
+                let ndouble = sample.chars().filter(|&c| c == '"').count();
+                let nsingle = sample.chars().filter(|&c| c == '\'').count();
+                if ndouble == 0 && nsingle > 0 && nsingle % 2 == 0 {
+                    Some('\'')
+                } else {
+                    Some('"')
+                }
+            }
+        };
+
+        let separator = match hints.separator {
+            Some(ref s) => s.clone(),
+            None => sniff_separator(lines.as_slice(), quote)
+        };
+
+        let mut dialect = Dialect {
+            separator: separator,
+            quote: quote,
+            comment: comment,
+            decimalComma: false,
+            hasHeader: true,
+            hasUnits: false,
+        };
+
+        // the header line may contain anything, so skip it for the decimal check if possible
+        dialect.decimalComma = match hints.decimalComma {
+            Some(b) => b,
+            None => {
+                if dialect.separator == SeparatorChar(',') {
+                    false
+                } else {
+                    let mut commaFields = 0u;
+                    let mut pointFields = 0u;
+                    for line in lines.iter().skip(1) {
+                        for field in dialect.split(*line).iter() {
+                            if looks_like_decimal_comma(field.as_slice()) {
+                                commaFields += 1;
+                            } else if parse_number_raw(field.as_slice(), false).is_some() && field.as_slice().contains_char('.') {
+                                pointFields += 1;
+                            }
+                        }
+                    }
+                    commaFields > 0 && pointFields == 0
+                }
+            }
+        };
+
+        dialect.hasHeader = match hints.hasHeader {
+            Some(b) => b,
+            None => {
+                match lines.as_slice().head() {
+                    Some(first) => {
+                        // a header is present if the first line contains anything that is not a number
+                        // while the following lines look like data
+                        let firstFields = dialect.split(*first);
+                        let firstNonNumeric = firstFields.iter().filter(|f| !dialect.is_value(f.as_slice()) && !f.as_slice().trim().is_empty()).count();
+                        let restNumeric = lines.iter().skip(1).any(|l| dialect.split(*l).iter().any(|f| dialect.is_value(f.as_slice())));
+                        firstNonNumeric > 0 && (restNumeric || lines.len() == 1)
+                    },
+                    None => true
+                }
+            }
+        };
+
+        // units are a second non-numeric line directly below the header
+        dialect.hasUnits = match hints.hasUnits {
+            Some(b) => b,
+            None => {
+                dialect.hasHeader && lines.len() > 2 && {
+                    let fields = dialect.split(lines[1]);
+                    let allText = fields.iter().all(|f| !dialect.is_value(f.as_slice()));
+                    let anyText = fields.iter().any(|f| !f.as_slice().trim().is_empty());
+                    let restNumeric = dialect.split(lines[2]).iter().any(|f| dialect.is_value(f.as_slice()));
+                    allText && anyText && restNumeric
+                }
+            }
+        };
+
+        dialect
+    }
+
+    pub fn is_comment(&self, line: &str) -> bool {
+        match self.comment {
+            Some(c) => line.trim_left().starts_with(String::from_char(1, c).as_slice()),
+            None => false
+        }
+    }
+
+    /// Checks if the line ends in the middle of a quoted field, so the record continues on the next line.
+    pub fn is_incomplete(&self, line: &str) -> bool {
+        match self.quote {
+            Some(q) => line.chars().filter(|&c| c == q).count() % 2 == 1,
+            None => false
+        }
+    }
+
+    /// Splits one record into its fields and removes quotes.
+    pub fn split(&self, line: &str) -> Vec<String> {
+        let mut fields = Vec::new();
+        let mut current = String::new();
+        let mut inQuotes = false;
+        let mut lastWasQuote = false;
+        let mut inWhitespace = false;
+
+        for c in line.trim_right_chars('\r').chars() {
+            if Some(c) == self.quote {
+                if inQuotes {
+                    inQuotes = false;
+                    lastWasQuote = true;
+                } else {
+                    if lastWasQuote {
+                        // escaped quote: ""
+                        current.push_char(c);
+                    }
+                    inQuotes = true;
+                    lastWasQuote = false;
+                }
+                inWhitespace = false;
+                continue;
+            }
+            lastWasQuote = false;
+
+            if inQuotes {
+                current.push_char(c);
+                continue;
+            }
+
+            match self.separator {
+                SeparatorChar(s) if c == s => {
+                    fields.push(current);
+                    current = String::new();
+                },
+                SeparatorWhitespace if c.is_whitespace() => {
+                    if !inWhitespace && !(fields.is_empty() && current.is_empty()) {
+                        fields.push(current);
+                        current = String::new();
+                    }
+                    inWhitespace = true;
+                },
+                _ => {
+                    inWhitespace = false;
+                    current.push_char(c);
+                }
+            }
+        }
+        if !(self.separator == SeparatorWhitespace && inWhitespace) {
+            fields.push(current);
+        }
+
+        fields
+    }
+
+    /// Parses a single field, missing and unparsable values result in `None`.
+    pub fn parse_number(&self, field: &str) -> Option<f64> {
+        parse_number_raw(field, self.decimalComma)
+    }
+
+    // numbers and timestamps count as values, everything else is text
+    fn is_value(&self, field: &str) -> bool {
+        self.parse_number(field).is_some() || datetime::parse_iso8601(field).is_some()
+    }
+
+    /// Strips brackets from unit fields like `[mV]` or `(s)`, empty units result in `None`.
+    pub fn parse_unit(&self, field: &str) -> Option<String> {
+        let trimmed = field.trim();
+        let stripped = if (trimmed.starts_with("[") && trimmed.ends_with("]")) || (trimmed.starts_with("(") && trimmed.ends_with(")")) {
+            trimmed.slice(1, trimmed.len() - 1).trim()
+        } else {
+            trimmed
+        };
+        if stripped.is_empty() {
+            None
+        } else {
+            Some(stripped.to_string())
+        }
+    }
+}
+
+fn sniff_separator(lines: &[&str], quote: Option<char>) -> Separator {
+    let mut best = SeparatorWhitespace;
+    let mut bestScore = (0u, 0u, 0u);
+
+    for &candidate in SEPARATOR_CANDIDATES.iter() {
+        let dialect = Dialect {
+            separator: SeparatorChar(candidate),
+            quote: quote,
+            comment: None,
+            decimalComma: false,
+            hasHeader: true,
+            hasUnits: false,
+        };
+        let rows: Vec<Vec<String>> = lines.iter().map(|l| dialect.split(*l)).collect();
+        let first = match rows.as_slice().head() {
+            Some(r) => r.len(),
+            None => continue
+        };
+        if first < 2 {
+            continue;
+        }
+
+        let consistent = rows.iter().filter(|r| r.len() == first).count();
+        if consistent * 10 < rows.len() * 9 {
+            continue;
+        }
+
+        // "1,5;2,7" splits consistently at ',' as well, so prefer the separator
+        // that produces more fields that look like numbers
+        let mut nfields = 0u;
+        let mut nnumeric = 0u;
+        for row in rows.iter().skip(1) {
+            for field in row.iter() {
+                nfields += 1;
+                if parse_number_raw(field.as_slice(), false).is_some() || looks_like_decimal_comma(field.as_slice()) {
+                    nnumeric += 1;
+                }
+            }
+        }
+        let numericPercent = if nfields > 0 { nnumeric * 100 / nfields } else { 0 };
+
+        let score = (consistent, numericPercent, first);
+        if score > bestScore {
+            best = SeparatorChar(candidate);
+            bestScore = score;
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Dialect, DialectHints, SeparatorChar, SeparatorWhitespace, parse_separator};
+
+    fn dialect(separator: char) -> Dialect {
+        Dialect {
+            separator: SeparatorChar(separator),
+            quote: Some('"'),
+            comment: None,
+            decimalComma: false,
+            hasHeader: true,
+            hasUnits: false,
+        }
+    }
+
+    #[test]
+    fn sniff_comma_header() {
+        let d = Dialect::sniff("a,b,c\n1,2.5,3\n4,5,6\n", &DialectHints::none());
+        assert_eq!(d.separator, SeparatorChar(','));
+        assert!(d.hasHeader);
+        assert!(!d.hasUnits);
+        assert!(!d.decimalComma);
+    }
+
+    #[test]
+    fn sniff_semicolon_decimal_comma() {
+        let d = Dialect::sniff("a;b\n1,5;2,7\n3,0;4,5\n", &DialectHints::none());
+        assert_eq!(d.separator, SeparatorChar(';'));
+        assert!(d.decimalComma);
+        assert!(d.hasHeader);
+        assert_eq!(d.parse_number("1,5"), Some(1.5f64));
+        assert_eq!(d.parse_number("1.5"), None);
+    }
+
+    #[test]
+    fn sniff_without_header() {
+        let d = Dialect::sniff("1,2\n3,4\n", &DialectHints::none());
+        assert_eq!(d.separator, SeparatorChar(','));
+        assert!(!d.hasHeader);
+    }
+
+    #[test]
+    fn sniff_units() {
+        let d = Dialect::sniff("t,v\n[s],(mV)\n0,1\n1,2\n", &DialectHints::none());
+        assert!(d.hasHeader);
+        assert!(d.hasUnits);
+        assert_eq!(d.parse_unit("[s]"), Some("s".to_string()));
+        assert_eq!(d.parse_unit("( mV )"), Some("mV".to_string()));
+        assert_eq!(d.parse_unit("[]"), None);
+    }
+
+    #[test]
+    fn sniff_comments_and_hints() {
+        let d = Dialect::sniff("# generated\nx|y\n1|2\n", &DialectHints::none());
+        assert_eq!(d.comment, Some('#'));
+        assert_eq!(d.separator, SeparatorChar('|'));
+        assert!(d.is_comment("  # note"));
+
+        let hints = DialectHints { hasHeader: Some(false), ..DialectHints::none() };
+        assert!(!Dialect::sniff("x,y\n1,2\n", &hints).hasHeader);
+    }
+
+    #[test]
+    fn split_quotes() {
+        let d = dialect(',');
+        assert_eq!(d.split("a,\"b,c\",\"say \"\"hi\"\"\""), vec!["a".to_string(), "b,c".to_string(), "say \"hi\"".to_string()]);
+        assert_eq!(d.split("1,,3\r"), vec!["1".to_string(), "".to_string(), "3".to_string()]);
+        assert!(d.is_incomplete("a,\"b"));
+        assert!(!d.is_incomplete("a,\"b\""));
+    }
+
+    #[test]
+    fn split_whitespace() {
+        let d = Dialect { separator: SeparatorWhitespace, ..dialect(',') };
+        assert_eq!(d.split("  1  2\t3 "), vec!["1".to_string(), "2".to_string(), "3".to_string()]);
+    }
+
+    #[test]
+    fn separator_names() {
+        assert_eq!(parse_separator("tab"), Some(SeparatorChar('\t')));
+        assert_eq!(parse_separator("space"), Some(SeparatorWhitespace));
+        assert_eq!(parse_separator(";"), Some(SeparatorChar(';')));
+        assert_eq!(parse_separator(""), None);
+    }
+}
@@ -0,0 +1,665 @@
+use flate;
+use serialize::base64;
+use serialize::base64::ToBase64;
+use std::cmp;
+use std::f64;
+use std::io;
+use std::path::Path;
+use textdrawer;
+
+pub type Color = [f32, ..3];
+
+/// RGBA pixels, rows from top to bottom.
+pub struct Raster {
+    pub width: uint,
+    pub height: uint,
+    pub pixels: Vec<u8>,
+}
+
+impl Raster {
+    /// Writes PNG or binary PPM depending on the extension of `path`, PPM drops the alpha.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data = match path.extension_str() {
+            Some("png") => png(self),
+            Some("ppm") => {
+                let mut data = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+                for px in self.pixels.as_slice().chunks(4) {
+                    data.push_all(px.slice(0, 3));
+                }
+                data
+            },
+            _ => return Err(format!("{}: images need a .png or .ppm file", path.display()))
+        };
+        let mut file = try!(io::File::create(path).map_err(|e| format!("{}: {}", path.display(), e)));
+        file.write(data.as_slice()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
+
+/// Coordinates are pixels of the window, y goes down.
+pub enum Shape {
+    // from, to and width
+    ShapeLine(f64, f64, f64, f64, f64),
+    // center, radius, color and opacity
+    ShapeCircle(f64, f64, f64, Color, f64),
+    // anchor, rotation in degrees (clockwise), alignment and text
+    ShapeText(f64, f64, f64, textdrawer::AnchorHor, textdrawer::AnchorVert, String),
+    // rectangle with colors from left to right
+    ShapeGradient(f64, f64, f64, f64, Vec<Color>),
+    // rectangle the raster is stretched to
+    ShapeImage(f64, f64, f64, f64, Raster),
+}
+
+/// A frame as vector shapes, independent of the output format.
+pub struct Figure {
+    pub width: f64,
+    pub height: f64,
+    pub fontSize: f64,
+    pub background: Color,
+    // lines and text
+    pub foreground: Color,
+    // plot area as x, y, width and height
+    pub clip: (f64, f64, f64, f64),
+    // point layer, clipped to the plot area
+    pub plot: Vec<Shape>,
+    // axes, labels and legend on top of the points
+    pub shapes: Vec<Shape>,
+}
+
+impl Figure {
+    pub fn new(width: f64, height: f64, fontSize: f64, background: Color, foreground: Color, clip: (f64, f64, f64, f64)) -> Figure {
+        Figure {
+            width: width,
+            height: height,
+            fontSize: fontSize,
+            background: background,
+            foreground: foreground,
+            clip: clip,
+            plot: Vec::new(),
+            shapes: Vec::new(),
+        }
+    }
+
+    pub fn line(&mut self, x0: f64, y0: f64, x1: f64, y1: f64, width: f64) {
+        self.shapes.push(ShapeLine(x0, y0, x1, y1, width));
+    }
+
+    pub fn text(&mut self, x: f64, y: f64, angle: f64, hor: textdrawer::AnchorHor, vert: textdrawer::AnchorVert, text: &str) {
+        self.shapes.push(ShapeText(x, y, angle, hor, vert, text.to_string()));
+    }
+
+    /// Writes SVG or PDF depending on the extension of `path`.
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let data = match path.extension_str() {
+            Some("svg") => self.svg().into_bytes(),
+            Some("pdf") => self.pdf(),
+            _ => return Err(format!("{}: export needs a .svg, .pdf, .png or .ppm file", path.display()))
+        };
+        let mut file = try!(io::File::create(path).map_err(|e| format!("{}: {}", path.display(), e)));
+        file.write(data.as_slice()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn svg(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str(format!("<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n", self.width, self.height).as_slice());
+        let (cx, cy, cw, ch) = self.clip;
+        out.push_str(format!("<defs><clipPath id=\"plot\"><rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/></clipPath></defs>\n", cx, cy, cw, ch).as_slice());
+        out.push_str(format!("<rect width=\"100%\" height=\"100%\" fill=\"{}\"/>\n", hex(self.background)).as_slice());
+
+        out.push_str("<g clip-path=\"url(#plot)\">\n");
+        for (i, shape) in self.plot.iter().enumerate() {
+            out.push_str(svg_shape(shape, format!("p{}", i).as_slice()).as_slice());
+        }
+        out.push_str("</g>\n");
+
+        out.push_str(format!("<g stroke=\"{0}\" fill=\"{0}\" stroke-linecap=\"round\" font-family=\"DejaVu Sans Condensed, sans-serif\" font-weight=\"bold\" font-size=\"{1}\">\n", hex(self.foreground), self.fontSize).as_slice());
+        for (i, shape) in self.shapes.iter().enumerate() {
+            out.push_str(svg_shape(shape, format!("s{}", i).as_slice()).as_slice());
+        }
+        out.push_str("</g>\n</svg>\n");
+        out
+    }
+
+    pub fn pdf(&self) -> Vec<u8> {
+        let mut pdf = Pdf::new(self.height);
+        let mut content = String::new();
+        content.push_str(format!("{} rg 0 0 {} {} re f\n", rgb(self.background), self.width, self.height).as_slice());
+
+        let (cx, cy, cw, ch) = self.clip;
+        content.push_str(format!("q {:.2} {:.2} {:.2} {:.2} re W n\n", cx, self.height - cy - ch, cw, ch).as_slice());
+        for shape in self.plot.iter() {
+            content.push_str(pdf.shape(shape, self.fontSize).as_slice());
+        }
+        content.push_str("Q\n");
+
+        content.push_str(format!("{0} rg {0} RG 1 J\n", rgb(self.foreground)).as_slice());
+        for shape in self.shapes.iter() {
+            content.push_str(pdf.shape(shape, self.fontSize).as_slice());
+        }
+        pdf.finish(self.width, content.as_slice())
+    }
+}
+
+/// Color of a Z value, same as `z2rgba` in `gradient.lib.glsl`.
+pub fn z2rgb(z: f64) -> Color {
+    // sigmoid limits z to (0,1), the hue goes from cyan to red
+    let t = 1f64 / (1f64 + (-z * 3f64).exp());
+    let h = 0.5f64 - 0.5f64 * t;
+    let mut c = [0f32, ..3];
+    for (i, &k) in [1f64, 2f64 / 3f64, 1f64 / 3f64].iter().enumerate() {
+        let x = h + k;
+        let p = ((x - x.floor()) * 6f64 - 3f64).abs();
+        c[i] = (p - 1f64).max(0f64).min(1f64) as f32;
+    }
+    c
+}
+
+/// Color and opacity of accumulated points as in `texture.fragment.glsl`, `sum` holds the
+/// colors weighted by their coverage and the coverage itself. None if nothing was drawn.
+pub fn shade(sum: [f64, ..4], count: f64, alpha: f64) -> Option<(Color, f64)> {
+    if sum[3] == 0f64 {
+        return None;
+    }
+    let full = [sum[0] / sum[3], sum[1] / sum[3], sum[2] / sum[3]];
+    let opacity = (1f64 + (sum[3] / count).powf(1f64 / alpha - 1f64)).ln() / 2f64.ln();
+    let delta = 1f64 - full[0].max(full[1]).max(full[2]);
+    let color = [(full[0] + delta) as f32, (full[1] + delta) as f32, (full[2] + delta) as f32];
+    Some((color, opacity.max(0f64).min(1f64)))
+}
+
+/// Opacity of each of `n` shapes drawn on top of each other, so that they cover `opacity` together.
+pub fn stacked_opacity(opacity: f64, n: f64) -> f64 {
+    if n <= 1f64 {
+        opacity
+    } else {
+        1f64 - (1f64 - opacity).powf(1f64 / n)
+    }
+}
+
+/// Splits `width` x `height` pixels into tiles of at most `size` pixels, as x, y, width and height.
+pub fn tiles(width: uint, height: uint, size: uint) -> Vec<(uint, uint, uint, uint)> {
+    assert!(size > 0, "tiles need a positive size");
+    let mut result = Vec::new();
+    cfor!{let mut y = 0u; y < height; (y += size) {
+        cfor!{let mut x = 0u; x < width; (x += size) {
+            result.push((x, y, cmp::min(size, width - x), cmp::min(size, height - y)));
+        }}
+    }}
+    result
+}
+
+/// Scale and offset from the normalized device coordinates of a frame `full` pixels wide to those
+/// of the part from `origin` that is `size` pixels wide.
+pub fn tile_transform(full: f32, origin: f32, size: f32) -> (f32, f32) {
+    (full / size, (full - 2f32 * origin) / size - 1f32)
+}
+
+/// Edge length of tiles without their `padding` on each side, if the GPU renders at most `limit`
+/// pixels.
+pub fn tile_size(limit: uint, padding: uint) -> Result<uint, String> {
+    if limit <= 2 * padding {
+        return Err(format!("points of {} pixels do not fit into tiles of {} pixels", 2 * padding, limit));
+    }
+    Ok(limit - 2 * padding)
+}
+
+/// Exports are saved next to the input `file` without its extension, streams are named after
+/// their address `name`.
+pub fn default_stem(file: Option<&Path>, name: &str) -> String {
+    match file {
+        Some(path) => format!("{}", path.with_extension("").display()),
+        None => name.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' }).collect()
+    }
+}
+
+fn hex(c: Color) -> String {
+    let byte = |x: f32| (x.max(0f32).min(1f32) * 255f32).round() as u8;
+    format!("#{:02x}{:02x}{:02x}", byte(c[0]), byte(c[1]), byte(c[2]))
+}
+
+fn rgb(c: Color) -> String {
+    format!("{:.3} {:.3} {:.3}", c[0], c[1], c[2])
+}
+
+// distance from the anchor down to the baseline, assuming an ascent of 3/4 and a descent of 1/4
+fn baseline(vert: textdrawer::AnchorVert, size: f64) -> f64 {
+    match vert {
+        textdrawer::Top => 0.75f64 * size,
+        textdrawer::Middle => 0.25f64 * size,
+        textdrawer::Bottom => -0.25f64 * size,
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push_char(c)
+        }
+    }
+    out
+}
+
+fn svg_shape(shape: &Shape, id: &str) -> String {
+    match *shape {
+        ShapeLine(x0, y0, x1, y1, width) => {
+            format!("<line x1=\"{:.2}\" y1=\"{:.2}\" x2=\"{:.2}\" y2=\"{:.2}\" stroke-width=\"{}\"/>\n", x0, y0, x1, y1, width)
+        },
+        ShapeCircle(x, y, r, color, opacity) => {
+            format!("<circle cx=\"{:.2}\" cy=\"{:.2}\" r=\"{:.2}\" fill=\"{}\" fill-opacity=\"{:.3}\"/>\n", x, y, r, hex(color), opacity)
+        },
+        ShapeText(x, y, angle, hor, vert, ref text) => {
+            let anchor = match hor {
+                textdrawer::Left => "start",
+                textdrawer::Center => "middle",
+                textdrawer::Right => "end",
+            };
+            // the size is inherited, the baseline is relative to a size of 1em
+            format!("<text transform=\"translate({:.2},{:.2}) rotate({})\" y=\"{:.2}em\" text-anchor=\"{}\" stroke=\"none\">{}</text>\n",
+                x, y, angle, baseline(vert, 1f64), anchor, escape_xml(text.as_slice()))
+        },
+        ShapeGradient(x, y, w, h, ref colors) => {
+            let mut out = format!("<linearGradient id=\"{}\">", id);
+            for (i, &c) in colors.iter().enumerate() {
+                let offset = if colors.len() > 1 { i as f64 / (colors.len() - 1) as f64 } else { 0f64 };
+                out.push_str(format!("<stop offset=\"{:.4}\" stop-color=\"{}\"/>", offset, hex(c)).as_slice());
+            }
+            out.push_str("</linearGradient>\n");
+            out.push_str(format!("<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" fill=\"url(#{})\" stroke=\"none\"/>\n", x, y, w, h, id).as_slice());
+            out
+        },
+        ShapeImage(x, y, w, h, ref raster) => {
+            format!("<image x=\"{:.2}\" y=\"{:.2}\" width=\"{:.2}\" height=\"{:.2}\" preserveAspectRatio=\"none\" xlink:href=\"data:image/png;base64,{}\"/>\n",
+                x, y, w, h, png(raster).as_slice().to_base64(base64::STANDARD))
+        },
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xffffffffu32;
+    for &b in data.iter() {
+        crc ^= b as u32;
+        for _ in range(0u, 8) {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb88320u32 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn push_u32(out: &mut Vec<u8>, x: u32) {
+    out.push_all([(x >> 24) as u8, (x >> 16) as u8, (x >> 8) as u8, x as u8]);
+}
+
+fn png_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    push_u32(out, data.len() as u32);
+    let mut body = kind.to_vec();
+    body.push_all(data);
+    out.push_all(body.as_slice());
+    push_u32(out, crc32(body.as_slice()));
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    flate::deflate_bytes_zlib(data).expect("deflate failed").as_slice().to_vec()
+}
+
+// RGBA with 8 bits per channel and no row filters
+fn png(raster: &Raster) -> Vec<u8> {
+    let mut rows = Vec::with_capacity((raster.width * 4 + 1) * raster.height);
+    for y in range(0, raster.height) {
+        rows.push(0u8);
+        rows.push_all(raster.pixels.slice(y * raster.width * 4, (y + 1) * raster.width * 4));
+    }
+
+    let mut out = vec![0x89u8, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+    let mut header = Vec::new();
+    push_u32(&mut header, raster.width as u32);
+    push_u32(&mut header, raster.height as u32);
+    // bit depth, RGBA, deflate, no filters, no interlace
+    header.push_all([8u8, 6, 0, 0, 0]);
+    png_chunk(&mut out, b"IHDR", header.as_slice());
+    png_chunk(&mut out, b"IDAT", deflate(rows.as_slice()).as_slice());
+    png_chunk(&mut out, b"IEND", &[]);
+    out
+}
+
+// advance widths of Helvetica for the characters 32 to 126 in 1/1000 of the font size
+static HELVETICA_WIDTHS: [u16, ..95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278,
+    556, 556, 556, 556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556,
+    1015, 667, 667, 722, 722, 667, 611, 778, 722, 278, 500, 667, 556, 833, 722, 778,
+    667, 778, 722, 667, 611, 722, 667, 944, 667, 667, 611, 278, 278, 278, 469, 556,
+    333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500, 222, 833, 556, 556,
+    556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+fn text_width(text: &str, size: f64) -> f64 {
+    let units = text.chars().fold(0u, |sum, c| {
+        let w = if c >= ' ' && c <= '~' { HELVETICA_WIDTHS[c as uint - 32] } else { 556 };
+        sum + w as uint
+    });
+    units as f64 * size / 1000f64
+}
+
+// PDF string literal, characters outside of ASCII become `?` as the font has no others
+fn pdf_string(text: &str) -> String {
+    let mut out = String::from_str("(");
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                out.push_char('\\');
+                out.push_char(c);
+            },
+            c if c >= ' ' && c <= '~' => out.push_char(c),
+            _ => out.push_char('?')
+        }
+    }
+    out.push_char(')');
+    out
+}
+
+// objects of a single page document, the page content is added last
+struct Pdf {
+    height: f64,
+    objects: Vec<Vec<u8>>,
+    // fill opacities and their graphic state objects
+    opacities: Vec<(f64, uint)>,
+    // image objects
+    images: Vec<uint>,
+}
+
+impl Pdf {
+    fn new(height: f64) -> Pdf {
+        Pdf {
+            height: height,
+            objects: Vec::new(),
+            opacities: Vec::new(),
+            images: Vec::new(),
+        }
+    }
+
+    // returns the object number
+    fn add(&mut self, object: Vec<u8>) -> uint {
+        self.objects.push(object);
+        self.objects.len()
+    }
+
+    fn add_stream(&mut self, dict: &str, data: &[u8]) -> uint {
+        let compressed = deflate(data);
+        let mut object = format!("<< {} /Filter /FlateDecode /Length {} >>\nstream\n", dict, compressed.len()).into_bytes();
+        object.push_all(compressed.as_slice());
+        object.push_all(b"\nendstream");
+        self.add(object)
+    }
+
+    // name of the graphic state setting the fill opacity
+    fn opacity(&mut self, opacity: f64) -> String {
+        // rounded, so points of the same color share a state
+        let opacity = (opacity * 1000f64).round() / 1000f64;
+        let index = match self.opacities.iter().position(|&(o, _)| o == opacity) {
+            Some(i) => i,
+            None => {
+                let object = self.add(format!("<< /Type /ExtGState /ca {:.3} >>", opacity).into_bytes());
+                self.opacities.push((opacity, object));
+                self.opacities.len() - 1
+            }
+        };
+        format!("/GS{}", index)
+    }
+
+    fn image(&mut self, raster: &Raster) -> String {
+        let n = raster.width * raster.height;
+        let mut color = Vec::with_capacity(n * 3);
+        let mut alpha = Vec::with_capacity(n);
+        for px in raster.pixels.as_slice().chunks(4) {
+            color.push_all(px.slice(0, 3));
+            alpha.push(px[3]);
+        }
+        let mask = self.add_stream(format!("/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceGray /BitsPerComponent 8",
+            raster.width, raster.height).as_slice(), alpha.as_slice());
+        let image = self.add_stream(format!("/Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB /BitsPerComponent 8 /SMask {} 0 R",
+            raster.width, raster.height, mask).as_slice(), color.as_slice());
+        self.images.push(image);
+        format!("/Im{}", self.images.len() - 1)
+    }
+
+    // content stream operators, y is flipped since PDF starts at the bottom
+    fn shape(&mut self, shape: &Shape, fontSize: f64) -> String {
+        let height = self.height;
+        match *shape {
+            ShapeLine(x0, y0, x1, y1, width) => {
+                format!("{} w {:.2} {:.2} m {:.2} {:.2} l S\n", width, x0, height - y0, x1, height - y1)
+            },
+            ShapeCircle(x, y, r, color, opacity) => {
+                // four Bezier curves
+                let k = 0.5523f64 * r;
+                let y = height - y;
+                format!("q {} gs {} rg {:.2} {:.2} m {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c {:.2} {:.2} {:.2} {:.2} {:.2} {:.2} c f Q\n",
+                    self.opacity(opacity), rgb(color),
+                    x + r, y,
+                    x + r, y + k, x + k, y + r, x, y + r,
+                    x - k, y + r, x - r, y + k, x - r, y,
+                    x - r, y - k, x - k, y - r, x, y - r,
+                    x + k, y - r, x + r, y - k, x + r, y)
+            },
+            ShapeText(x, y, angle, hor, vert, ref text) => {
+                let width = text_width(text.as_slice(), fontSize);
+                let dx = match hor {
+                    textdrawer::Left => 0f64,
+                    textdrawer::Center => -width / 2f64,
+                    textdrawer::Right => -width,
+                };
+                let dy = -baseline(vert, fontSize);
+                // clockwise on screen is counterclockwise in PDF
+                let a = -angle * f64::consts::PI / 180f64;
+                let (sin, cos) = (a.sin(), a.cos());
+                let mut out = format!("q 1 0 0 1 {:.2} {:.2} cm {:.4} {:.4} {:.4} {:.4} 0 0 cm BT /F1 {} Tf {:.2} {:.2} Td ",
+                    x, height - y, cos, sin, -sin, cos, fontSize, dx, dy);
+                out.push_str(pdf_string(text.as_slice()).as_slice());
+                out.push_str(" Tj ET Q\n");
+                out
+            },
+            ShapeGradient(x, y, w, h, ref colors) => {
+                let step = w / colors.len() as f64;
+                let mut out = String::from_str("q ");
+                for (i, &c) in colors.iter().enumerate() {
+                    // slightly wider, so there are no gaps between the stripes
+                    out.push_str(format!("{} rg {:.2} {:.2} {:.2} {:.2} re f ", rgb(c), x + i as f64 * step, height - y - h, step + 0.5f64, h).as_slice());
+                }
+                out.push_str("Q\n");
+                out
+            },
+            ShapeImage(x, y, w, h, ref raster) => {
+                format!("q {:.2} 0 0 {:.2} {:.2} {:.2} cm {} Do Q\n", w, h, x, height - y - h, self.image(raster))
+            },
+        }
+    }
+
+    fn finish(mut self, width: f64, content: &str) -> Vec<u8> {
+        let contents = self.add_stream("", content.as_bytes());
+        let font = self.add(b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_vec());
+
+        let mut resources = format!("<< /Font << /F1 {} 0 R >>", font);
+        resources.push_str(" /ExtGState <<");
+        for (i, &(_, object)) in self.opacities.iter().enumerate() {
+            resources.push_str(format!(" /GS{} {} 0 R", i, object).as_slice());
+        }
+        resources.push_str(" >> /XObject <<");
+        for (i, &object) in self.images.iter().enumerate() {
+            resources.push_str(format!(" /Im{} {} 0 R", i, object).as_slice());
+        }
+        resources.push_str(" >> >>");
+
+        // the page tree is the object after the page
+        let pages = self.objects.len() + 2;
+        let page = self.add(format!("<< /Type /Page /Parent {} 0 R /MediaBox [0 0 {} {}] /Resources {} /Contents {} 0 R >>",
+            pages, width, self.height, resources, contents).into_bytes());
+        self.add(format!("<< /Type /Pages /Kids [{} 0 R] /Count 1 >>", page).into_bytes());
+        let catalog = self.add(format!("<< /Type /Catalog /Pages {} 0 R >>", pages).into_bytes());
+
+        let mut out = b"%PDF-1.4\n".to_vec();
+        let mut offsets = Vec::new();
+        for (i, object) in self.objects.iter().enumerate() {
+            offsets.push(out.len());
+            out.push_all(format!("{} 0 obj\n", i + 1).as_bytes());
+            out.push_all(object.as_slice());
+            out.push_all(b"\nendobj\n");
+        }
+        let xref = out.len();
+        out.push_all(format!("xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1).as_bytes());
+        for offset in offsets.iter() {
+            out.push_all(format!("{:010} 00000 n \n", *offset).as_bytes());
+        }
+        out.push_all(format!("trailer\n<< /Size {} /Root {} 0 R >>\nstartxref\n{}\n%%EOF\n", offsets.len() + 1, catalog, xref).as_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use flate;
+    use std::str;
+    use super::{crc32, default_stem, png, stacked_opacity, tile_size, tile_transform, tiles, Figure, Raster, ShapeCircle, ShapeImage};
+    use textdrawer;
+
+    fn u32_at(data: &[u8], pos: uint) -> u32 {
+        data.slice(pos, pos + 4).iter().fold(0u32, |x, &b| (x << 8) | b as u32)
+    }
+
+    fn small_raster() -> Raster {
+        Raster {
+            width: 3,
+            height: 2,
+            pixels: range(0u, 24).map(|i| (i * 10) as u8).collect(),
+        }
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xcbf43926u32);
+        assert_eq!(crc32(b""), 0u32);
+    }
+
+    #[test]
+    fn png_chunks() {
+        let raster = small_raster();
+        let data = png(&raster);
+        assert_eq!(data.slice_to(8), [0x89u8, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'].as_slice());
+
+        // every chunk is length, type, data and the CRC of type and data
+        let mut pos = 8u;
+        let mut kinds = Vec::new();
+        let mut idat = Vec::new();
+        while pos < data.len() {
+            let len = u32_at(data.as_slice(), pos) as uint;
+            let kind = data.slice(pos + 4, pos + 8);
+            let body = data.slice(pos + 8, pos + 8 + len);
+            assert_eq!(u32_at(data.as_slice(), pos + 8 + len), crc32(data.slice(pos + 4, pos + 8 + len)));
+            match kind {
+                b"IHDR" => {
+                    assert_eq!(len, 13);
+                    assert_eq!(u32_at(body, 0), 3);
+                    assert_eq!(u32_at(body, 4), 2);
+                    assert_eq!(body.slice_from(8), [8u8, 6, 0, 0, 0].as_slice());
+                },
+                b"IDAT" => idat.push_all(body),
+                _ => ()
+            }
+            kinds.push(str::from_utf8(kind).unwrap().to_string());
+            pos += 12 + len;
+        }
+        assert_eq!(pos, data.len());
+        assert_eq!(kinds, vec!["IHDR".to_string(), "IDAT".to_string(), "IEND".to_string()]);
+
+        // rows start with filter type 0 followed by the RGBA pixels
+        let rows = flate::inflate_bytes_zlib(idat.as_slice()).unwrap();
+        assert_eq!(rows.as_slice().len(), 2 * (1 + 3 * 4));
+        assert_eq!(rows.as_slice()[0], 0);
+        assert_eq!(rows.as_slice().slice(1, 13), raster.pixels.slice(0, 12));
+        assert_eq!(rows.as_slice()[13], 0);
+        assert_eq!(rows.as_slice().slice(14, 26), raster.pixels.slice(12, 24));
+    }
+
+    #[test]
+    fn pdf_xref_points_to_objects() {
+        let mut figure = Figure::new(200f64, 100f64, 12f64, [1f32, 1f32, 1f32], [0f32, 0f32, 0f32], (10f64, 10f64, 180f64, 80f64));
+        figure.plot.push(ShapeCircle(50f64, 50f64, 2f64, [1f32, 0f32, 0f32], 0.5f64));
+        figure.plot.push(ShapeImage(10f64, 10f64, 180f64, 80f64, small_raster()));
+        figure.line(10f64, 90f64, 190f64, 90f64, 1f64);
+        figure.text(100f64, 95f64, 0f64, textdrawer::Center, textdrawer::Top, "x (€)");
+        let data = figure.pdf();
+        assert!(data.as_slice().starts_with(b"%PDF-1.4\n"));
+        assert!(data.as_slice().ends_with(b"%%EOF\n"));
+
+        let tail = String::from_utf8_lossy(data.slice_from(data.len() - 64)).into_string();
+        let start = tail.as_slice().find_str("startxref\n").unwrap() + "startxref\n".len();
+        let xref = from_str::<uint>(tail.as_slice().slice_from(start).lines().next().unwrap()).unwrap();
+        assert!(data.slice_from(xref).starts_with(b"xref\n0 "));
+
+        // "xref\n0 N\n" followed by N entries of 20 bytes, the first one free
+        let line = str::from_utf8(data.slice_from(xref + 7)).unwrap().lines().next().unwrap();
+        let count = from_str::<uint>(line).unwrap();
+        let entries = xref + 7 + line.len() + 1;
+        assert!(count > 1);
+        assert!(data.slice_from(entries).starts_with(b"0000000000 65535 f \n"));
+        for i in range(1u, count) {
+            let entry = entries + 20 * i;
+            let offset = from_str::<uint>(str::from_utf8(data.slice(entry, entry + 10)).unwrap()).unwrap();
+            assert!(data.slice_from(offset).starts_with(format!("{} 0 obj\n", i).as_bytes()));
+        }
+    }
+
+    #[test]
+    fn svg_is_well_formed() {
+        let mut figure = Figure::new(200f64, 100f64, 12f64, [1f32, 1f32, 1f32], [0f32, 0f32, 0f32], (10f64, 10f64, 180f64, 80f64));
+        figure.text(100f64, 95f64, 0f64, textdrawer::Center, textdrawer::Top, "a < b & c");
+        let svg = figure.svg();
+        assert!(svg.as_slice().starts_with("<?xml"));
+        assert!(svg.as_slice().contains("a &lt; b &amp; c"));
+        assert!(svg.as_slice().ends_with("</svg>\n"));
+    }
+
+    #[test]
+    fn tiles_cover_the_frame() {
+        let parts = tiles(5, 3, 2);
+        assert_eq!(parts, vec![(0, 0, 2, 2), (2, 0, 2, 2), (4, 0, 1, 2), (0, 2, 2, 1), (2, 2, 2, 1), (4, 2, 1, 1)]);
+        assert_eq!(tiles(4, 4, 4), vec![(0, 0, 4, 4)]);
+        assert!(tiles(0, 4, 4).is_empty());
+    }
+
+    #[test]
+    fn tile_transform_maps_the_part() {
+        // the tile from 100 to 300 of a 400 pixel frame
+        let (s, o) = tile_transform(400f32, 100f32, 200f32);
+        let to_tile = |ndc: f32| s * ndc + o;
+        // frame positions 100 and 300 become the edges of the tile
+        assert_eq!(to_tile(-0.5f32), -1f32);
+        assert_eq!(to_tile(0.5f32), 1f32);
+        assert_eq!(tile_transform(400f32, 0f32, 400f32), (1f32, 0f32));
+    }
+
+    #[test]
+    fn tile_sizes() {
+        assert_eq!(tile_size(2048, 10), Ok(2028));
+        assert!(tile_size(20, 10).is_err());
+        assert!(tile_size(0, 0).is_err());
+    }
+
+    #[test]
+    fn export_names() {
+        assert_eq!(default_stem(Some(&Path::new("dir/data.csv")), "data.csv"), "dir/data".to_string());
+        assert_eq!(default_stem(Some(&Path::new("data")), "data"), "data".to_string());
+        assert_eq!(default_stem(None, "tcp:127.0.0.1:7000"), "tcp_127_0_0_1_7000".to_string());
+        assert_eq!(default_stem(None, "/tmp/my-fifo"), "_tmp_my-fifo".to_string());
+    }
+
+    #[test]
+    fn stacked_opacity_adds_up() {
+        let each = stacked_opacity(0.6f64, 5f64);
+        assert!((1f64 - (1f64 - each).powi(5) - 0.6f64).abs() < 1e-12);
+        assert_eq!(stacked_opacity(0.6f64, 1f64), 0.6f64);
+    }
+}
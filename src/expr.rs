@@ -0,0 +1,645 @@
+use data;
+use normalize;
+use std::f64;
+use std::fmt;
+use std::num::Float;
+
+#[deriving(Clone, PartialEq)]
+enum Token {
+    TokNumber(f64),
+    TokIdent(String),
+    // quoted column name, never a function or constant
+    TokQuoted(String),
+    TokOp(&'static str),
+    TokLParen,
+    TokRParen,
+    TokComma,
+    TokAssign,
+    TokSemicolon,
+}
+
+// shown as written, for error messages
+impl fmt::Show for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TokNumber(x) => write!(f, "{}", x),
+            TokIdent(ref name) => write!(f, "{}", name),
+            TokQuoted(ref name) => write!(f, "\"{}\"", name.replace("\"", "\"\"")),
+            TokOp(op) => write!(f, "{}", op),
+            TokLParen => write!(f, "("),
+            TokRParen => write!(f, ")"),
+            TokComma => write!(f, ","),
+            TokAssign => write!(f, "="),
+            TokSemicolon => write!(f, ";"),
+        }
+    }
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum UnaryOp {
+    OpNeg,
+    OpNot,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum BinaryOp {
+    OpAdd,
+    OpSub,
+    OpMul,
+    OpDiv,
+    OpMod,
+    OpPow,
+    OpLt,
+    OpLe,
+    OpGt,
+    OpGe,
+    OpEq,
+    OpNe,
+    OpAnd,
+    OpOr,
+}
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum Expr {
+    ExprNumber(f64),
+    ExprColumn(String),
+    ExprUnary(UnaryOp, Box<Expr>),
+    ExprBinary(BinaryOp, Box<Expr>, Box<Expr>),
+    ExprCall(String, Vec<Expr>),
+}
+
+/// A derived column, e.g. `ratio = a / b`.
+#[deriving(Clone, Show)]
+pub struct Definition {
+    pub name: String,
+    pub expr: Expr,
+    pub source: String,
+}
+
+static OPERATORS: [&'static str, ..16] = ["&&", "||", "<=", ">=", "==", "!=", "<", ">", "+", "-", "*", "/", "%", "^", "!", "="];
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0u;
+
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_digit() || (c == '.' && i + 1 < chars.len() && chars[i + 1].is_digit()) {
+            let start = i;
+            while i < chars.len() && (chars[i].is_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                i += 1;
+                if i < chars.len() && (chars[i] == '+' || chars[i] == '-') {
+                    i += 1;
+                }
+                while i < chars.len() && chars[i].is_digit() {
+                    i += 1;
+                }
+            }
+            let text: String = chars.slice(start, i).iter().map(|&c| c).collect();
+            match from_str::<f64>(text.as_slice()) {
+                Some(x) => tokens.push(TokNumber(x)),
+                None => return Err(format!("invalid number: {}", text))
+            }
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                i += 1;
+            }
+            tokens.push(TokIdent(chars.slice(start, i).iter().map(|&c| c).collect()));
+        } else if c == '"' || c == '`' {
+            // quoted column names, the quote character is escaped by doubling it
+            let quote = c;
+            let mut name = String::new();
+            i += 1;
+            loop {
+                if i >= chars.len() {
+                    return Err(format!("unterminated column name: {}{}", quote, name));
+                }
+                if chars[i] == quote {
+                    if i + 1 < chars.len() && chars[i + 1] == quote {
+                        name.push_char(quote);
+                        i += 2;
+                    } else {
+                        i += 1;
+                        break;
+                    }
+                } else {
+                    name.push_char(chars[i]);
+                    i += 1;
+                }
+            }
+            tokens.push(TokQuoted(name));
+        } else if c == '(' {
+            tokens.push(TokLParen);
+            i += 1;
+        } else if c == ')' {
+            tokens.push(TokRParen);
+            i += 1;
+        } else if c == ',' {
+            tokens.push(TokComma);
+            i += 1;
+        } else if c == ';' {
+            tokens.push(TokSemicolon);
+            i += 1;
+        } else {
+            let rest: String = chars.slice_from(i).iter().take(2).map(|&c| c).collect();
+            match OPERATORS.iter().find(|op| rest.as_slice().starts_with(**op)) {
+                Some(op) if *op == "=" => {
+                    tokens.push(TokAssign);
+                    i += 1;
+                },
+                Some(op) => {
+                    tokens.push(TokOp(*op));
+                    i += op.len();
+                },
+                None => return Err(format!("unexpected character: {}", c))
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: uint,
+}
+
+impl Parser {
+    fn peek<'a>(&'a self) -> Option<&'a Token> {
+        if self.pos < self.tokens.len() {
+            Some(&self.tokens[self.pos])
+        } else {
+            None
+        }
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let result = self.peek().map(|t| t.clone());
+        self.pos += 1;
+        result
+    }
+
+    fn accept_op(&mut self, op: &str) -> bool {
+        match self.peek() {
+            Some(&TokOp(o)) if o == op => (),
+            _ => return false
+        }
+        self.pos += 1;
+        true
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), String> {
+        match self.next() {
+            Some(ref t) if *t == token => Ok(()),
+            Some(t) => Err(format!("expected {}, got {}", token, t)),
+            None => Err(format!("expected {}, got end of input", token))
+        }
+    }
+
+    fn parse_or(&mut self) -> Result<Expr, String> {
+        let mut lhs = try!(self.parse_and());
+        while self.accept_op("||") {
+            let rhs = try!(self.parse_and());
+            lhs = ExprBinary(OpOr, box lhs, box rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_and(&mut self) -> Result<Expr, String> {
+        let mut lhs = try!(self.parse_cmp());
+        while self.accept_op("&&") {
+            let rhs = try!(self.parse_cmp());
+            lhs = ExprBinary(OpAnd, box lhs, box rhs);
+        }
+        Ok(lhs)
+    }
+
+    fn parse_cmp(&mut self) -> Result<Expr, String> {
+        let lhs = try!(self.parse_add());
+        let ops = [("<=", OpLe), (">=", OpGe), ("==", OpEq), ("!=", OpNe), ("<", OpLt), (">", OpGt)];
+        for &(text, op) in ops.iter() {
+            if self.accept_op(text) {
+                let rhs = try!(self.parse_add());
+                return Ok(ExprBinary(op, box lhs, box rhs));
+            }
+        }
+        Ok(lhs)
+    }
+
+    fn parse_add(&mut self) -> Result<Expr, String> {
+        let mut lhs = try!(self.parse_mul());
+        loop {
+            let op = if self.accept_op("+") {
+                OpAdd
+            } else if self.accept_op("-") {
+                OpSub
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = try!(self.parse_mul());
+            lhs = ExprBinary(op, box lhs, box rhs);
+        }
+    }
+
+    fn parse_mul(&mut self) -> Result<Expr, String> {
+        let mut lhs = try!(self.parse_unary());
+        loop {
+            let op = if self.accept_op("*") {
+                OpMul
+            } else if self.accept_op("/") {
+                OpDiv
+            } else if self.accept_op("%") {
+                OpMod
+            } else {
+                return Ok(lhs);
+            };
+            let rhs = try!(self.parse_unary());
+            lhs = ExprBinary(op, box lhs, box rhs);
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Expr, String> {
+        if self.accept_op("-") {
+            Ok(ExprUnary(OpNeg, box try!(self.parse_unary())))
+        } else if self.accept_op("!") {
+            Ok(ExprUnary(OpNot, box try!(self.parse_unary())))
+        } else {
+            self.parse_pow()
+        }
+    }
+
+    fn parse_pow(&mut self) -> Result<Expr, String> {
+        let base = try!(self.parse_primary());
+        if self.accept_op("^") {
+            // right associative and binds tighter than unary minus on the left: -a^2 == -(a^2)
+            let exp = try!(self.parse_unary());
+            Ok(ExprBinary(OpPow, box base, box exp))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn parse_primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(TokNumber(x)) => Ok(ExprNumber(x)),
+            Some(TokQuoted(name)) => Ok(ExprColumn(name)),
+            Some(TokIdent(name)) => {
+                if self.peek() == Some(&TokLParen) {
+                    self.pos += 1;
+                    let mut args = Vec::new();
+                    if self.peek() != Some(&TokRParen) {
+                        loop {
+                            args.push(try!(self.parse_or()));
+                            if self.peek() == Some(&TokComma) {
+                                self.pos += 1;
+                            } else {
+                                break;
+                            }
+                        }
+                    }
+                    try!(self.expect(TokRParen));
+                    Ok(ExprCall(name, args))
+                } else {
+                    match name.as_slice() {
+                        "pi" => Ok(ExprNumber(f64::consts::PI)),
+                        "e" => Ok(ExprNumber(f64::consts::E)),
+                        "nan" => Ok(ExprNumber(Float::nan())),
+                        _ => Ok(ExprColumn(name))
+                    }
+                }
+            },
+            Some(TokLParen) => {
+                let inner = try!(self.parse_or());
+                try!(self.expect(TokRParen));
+                Ok(inner)
+            },
+            Some(t) => Err(format!("unexpected {}", t)),
+            None => Err("unexpected end of input".to_string())
+        }
+    }
+}
+
+/// Parses a single expression, e.g. `log10(c) * 2`.
+pub fn parse(s: &str) -> Result<Expr, String> {
+    let mut parser = Parser{tokens: try!(tokenize(s)), pos: 0};
+    let result = try!(parser.parse_or());
+    match parser.peek() {
+        Some(t) => Err(format!("unexpected {}", t)),
+        None => Ok(result)
+    }
+}
+
+/// Parses definitions separated by `;`, e.g. `ratio = a / b; "log c" = log10(c)`.
+pub fn parse_definitions(s: &str) -> Result<Vec<Definition>, String> {
+    let mut result = Vec::new();
+    for part in split_definitions(s).iter() {
+        let mut parser = Parser{tokens: try!(tokenize(part.as_slice())), pos: 0};
+        if parser.tokens.is_empty() {
+            continue;
+        }
+        let name = match parser.next() {
+            Some(TokIdent(name)) | Some(TokQuoted(name)) => name,
+            _ => return Err(format!("expected column name in: {}", part))
+        };
+        try!(parser.expect(TokAssign));
+        let expr = try!(parser.parse_or());
+        match parser.peek() {
+            Some(t) => return Err(format!("unexpected {}", t)),
+            None => ()
+        }
+        result.push(Definition{
+            name: name,
+            expr: expr,
+            source: part.as_slice().trim().to_string(),
+        });
+    }
+    Ok(result)
+}
+
+// splits at `;` outside of quoted column names
+fn split_definitions(s: &str) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    for c in s.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            None if c == '"' || c == '`' => quote = Some(c),
+            None if c == ';' => {
+                parts.push(current);
+                current = String::new();
+                continue;
+            },
+            _ => ()
+        }
+        current.push_char(c);
+    }
+    parts.push(current);
+    parts
+}
+
+fn truth(x: bool) -> f64 {
+    if x { 1f64 } else { 0f64 }
+}
+
+fn apply_unary(op: UnaryOp, x: f64) -> f64 {
+    match op {
+        OpNeg => -x,
+        OpNot => if x.is_nan() { x } else { truth(x == 0f64) }
+    }
+}
+
+fn apply_binary(op: BinaryOp, a: f64, b: f64) -> f64 {
+    match op {
+        OpAdd => a + b,
+        OpSub => a - b,
+        OpMul => a * b,
+        OpDiv => a / b,
+        OpMod => a % b,
+        OpPow => a.powf(b),
+        OpLt => truth(a < b),
+        OpLe => truth(a <= b),
+        OpGt => truth(a > b),
+        OpGe => truth(a >= b),
+        OpEq => truth(a == b),
+        OpNe => truth(a != b),
+        OpAnd => truth(is_true(a) && is_true(b)),
+        OpOr => truth(is_true(a) || is_true(b)),
+    }
+}
+
+fn arity(name: &str) -> uint {
+    match name {
+        "if" => 3,
+        "min" | "max" | "pow" | "atan2" => 2,
+        _ => 1
+    }
+}
+
+// functions that work on single values, `args` has the correct arity
+fn call_scalar(name: &str, args: &[f64]) -> Result<f64, String> {
+    let x = args[0];
+    let result = match name {
+        "log" | "ln" => x.ln(),
+        "log10" => x.log10(),
+        "log2" => x.log2(),
+        "log1p" => x.ln_1p(),
+        "exp" => x.exp(),
+        "sqrt" => x.sqrt(),
+        "abs" => x.abs(),
+        "floor" => x.floor(),
+        "ceil" => x.ceil(),
+        "round" => x.round(),
+        "sin" => x.sin(),
+        "cos" => x.cos(),
+        "tan" => x.tan(),
+        "isnan" => truth(x.is_nan()),
+        "min" => x.min(args[1]),
+        "max" => x.max(args[1]),
+        "pow" => x.powf(args[1]),
+        "atan2" => x.atan2(args[1]),
+        "if" => {
+            if x.is_nan() {
+                x
+            } else if x != 0f64 {
+                args[1]
+            } else {
+                args[2]
+            }
+        },
+        "rank" | "zscore" => return Err(format!("{}() needs the whole column and cannot be used here", name)),
+        _ => return Err(format!("unknown function: {}", name))
+    };
+    Ok(result)
+}
+
+fn call(name: &str, args: Vec<Vec<f64>>) -> Result<Vec<f64>, String> {
+    if args.len() != arity(name) {
+        return Err(format!("{}() takes {} arguments, got {}", name, arity(name), args.len()));
+    }
+
+    match name {
+        "rank" => Ok(normalize::rank(&args[0])),
+        "zscore" => Ok(normalize::zscore(&args[0])),
+        _ => {
+            let mut buffer = [0f64, ..3];
+            let mut result = Vec::with_capacity(args[0].len());
+            for i in range(0, args[0].len()) {
+                for (k, arg) in args.iter().enumerate() {
+                    buffer[k] = arg[i];
+                }
+                result.push(try!(call_scalar(name, buffer.slice_to(args.len()))));
+            }
+            Ok(result)
+        }
+    }
+}
+
+/// Evaluates an expression for all rows of the table.
+pub fn eval(expr: &Expr, table: &data::Table) -> Result<Vec<f64>, String> {
+    match *expr {
+        ExprNumber(x) => Ok(Vec::from_elem(table.len(), x)),
+        ExprColumn(ref name) => match table.get(name) {
+            Some(data) => Ok(data.clone()),
+            None => Err(format!("unknown column: {}", name))
+        },
+        ExprUnary(op, ref inner) => {
+            let values = try!(eval(&**inner, table));
+            Ok(values.iter().map(|&x| apply_unary(op, x)).collect())
+        },
+        ExprBinary(op, ref lhs, ref rhs) => {
+            let a = try!(eval(&**lhs, table));
+            let b = try!(eval(&**rhs, table));
+            Ok(a.iter().zip(b.iter()).map(|(&x, &y)| apply_binary(op, x, y)).collect())
+        },
+        ExprCall(ref name, ref args) => {
+            let mut values = Vec::new();
+            for arg in args.iter() {
+                values.push(try!(eval(arg, table)));
+            }
+            call(name.as_slice(), values)
+        }
+    }
+}
+
+/// Evaluates an expression for a single row, `names` are the column names of the values in `row`.
+pub fn eval_row(expr: &Expr, names: &Vec<String>, row: &Vec<f64>) -> Result<f64, String> {
+    match *expr {
+        ExprNumber(x) => Ok(x),
+        ExprColumn(ref name) => match names.iter().position(|n| n == name) {
+            Some(i) => Ok(row[i]),
+            None => Err(format!("unknown column: {}", name))
+        },
+        ExprUnary(op, ref inner) => Ok(apply_unary(op, try!(eval_row(&**inner, names, row)))),
+        ExprBinary(op, ref lhs, ref rhs) => {
+            let a = try!(eval_row(&**lhs, names, row));
+            let b = try!(eval_row(&**rhs, names, row));
+            Ok(apply_binary(op, a, b))
+        },
+        ExprCall(ref name, ref args) => {
+            if args.len() != arity(name.as_slice()) {
+                return Err(format!("{}() takes {} arguments, got {}", name, arity(name.as_slice()), args.len()));
+            }
+            let mut buffer = [0f64, ..3];
+            for (k, arg) in args.iter().enumerate() {
+                buffer[k] = try!(eval_row(arg, names, row));
+            }
+            call_scalar(name.as_slice(), buffer.slice_to(args.len()))
+        }
+    }
+}
+
+/// Filters treat every value except 0 and NaN as true.
+pub fn is_true(x: f64) -> bool {
+    x != 0f64 && !x.is_nan()
+}
+
+/// Evaluates a definition and adds (or replaces) the resulting column.
+pub fn apply(definition: &Definition, table: &mut data::Table) -> Result<(), String> {
+    let values = try!(eval(&definition.expr, table));
+    table.add_column(&definition.name, values);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use data;
+    use std::collections::TreeSet;
+    use std::iter::FromIterator;
+    use std::num::Float;
+    use super::{apply, eval, eval_row, is_true, parse, parse_definitions};
+
+    // evaluates `s` for a single row with the columns a, "b c" and x
+    fn calc(s: &str) -> f64 {
+        let names = vec!["a".to_string(), "b c".to_string(), "x".to_string()];
+        eval_row(&parse(s).unwrap(), &names, &vec![1f64, 2f64, 3f64]).unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(calc("1 + 2 * 3 ^ 2"), 19f64);
+        assert_eq!(calc("(1 + 2) * 3"), 9f64);
+        assert_eq!(calc("-2 ^ 2"), -4f64);
+        assert_eq!(calc("2 ^ 3 ^ 2"), 512f64);
+        assert_eq!(calc("10 - 4 - 3"), 3f64);
+        assert_eq!(calc("10 % 3"), 1f64);
+        assert_eq!(calc("1 < 2 && 3 > 4 || 1"), 1f64);
+        assert_eq!(calc("!0 + !5"), 1f64);
+    }
+
+    #[test]
+    fn columns_and_constants() {
+        assert_eq!(calc("a + \"b c\" * x"), 7f64);
+        assert_eq!(calc("`b c` == 2"), 1f64);
+        assert_eq!(calc("pi > 3.14 && e < 2.72"), 1f64);
+        assert_eq!(calc("1.5e1 + .5"), 15.5f64);
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(calc("log10(100)"), 2f64);
+        assert_eq!(calc("max(a, x) + min(a, x)"), 4f64);
+        assert_eq!(calc("if(x > 1, 10, 20)"), 10f64);
+        assert!(calc("if(nan, 10, 20)").is_nan());
+        assert_eq!(calc("isnan(nan) + (nan == nan)"), 1f64);
+    }
+
+    #[test]
+    fn errors() {
+        for s in ["1 +", "(1", "1 2", "a $ b", "\"a", "f(1,", ""].iter() {
+            assert!(parse(*s).is_err(), "{} should not parse", s);
+        }
+        // tokens are named as they are written
+        assert_eq!(parse("(1"), Err("expected ), got end of input".to_string()));
+        assert_eq!(parse("(1 2"), Err("expected ), got 2".to_string()));
+        assert_eq!(parse("a \"b\"\"c\""), Err("unexpected \"b\"\"c\"".to_string()));
+        let names = vec!["a".to_string()];
+        let row = vec![1f64];
+        for s in ["b + 1", "min(a)", "foo(a)", "rank(a)"].iter() {
+            assert!(eval_row(&parse(*s).unwrap(), &names, &row).is_err(), "{} should fail", s);
+        }
+    }
+
+    #[test]
+    fn definitions() {
+        let defs = parse_definitions("r = a / b; \"log;c\" = log10(c);").unwrap();
+        assert_eq!(defs.len(), 2);
+        assert_eq!(defs[0].name, "r".to_string());
+        assert_eq!(defs[0].source, "r = a / b".to_string());
+        assert_eq!(defs[1].name, "log;c".to_string());
+        assert!(parse_definitions("a + b").is_err());
+        assert!(parse_definitions("1 = a").is_err());
+    }
+
+    #[test]
+    fn whole_columns() {
+        let columns: TreeSet<String> = FromIterator::from_iter(vec!["a".to_string(), "b".to_string()].move_iter());
+        let mut table = data::Table::new("test".to_string(), columns);
+        table.push(&vec![1f64, 2f64]);
+        table.push(&vec![3f64, 4f64]);
+        table.push(&vec![5f64, Float::nan()]);
+        assert_eq!(eval(&parse("a * 2").unwrap(), &table).unwrap(), vec![2f64, 6f64, 10f64]);
+        assert!(eval(&parse("c").unwrap(), &table).is_err());
+
+        let defs = parse_definitions("s = a + b").unwrap();
+        apply(&defs[0], &mut table).unwrap();
+        let s = table.get(&"s".to_string()).unwrap();
+        assert_eq!(s.slice_to(2), [3f64, 7f64].as_slice());
+        assert!(s[2].is_nan());
+    }
+
+    #[test]
+    fn truth() {
+        assert!(is_true(1f64));
+        assert!(is_true(-0.5f64));
+        assert!(!is_true(0f64));
+        assert!(!is_true(Float::nan()));
+    }
+}
@@ -0,0 +1,371 @@
+use glfw;
+
+/// Everything that can be bound to a key.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Action {
+    ActionQuit,
+    ActionHelp,
+    ActionStats,
+    ActionReset,
+    ActionUndo,
+    ActionRedo,
+    ActionZoomBack,
+    ActionRangePrompt,
+    ActionEqualAspect,
+    ActionPointSmaller,
+    ActionPointLarger,
+    ActionAlphaLower,
+    ActionAlphaHigher,
+    ActionNextY,
+    ActionPrevY,
+    ActionNextX,
+    ActionPrevX,
+    ActionNextZ,
+    ActionPrevZ,
+    ActionPicker,
+    ActionNormX,
+    ActionNormY,
+    ActionNormZ,
+    ActionDerive,
+    ActionFilter,
+    ActionPca,
+    ActionTsne,
+    ActionCluster,
+    ActionCentroids,
+    ActionSample,
+    ActionPanel,
+    ActionSaveRanges,
+    ActionSaveSession,
+    ActionLoadSession,
+    ActionExport,
+    ActionRangeMode,
+    ActionOutliers,
+}
+
+// in the order of the help text
+static ACTIONS: [Action, ..37] = [
+    ActionHelp, ActionQuit, ActionStats, ActionReset, ActionUndo, ActionRedo, ActionZoomBack,
+    ActionRangePrompt, ActionEqualAspect, ActionPointSmaller, ActionPointLarger, ActionAlphaLower,
+    ActionAlphaHigher, ActionPrevY, ActionNextY, ActionPrevX, ActionNextX, ActionPrevZ, ActionNextZ,
+    ActionPicker, ActionNormX, ActionNormY, ActionNormZ, ActionDerive, ActionFilter, ActionPca,
+    ActionTsne, ActionCluster, ActionCentroids, ActionSample, ActionPanel, ActionSaveRanges,
+    ActionSaveSession, ActionLoadSession, ActionExport, ActionRangeMode, ActionOutliers,
+];
+
+impl Action {
+    /// Name used in the config file.
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ActionQuit => "quit",
+            ActionHelp => "help",
+            ActionStats => "stats",
+            ActionReset => "reset",
+            ActionUndo => "undo",
+            ActionRedo => "redo",
+            ActionZoomBack => "zoom_back",
+            ActionRangePrompt => "range",
+            ActionEqualAspect => "equal_aspect",
+            ActionPointSmaller => "point_smaller",
+            ActionPointLarger => "point_larger",
+            ActionAlphaLower => "alpha_lower",
+            ActionAlphaHigher => "alpha_higher",
+            ActionNextY => "next_y",
+            ActionPrevY => "prev_y",
+            ActionNextX => "next_x",
+            ActionPrevX => "prev_x",
+            ActionNextZ => "next_z",
+            ActionPrevZ => "prev_z",
+            ActionPicker => "columns",
+            ActionNormX => "normalize_x",
+            ActionNormY => "normalize_y",
+            ActionNormZ => "normalize_z",
+            ActionDerive => "define",
+            ActionFilter => "filter",
+            ActionPca => "pca",
+            ActionTsne => "tsne",
+            ActionCluster => "cluster",
+            ActionCentroids => "centroids",
+            ActionSample => "sample",
+            ActionPanel => "panel",
+            ActionSaveRanges => "save_ranges",
+            ActionSaveSession => "save_session",
+            ActionLoadSession => "load_session",
+            ActionExport => "export",
+            ActionRangeMode => "range_mode",
+            ActionOutliers => "outliers",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            ActionQuit => "Quit",
+            ActionHelp => "Toggle help",
+            ActionStats => "Toggle column statistics",
+            ActionReset => "Reset view",
+            ActionUndo => "Undo view change",
+            ActionRedo => "Redo view change",
+            ActionZoomBack => "Back to previous zoom",
+            ActionRangePrompt => "Set exact axis ranges, e.g. x 0,10; y -1,1",
+            ActionEqualAspect => "Toggle equal aspect of X and Y",
+            ActionPointSmaller => "Decrease point size",
+            ActionPointLarger => "Increase point size",
+            ActionAlphaLower => "Decrease alpha",
+            ActionAlphaHigher => "Increase alpha",
+            ActionNextY => "Next Y dimension",
+            ActionPrevY => "Previous Y dimension",
+            ActionNextX => "Next X dimension",
+            ActionPrevX => "Previous X dimension",
+            ActionNextZ => "Next Z dimension",
+            ActionPrevZ => "Previous Z dimension",
+            ActionPicker => "Search columns, Tab chooses X/Y/Z, Enter selects",
+            ActionNormX => "Cycle normalization of X column (z-score, min-max, rank, log1p, box-cox)",
+            ActionNormY => "Cycle normalization of Y column",
+            ActionNormZ => "Cycle normalization of Z column",
+            ActionDerive => "Define column, e.g. ratio = a / b",
+            ActionFilter => "Filter rows, e.g. c > 0 && a < 5",
+            ActionPca => "PCA, e.g. std, k=2, a, b, c (all columns if none given)",
+            ActionTsne => "t-SNE, e.g. perplexity=30, seed=1, a, b, c",
+            ActionCluster => "Cluster, e.g. k=5, a, b or dbscan, eps=0.5, min=5, a, b",
+            ActionCentroids => "Toggle cluster centroids",
+            ActionSample => "Switch between sample and full data",
+            ActionPanel => "Toggle column range panel",
+            ActionSaveRanges => "Save column ranges",
+            ActionSaveSession => "Save session",
+            ActionLoadSession => "Load session",
+            ActionExport => "Export view as SVG and PDF",
+            ActionRangeMode => "Cycle axis range mode (full, percentile, mad)",
+            ActionOutliers => "Toggle clamping/hiding of outliers",
+        }
+    }
+
+    /// Actions opening a prompt run on release, so the char event of their key does not end up in it.
+    pub fn on_release(&self) -> bool {
+        match *self {
+            ActionRangePrompt | ActionPicker | ActionDerive | ActionFilter | ActionPca | ActionTsne | ActionCluster => true,
+            _ => false
+        }
+    }
+}
+
+pub fn parse_action(s: &str) -> Result<Action, String> {
+    match ACTIONS.iter().find(|a| a.name() == s) {
+        Some(a) => Ok(*a),
+        None => Err(format!("unknown action {}", s))
+    }
+}
+
+fn key_names() -> Vec<(&'static str, glfw::Key)> {
+    vec![
+        ("A", glfw::KeyA), ("B", glfw::KeyB), ("C", glfw::KeyC), ("D", glfw::KeyD), ("E", glfw::KeyE),
+        ("F", glfw::KeyF), ("G", glfw::KeyG), ("H", glfw::KeyH), ("I", glfw::KeyI), ("J", glfw::KeyJ),
+        ("K", glfw::KeyK), ("L", glfw::KeyL), ("M", glfw::KeyM), ("N", glfw::KeyN), ("O", glfw::KeyO),
+        ("P", glfw::KeyP), ("Q", glfw::KeyQ), ("R", glfw::KeyR), ("S", glfw::KeyS), ("T", glfw::KeyT),
+        ("U", glfw::KeyU), ("V", glfw::KeyV), ("W", glfw::KeyW), ("X", glfw::KeyX), ("Y", glfw::KeyY),
+        ("Z", glfw::KeyZ),
+        ("0", glfw::Key0), ("1", glfw::Key1), ("2", glfw::Key2), ("3", glfw::Key3), ("4", glfw::Key4),
+        ("5", glfw::Key5), ("6", glfw::Key6), ("7", glfw::Key7), ("8", glfw::Key8), ("9", glfw::Key9),
+        ("F1", glfw::KeyF1), ("F2", glfw::KeyF2), ("F3", glfw::KeyF3), ("F4", glfw::KeyF4),
+        ("F5", glfw::KeyF5), ("F6", glfw::KeyF6), ("F7", glfw::KeyF7), ("F8", glfw::KeyF8),
+        ("F9", glfw::KeyF9), ("F10", glfw::KeyF10), ("F11", glfw::KeyF11), ("F12", glfw::KeyF12),
+        ("Escape", glfw::KeyEscape), ("Enter", glfw::KeyEnter), ("Tab", glfw::KeyTab),
+        ("Backspace", glfw::KeyBackspace), ("Insert", glfw::KeyInsert), ("Delete", glfw::KeyDelete),
+        ("Space", glfw::KeySpace), ("Left", glfw::KeyLeft), ("Right", glfw::KeyRight),
+        ("Up", glfw::KeyUp), ("Down", glfw::KeyDown), ("PageUp", glfw::KeyPageUp),
+        ("PageDown", glfw::KeyPageDown), ("Home", glfw::KeyHome), ("End", glfw::KeyEnd),
+        ("Minus", glfw::KeyMinus), ("Equal", glfw::KeyEqual), ("Comma", glfw::KeyComma),
+        ("Period", glfw::KeyPeriod), ("Slash", glfw::KeySlash),
+    ]
+}
+
+/// A key together with the modifiers that have to be held.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Binding {
+    pub key: glfw::Key,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+        let name = match key_names().move_iter().find(|&(_, k)| k == self.key) {
+            Some((n, _)) => n,
+            None => "?"
+        };
+        let mut label = String::new();
+        if self.ctrl {
+            label.push_str("Ctrl+");
+        }
+        if self.shift {
+            label.push_str("Shift+");
+        }
+        label.push_str(name);
+        label
+    }
+}
+
+fn lower(s: &str) -> String {
+    s.chars().map(|c| c.to_lowercase()).collect()
+}
+
+/// Parses e.g. `W`, `PageUp` or `Ctrl+Shift+Z`, names are case insensitive.
+pub fn parse_binding(s: &str) -> Result<Binding, String> {
+    let parts: Vec<String> = s.split('+').map(|p| lower(p.trim())).collect();
+    let mut binding = Binding {
+        key: glfw::KeyEscape,
+        ctrl: false,
+        shift: false,
+    };
+    for m in parts.init().iter() {
+        match m.as_slice() {
+            "ctrl" => binding.ctrl = true,
+            "shift" => binding.shift = true,
+            _ => return Err(format!("unknown modifier {} in {}", m, s))
+        }
+    }
+    let key = parts.last().unwrap();
+    binding.key = match key_names().move_iter().find(|&(n, _)| lower(n) == *key) {
+        Some((_, k)) => k,
+        None => return Err(format!("unknown key {}", s))
+    };
+    Ok(binding)
+}
+
+/// Maps keys to actions, an action can have several keys.
+pub struct KeyMap {
+    bindings: Vec<(Binding, Action)>,
+}
+
+impl KeyMap {
+    pub fn default() -> KeyMap {
+        let defaults = [
+            (ActionQuit, "Escape"),
+            (ActionHelp, "H"),
+            (ActionStats, "I"),
+            (ActionReset, "R"),
+            (ActionUndo, "Ctrl+Z"),
+            (ActionRedo, "Ctrl+Shift+Z"),
+            (ActionZoomBack, "Backspace"),
+            (ActionRangePrompt, "G"),
+            (ActionEqualAspect, "J"),
+            (ActionPointSmaller, "Q"),
+            (ActionPointLarger, "W"),
+            (ActionAlphaLower, "A"),
+            (ActionAlphaHigher, "S"),
+            (ActionNextY, "Down"),
+            (ActionPrevY, "Up"),
+            (ActionNextX, "Right"),
+            (ActionPrevX, "Left"),
+            (ActionNextZ, "PageDown"),
+            (ActionPrevZ, "PageUp"),
+            (ActionPicker, "N"),
+            (ActionNormX, "X"),
+            (ActionNormY, "Y"),
+            (ActionNormZ, "Z"),
+            (ActionDerive, "E"),
+            (ActionFilter, "F"),
+            (ActionPca, "C"),
+            (ActionTsne, "T"),
+            (ActionCluster, "L"),
+            (ActionCentroids, "M"),
+            (ActionSample, "V"),
+            (ActionPanel, "P"),
+            (ActionSaveRanges, "K"),
+            (ActionSaveSession, "Ctrl+S"),
+            (ActionLoadSession, "Ctrl+O"),
+            (ActionExport, "Ctrl+E"),
+            (ActionRangeMode, "O"),
+            (ActionOutliers, "U"),
+        ];
+        KeyMap {
+            bindings: defaults.iter().map(|&(action, key)| (parse_binding(key).unwrap(), action)).collect(),
+        }
+    }
+
+    /// Replaces all keys of `action`, keys bound to other actions before are moved to `action`.
+    pub fn bind(&mut self, action: Action, keys: Vec<Binding>) {
+        self.bindings.retain(|&(ref b, a)| a != action && !keys.contains(b));
+        for key in keys.move_iter() {
+            self.bindings.push((key, action));
+        }
+    }
+
+    /// Action of a key, Shift is ignored if the key has no binding with it.
+    pub fn lookup(&self, key: glfw::Key, mods: glfw::Modifiers) -> Option<Action> {
+        let ctrl = mods.contains(glfw::Control);
+        let shift = mods.contains(glfw::Shift);
+        let find = |shift: bool| {
+            self.bindings.iter()
+                .find(|&&(ref b, _)| b.key == key && b.ctrl == ctrl && b.shift == shift)
+                .map(|&(_, a)| a)
+        };
+        match find(shift) {
+            Some(a) => Some(a),
+            None if shift => find(false),
+            None => None
+        }
+    }
+
+    /// One line per action and its keys, unbound actions are left out.
+    pub fn help(&self) -> String {
+        let mut lines = Vec::new();
+        for action in ACTIONS.iter() {
+            let keys: Vec<String> = self.bindings.iter()
+                .filter(|&&(_, a)| a == *action)
+                .map(|&(ref b, _)| b.label())
+                .collect();
+            if !keys.is_empty() {
+                lines.push(format!("{}: {}", keys.connect(", "), action.description()));
+            }
+        }
+        lines.connect("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use glfw;
+    use super::{parse_action, parse_binding, ACTIONS, ActionExport, ActionQuit, ActionRedo, ActionUndo, KeyMap};
+
+    #[test]
+    fn bindings() {
+        let b = parse_binding("ctrl + SHIFT + pageup").unwrap();
+        assert_eq!(b.key, glfw::KeyPageUp);
+        assert!(b.ctrl && b.shift);
+        assert_eq!(b.label(), "Ctrl+Shift+PageUp".to_string());
+        assert_eq!(parse_binding("w").unwrap().label(), "W".to_string());
+        assert!(parse_binding("Alt+W").is_err());
+        assert!(parse_binding("Ctrl+").is_err());
+        assert!(parse_binding("Foo").is_err());
+    }
+
+    #[test]
+    fn action_names() {
+        for action in ACTIONS.iter() {
+            assert_eq!(parse_action(action.name()), Ok(*action));
+        }
+        assert!(parse_action("explode").is_err());
+    }
+
+    #[test]
+    fn lookup_with_modifiers() {
+        let keys = KeyMap::default();
+        let none = glfw::Modifiers::empty();
+        assert_eq!(keys.lookup(glfw::KeyZ, glfw::Control), Some(ActionUndo));
+        assert_eq!(keys.lookup(glfw::KeyZ, glfw::Control | glfw::Shift), Some(ActionRedo));
+        // Shift falls back to the binding without it
+        assert_eq!(keys.lookup(glfw::KeyEscape, glfw::Shift), Some(ActionQuit));
+        assert_eq!(keys.lookup(glfw::KeyE, glfw::Control), Some(ActionExport));
+        assert_eq!(keys.lookup(glfw::KeyF12, none), None);
+    }
+
+    #[test]
+    fn rebinding_moves_keys() {
+        let mut keys = KeyMap::default();
+        keys.bind(ActionQuit, vec![parse_binding("Ctrl+Z").unwrap()]);
+        assert_eq!(keys.lookup(glfw::KeyZ, glfw::Control), Some(ActionQuit));
+        assert_eq!(keys.lookup(glfw::KeyEscape, glfw::Modifiers::empty()), None);
+        // undo lost its only key and disappears from the help
+        assert!(!keys.help().as_slice().contains("Undo"));
+        assert!(keys.help().as_slice().starts_with("H: "));
+    }
+}
@@ -0,0 +1,564 @@
+use data;
+use datetime;
+use dialect;
+use expr;
+use std::cmp;
+use std::collections::HashMap;
+use std::collections::TreeSet;
+use std::io;
+use std::io::stdio;
+use std::iter::FromIterator;
+use std::num::Float;
+use std::path::Path;
+use std::rand::{Rng, SeedableRng, XorShiftRng};
+use std::vec::Vec;
+
+static SNIFF_BYTES: uint = 16 * 1024;
+static PREFIX_BYTES: u64 = 4096;
+
+// samples are reproducible across runs
+static SAMPLE_SEED: [u32, ..4] = [0x2545f491, 0x9e3779b9, 0x243f6a88, 0xb7e15162];
+
+#[deriving(Clone, Show)]
+pub enum Sampling {
+    // uniform sample of exactly this many rows
+    SampleCount(uint),
+    // every row is kept with this probability
+    SampleFraction(f64),
+}
+
+#[deriving(Clone)]
+pub struct Options {
+    pub hints: dialect::DialectHints,
+    pub names: Option<Vec<String>>,
+    pub timeColumns: Vec<String>,
+    pub filter: Option<expr::Expr>,
+    pub sample: Option<Sampling>,
+}
+
+impl Options {
+    pub fn new() -> Options {
+        Options {
+            hints: dialect::DialectHints::none(),
+            names: None,
+            timeColumns: Vec::new(),
+            filter: None,
+            sample: None,
+        }
+    }
+}
+
+// reservoir sampling, keeps memory bounded by the sample size while reading everything
+struct Sampler {
+    sampling: Sampling,
+    rng: XorShiftRng,
+    seen: uint,
+    // rows with their position, so the original order can be restored
+    reservoir: Vec<(uint, Vec<f64>)>,
+}
+
+impl Sampler {
+    fn new(sampling: Sampling) -> Sampler {
+        Sampler {
+            sampling: sampling,
+            rng: SeedableRng::from_seed(SAMPLE_SEED),
+            seen: 0,
+            reservoir: Vec::new(),
+        }
+    }
+
+    fn offer(&mut self, row: &Vec<f64>, table: &mut data::Table) {
+        match self.sampling {
+            SampleCount(n) => {
+                if self.seen < n {
+                    self.reservoir.push((self.seen, row.clone()));
+                } else {
+                    let j = self.rng.gen_range(0, self.seen + 1);
+                    if j < n {
+                        *self.reservoir.get_mut(j) = (self.seen, row.clone());
+                    }
+                }
+            },
+            SampleFraction(p) => {
+                if self.rng.gen::<f64>() < p {
+                    table.push(row);
+                }
+            }
+        }
+        self.seen += 1;
+    }
+
+    fn finish(mut self, table: &mut data::Table) {
+        self.reservoir.sort_by(|a, b| a.val0().cmp(&b.val0()));
+        for &(_, ref row) in self.reservoir.iter() {
+            table.push(row);
+        }
+    }
+}
+
+/// Where `load` stopped reading, so rows appended to the file later on can be read with
+/// `load_appended`.
+pub struct Resume {
+    dialect: dialect::Dialect,
+    positions: HashMap<uint, uint>,
+    names: Vec<String>,
+    // scale applied to columns with Unix timestamps, 1 for all others
+    factors: Vec<f64>,
+    offset: u64,
+    lineno: uint,
+    // the last line had no newline yet, the writer may still be extending it
+    partial: bool,
+    // modification time when the file was read, rewrites that keep the size change it
+    modified: u64,
+    // start of the file, a rewritten file is detected by comparing it
+    prefix: Vec<u8>,
+    // sampled tables cannot be extended
+    appendable: bool,
+}
+
+impl Resume {
+    /// Names of the columns read from the file.
+    pub fn columns<'a>(&'a self) -> &'a Vec<String> {
+        &self.names
+    }
+
+    /// Names of the columns in the order of the file.
+    pub fn file_columns(&self) -> Vec<String> {
+        let mut indexed: Vec<(uint, &String)> = self.names.iter().enumerate()
+            .map(|(i, name)| (self.positions.find(&i).unwrap().clone(), name))
+            .collect();
+        indexed.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+        indexed.move_iter().map(|(_, name)| name.clone()).collect()
+    }
+}
+
+// converts the fields of a record into the column order of the table, `timeCounts` counts the
+// ISO-8601 dates and the non-empty fields of each column
+fn parse_row(dialect: &dialect::Dialect, positions: &HashMap<uint, uint>, row: &Vec<String>, rowVec: &mut Vec<f64>, timeCounts: &mut Vec<(uint, uint)>) {
+    for x in range(0, row.len()) {
+        let value = row[positions.find(&x).unwrap().clone()].as_slice();
+        let &mut (ref mut dates, ref mut filled) = timeCounts.get_mut(x);
+        if !value.trim().is_empty() {
+            *filled += 1;
+        }
+        *rowVec.get_mut(x) = match dialect.parse_number(value) {
+            Some(v) => v,
+            None => match datetime::parse_iso8601(value) {
+                Some(t) => {
+                    *dates += 1;
+                    t
+                },
+                None => Float::nan()
+            }
+        };
+    }
+}
+
+/// Whether a row passes `--where`, fails if the filter cannot be evaluated with these columns.
+pub fn keep_row(options: &Options, names: &Vec<String>, rowVec: &Vec<f64>) -> Result<bool, String> {
+    match options.filter {
+        Some(ref f) => match expr::eval_row(f, names, rowVec) {
+            Ok(x) => Ok(expr::is_true(x)),
+            Err(e) => Err(format!("--where: {}", e))
+        },
+        None => Ok(true)
+    }
+}
+
+// scale of each column with Unix timestamps, None for all other columns and for dates. `values`
+// is the `epoch_factor` of all values in each column.
+fn epoch_factors(options: &Options, names: &Vec<String>, dates: &Vec<bool>, values: &Vec<Option<f64>>) -> Vec<Option<f64>> {
+    names.iter().zip(dates.iter()).zip(values.iter()).map(|((name, &date), &value)| {
+        if date {
+            return None;
+        }
+        let factor = if options.timeColumns.contains(name) {
+            Some(value.unwrap_or(1f64))
+        } else if datetime::epoch_name(name) {
+            value
+        } else {
+            None
+        };
+        if factor.is_some() {
+            println!("Column {} contains Unix timestamps", name);
+        }
+        factor
+    }).collect()
+}
+
+// whether a column may hold Unix timestamps, by its name or --time
+fn epoch_candidate(options: &Options, name: &String) -> bool {
+    options.timeColumns.contains(name) || datetime::epoch_name(name)
+}
+
+// `epoch_factors` from a separate pass over the records after the first `skip`, for filters that
+// have to see timestamps in seconds before the table is complete
+fn scan_epochs(path: &Path, options: &Options, dialect: &dialect::Dialect, positions: &HashMap<uint, uint>, names: &Vec<String>, skip: uint) -> Result<Vec<Option<f64>>, String> {
+    let file = try!(io::File::open(path).map_err(|e| format!("{}: {}", path.display(), e)));
+    let mut reader = io::BufferedReader::new(file);
+    let records = RecordIter {
+        lines: reader.lines(),
+        dialect: dialect,
+        lineno: 0,
+        offset: 0,
+        complete: false,
+        partial: false,
+    };
+    let candidates: Vec<bool> = names.iter().map(|n| epoch_candidate(options, n)).collect();
+    let mut scans: Vec<datetime::EpochScan> = range(0, names.len()).map(|_| datetime::EpochScan::new()).collect();
+    let mut rowVec: Vec<f64> = Vec::from_elem(names.len(), 0f64);
+    let mut timeCounts: Vec<(uint, uint)> = Vec::from_elem(names.len(), (0u, 0u));
+    for record in records.skip(skip) {
+        let (lineno, row) = try!(record.map_err(|e| format!("{}:{}", path.display(), e)));
+        if row.len() != rowVec.len() {
+            return Err(format!("{}:{}: expected {} fields, got {}", path.display(), lineno, rowVec.len(), row.len()));
+        }
+        parse_row(dialect, positions, &row, &mut rowVec, &mut timeCounts);
+        for ((scan, &x), &candidate) in scans.mut_iter().zip(rowVec.iter()).zip(candidates.iter()) {
+            if candidate {
+                scan.add(x);
+            }
+        }
+    }
+    let dates: Vec<bool> = timeCounts.iter().map(|&(dates, filled)| dates > 0 && dates == filled).collect();
+    let values: Vec<Option<f64>> = scans.iter().map(|s| s.factor()).collect();
+    Ok(epoch_factors(options, names, &dates, &values))
+}
+
+fn read_prefix(path: &Path, len: u64) -> io::IoResult<Vec<u8>> {
+    let mut file = try!(io::File::open(path));
+    file.read_exact(cmp::min(len, PREFIX_BYTES) as uint)
+}
+
+/// Reads the whole file, `--where` sees timestamps in seconds like the table does.
+pub fn load(path: &Path, options: &Options) -> Result<(data::Table, Resume), String> {
+    let stat = try!(path.stat().map_err(|e| format!("{}: {}", path.display(), e)));
+    let prefix = try!(read_prefix(path, stat.size).map_err(|e| format!("{}: {}", path.display(), e)));
+    let file = try!(io::File::open(path).map_err(|e| format!("{}: {}", path.display(), e)));
+    let mut reader = io::BufferedReader::with_capacity(SNIFF_BYTES, file);
+
+    let dialect = {
+        let sample = String::from_utf8_lossy(reader.fill_buf().unwrap()).into_string();
+        dialect::Dialect::sniff(sample.as_slice(), &options.hints)
+    };
+
+    let mut records = RecordIter {
+        lines: reader.lines(),
+        dialect: &dialect,
+        lineno: 0,
+        offset: 0,
+        complete: false,
+        partial: false,
+    };
+
+    let (firstLineno, first) = match records.next() {
+        Some(r) => try!(r.map_err(|e| format!("{}:{}", path.display(), e))),
+        None => return Err(format!("{} does not contain any data", path.display()))
+    };
+    let headers: Vec<String> = match options.names {
+        Some(ref names) => {
+            if names.len() != first.len() {
+                return Err(format!("{}: got {} names for {} columns", path.display(), names.len(), first.len()));
+            }
+            names.clone()
+        },
+        None => {
+            if dialect.hasHeader {
+                first.iter().map(|s| s.as_slice().trim().to_string()).collect()
+            } else {
+                range(0, first.len()).map(|i| format!("col{}", i)).collect()
+            }
+        }
+    };
+
+    let units: Vec<Option<String>> = if dialect.hasHeader && dialect.hasUnits {
+        match records.next() {
+            Some(r) => {
+                let (lineno, row) = try!(r.map_err(|e| format!("{}:{}", path.display(), e)));
+                if row.len() != headers.len() {
+                    return Err(format!("{}:{}: expected {} units, got {}", path.display(), lineno, headers.len(), row.len()));
+                }
+                row.iter().map(|u| dialect.parse_unit(u.as_slice())).collect()
+            },
+            None => return Err(format!("{} does not contain a units line", path.display()))
+        }
+    } else {
+        range(0, headers.len()).map(|_| None).collect()
+    };
+
+    let columns: TreeSet<String> = FromIterator::from_iter(headers.clone().move_iter());
+    if columns.len() != headers.len() {
+        return Err(format!("{}: column names are not unique", path.display()));
+    }
+    let mut table = data::Table::new(path.as_str().unwrap().to_string(), columns);
+    for (name, unit) in headers.iter().zip(units.move_iter()) {
+        table.set_unit(name, unit);
+    }
+
+    let mut positions: HashMap<uint, uint> = HashMap::new();
+    for (orig_pos, orig_value) in headers.iter().enumerate() {
+        let (target_pos, _target_value) = table.columns().iter().enumerate().find(|x| x.val1() == orig_value).unwrap();
+        positions.insert(target_pos, orig_pos);
+    }
+
+    let names: Vec<String> = table.columns().iter().map(|c| c.clone()).collect();
+    let mut n: uint = 0;
+    let mut rowVec: Vec<f64> = Vec::new();
+    rowVec.grow(positions.len(), &0f64);
+    let mut timeCounts: Vec<(uint, uint)> = Vec::from_elem(positions.len(), (0u, 0u));
+    // --where sees timestamps in seconds, their factors depend on every value of a column though.
+    // If any column may hold timestamps they are found in a first pass over the file.
+    let filterFactors = match options.filter {
+        Some(_) if names.iter().any(|n| epoch_candidate(options, n)) => {
+            let skip = if dialect.hasHeader && dialect.hasUnits { 2 } else if dialect.hasHeader { 1 } else { 0 };
+            Some(try!(scan_epochs(path, options, &dialect, &positions, &names, skip)))
+        },
+        Some(_) => Some(Vec::from_elem(names.len(), None)),
+        None => None
+    };
+    let mut kept = 0u;
+    let mut sampler = options.sample.clone().map(|s| Sampler::new(s));
+    let mut pending = if dialect.hasHeader {
+        None
+    } else {
+        Some((firstLineno, first))
+    };
+    loop {
+        let (lineno, row) = match pending.take() {
+            Some(r) => r,
+            None => match records.next() {
+                Some(r) => try!(r.map_err(|e| format!("{}:{}", path.display(), e))),
+                None => break
+            }
+        };
+
+        if row.len() != rowVec.len() {
+            return Err(format!("{}:{}: expected {} fields, got {}", path.display(), lineno, rowVec.len(), row.len()));
+        }
+        if n % 100 == 0 {
+            print!("\rParsed {} lines", n);
+            stdio::flush();
+        }
+        n += 1;
+
+        parse_row(&dialect, &positions, &row, &mut rowVec, &mut timeCounts);
+        match filterFactors {
+            Some(ref factors) => {
+                for (x, &factor) in rowVec.mut_iter().zip(factors.iter()) {
+                    *x *= factor.unwrap_or(1f64);
+                }
+                if !try!(keep_row(options, &names, &rowVec)) {
+                    continue;
+                }
+                kept += 1;
+            },
+            None => ()
+        }
+        match sampler {
+            Some(ref mut s) => s.offer(&rowVec, &mut table),
+            None => table.push(&rowVec)
+        }
+    }
+    let (offset, lineno, partial) = (records.offset, records.lineno, records.partial);
+    match sampler {
+        Some(s) => s.finish(&mut table),
+        None => ()
+    }
+
+    // a column of dates only, a single date among other text is not enough
+    let dates: Vec<bool> = timeCounts.iter().map(|&(dates, filled)| dates > 0 && dates == filled).collect();
+    let factors = match filterFactors {
+        Some(factors) => {
+            print!("\rParsed {} lines, kept {}", n, kept);
+            factors
+        },
+        None => {
+            let values: Vec<Option<f64>> = names.iter().map(|n| datetime::epoch_factor(table.get(n).unwrap())).collect();
+            let factors = epoch_factors(options, &names, &dates, &values);
+            for (name, &factor) in names.iter().zip(factors.iter()) {
+                match factor {
+                    Some(f) if f != 1f64 => for x in table.get_mut(name).unwrap().mut_iter() {
+                        *x *= f;
+                    },
+                    _ => ()
+                }
+            }
+            print!("\rParsed {} lines", n);
+            factors
+        }
+    };
+    if options.sample.is_some() {
+        println!(", sampled {}", table.len());
+    } else {
+        println!("");
+    }
+    if table.len() == 0 {
+        return Err(match options.filter {
+            Some(_) => format!("{}: --where removes every row", path.display()),
+            None => format!("{}: no rows to show", path.display())
+        });
+    }
+
+    for (name, (&date, &factor)) in names.iter().zip(dates.iter().zip(factors.iter())) {
+        if date || factor.is_some() {
+            table.set_kind(name, data::KindTime);
+        }
+    }
+
+    table.compute_stats();
+    let resume = Resume {
+        dialect: dialect.clone(),
+        positions: positions,
+        names: names,
+        factors: factors.iter().map(|f| f.unwrap_or(1f64)).collect(),
+        offset: offset,
+        lineno: lineno,
+        partial: partial,
+        modified: stat.modified,
+        prefix: prefix,
+        appendable: options.sample.is_none(),
+    };
+    Ok((table, resume))
+}
+
+/// Reads the complete rows appended to the file since `load` or the previous call, `None` if
+/// there are none. A last line without newline is left for the next call. Fails if the file was
+/// changed in any other way or the last line read by `load` is extended, it has to be loaded again
+/// then.
+pub fn load_appended(path: &Path, options: &Options, resume: &mut Resume) -> Result<Option<data::Table>, String> {
+    if !resume.appendable {
+        return Err("sampled tables cannot be extended".to_string());
+    }
+    let stat = match path.stat() {
+        Ok(s) => s,
+        Err(e) => return Err(format!("{}", e))
+    };
+    if stat.size < resume.offset {
+        return Err(format!("{} was truncated", path.display()));
+    }
+    match read_prefix(path, resume.prefix.len() as u64) {
+        Ok(ref p) if *p == resume.prefix => (),
+        _ => return Err(format!("{} was rewritten", path.display()))
+    }
+    if stat.size == resume.offset {
+        if stat.modified != resume.modified {
+            return Err(format!("{} was rewritten", path.display()));
+        }
+        return Ok(None);
+    }
+    if resume.partial {
+        return Err(format!("{} extended its last line", path.display()));
+    }
+    resume.modified = stat.modified;
+
+    let mut file = match io::File::open(path) {
+        Ok(f) => f,
+        Err(e) => return Err(format!("{}", e))
+    };
+    match file.seek(resume.offset as i64, io::SeekSet) {
+        Ok(()) => (),
+        Err(e) => return Err(format!("{}", e))
+    }
+    let mut reader = io::BufferedReader::new(file);
+
+    let columns: TreeSet<String> = FromIterator::from_iter(resume.names.clone().move_iter());
+    let mut rows = data::Table::new(path.as_str().unwrap().to_string(), columns);
+    let mut rowVec: Vec<f64> = Vec::from_elem(resume.names.len(), 0f64);
+    let mut timeCounts: Vec<(uint, uint)> = Vec::from_elem(resume.names.len(), (0u, 0u));
+    let (offset, lineno) = {
+        let mut records = RecordIter {
+            lines: reader.lines(),
+            dialect: &resume.dialect,
+            lineno: resume.lineno,
+            offset: resume.offset,
+            complete: true,
+            partial: false,
+        };
+        loop {
+            let (lineno, row) = match records.next() {
+                Some(r) => try!(r.map_err(|e| format!("{}:{}", path.display(), e))),
+                None => break
+            };
+            if row.len() != rowVec.len() {
+                return Err(format!("{}:{}: expected {} fields, got {}", path.display(), lineno, rowVec.len(), row.len()));
+            }
+            parse_row(&resume.dialect, &resume.positions, &row, &mut rowVec, &mut timeCounts);
+            for (x, &f) in rowVec.mut_iter().zip(resume.factors.iter()) {
+                *x *= f;
+            }
+            if try!(keep_row(options, &resume.names, &rowVec)) {
+                rows.push(&rowVec);
+            }
+        }
+        (records.offset, records.lineno)
+    };
+    resume.offset = offset;
+    resume.lineno = lineno;
+
+    if rows.len() == 0 {
+        return Ok(None);
+    }
+    Ok(Some(rows))
+}
+
+// Yields split records together with their line number, skipping comments and empty lines and
+// joining lines of quoted fields that contain newlines. Errors start with the line number.
+struct RecordIter<'a, R> {
+    lines: io::Lines<'a, R>,
+    dialect: &'a dialect::Dialect,
+    lineno: uint,
+    // bytes consumed by the records returned so far
+    offset: u64,
+    // stop before a record without trailing newline, it may still be written
+    complete: bool,
+    // the last record read had no trailing newline
+    partial: bool,
+}
+
+impl<'a, R: Buffer> Iterator<Result<(uint, Vec<String>), String>> for RecordIter<'a, R> {
+    fn next(&mut self) -> Option<Result<(uint, Vec<String>), String>> {
+        let mut record = String::new();
+        let mut start = 0u;
+        // position after the last complete record or skipped line
+        let mut lineno = self.lineno;
+        let mut consumed = 0u64;
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(l)) => l,
+                Some(Err(e)) => return Some(Err(format!("{}: {}", self.lineno + 1, e))),
+                None => {
+                    if record.is_empty() || self.complete {
+                        self.lineno = lineno;
+                        return None;
+                    } else {
+                        return Some(Err(format!("{}: unterminated quoted field", start)));
+                    }
+                }
+            };
+            if self.complete && !line.as_slice().ends_with("\n") {
+                self.lineno = lineno;
+                return None;
+            }
+            self.lineno += 1;
+            consumed += line.len() as u64;
+            self.partial = !line.as_slice().ends_with("\n");
+
+            if record.is_empty() {
+                if line.as_slice().trim().is_empty() || self.dialect.is_comment(line.as_slice()) {
+                    lineno = self.lineno;
+                    self.offset += consumed;
+                    consumed = 0;
+                    continue;
+                }
+                start = self.lineno;
+            }
+
+            record.push_str(line.as_slice());
+            if !self.dialect.is_incomplete(record.as_slice()) {
+                self.offset += consumed;
+                let trimmed = record.as_slice().trim_right_chars(|c: char| c == '\n' || c == '\r').to_string();
+                return Some(Ok((start, self.dialect.split(trimmed.as_slice()))));
+            }
+        }
+    }
+}
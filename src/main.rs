@@ -7,28 +7,47 @@ extern crate serialize;
 
 #[phase(plugin)] extern crate cfor;
 extern crate cgmath;
-extern crate csv;
 #[phase(plugin)] extern crate docopt_macros;
 extern crate docopt;
+extern crate flate;
 extern crate freetype;
 extern crate gl;
 extern crate glfw;
 extern crate graphics;
 extern crate hgl;
+extern crate libc;
 extern crate native;
 extern crate opengl_graphics;
+extern crate toml;
 
-use std::collections::HashMap;
-use std::collections::TreeSet;
-use std::io::stdio;
-use std::iter::FromIterator;
-use std::num::Float;
+use std::f64;
 use std::path::Path;
-use std::vec::Vec;
 
+mod cluster;
+mod config;
+mod control;
+mod crossfilter;
 mod data;
+mod datetime;
+mod dialect;
+mod export;
+mod expr;
+mod keymap;
+mod loader;
+mod normalize;
+mod pca;
+mod picker;
+mod prompt;
 mod render;
+mod session;
+mod source;
+mod stats;
+mod stream;
 mod textdrawer;
+mod tsne;
+mod undo;
+mod vptree;
+mod watch;
 
 #[start]
 fn start(argc: int, argv: *const *const u8) -> int {
@@ -37,68 +56,221 @@ fn start(argc: int, argv: *const *const u8) -> int {
 
 docopt!(Args,"
 Usage: fluxcore [options] FILE [X Y Z]
+       fluxcore [options] --listen ADDRESS [X Y Z]
+       fluxcore [options] --session SESSION
+       fluxcore stats [options] FILE
        fluxcore (--help)
 
 Options:
-    --separator SEPARATOR   Sets seperator.
+    --separator SEPARATOR   Sets seperator, use \"tab\" or \"whitespace\" for those (default: auto-detect).
+    --quote QUOTE           Sets quote character (default: auto-detect).
+    --comment COMMENT       Skips lines starting with COMMENT (default: auto-detect #).
+    --decimal-comma         Parses numbers with decimal comma, e.g. 1,5 (default: auto-detect).
+    --no-header             File has no header line, columns are named col0, col1, ...
+    --names NAMES           Comma-separated column names, replaces the header line if present.
+    --units                 Second header line contains units (default: auto-detect).
+    --time COLUMNS          Comma-separated columns containing Unix timestamps (default: auto-detect).
+    --where FILTER          Only loads rows matching FILTER, e.g. \"c > 0 && a < 5\".
+    --derive DEFINITIONS    Adds derived columns, separated by ;, e.g. \"ratio = a / b; logc = log10(c)\".
+    --ranges FILE           Loads column ranges of the crossfilter panel from FILE if it exists and saves them there (default: FILE.ranges).
+    --range MODE            Default axis ranges: full, percentile[:P] cutting P% at both ends or mad[:K] for
+                            median +- K deviations (default: full).
+    --hide-outliers         Hides points outside of the axis ranges instead of clamping them to the border.
+    --xlim LO,HI            Shows exactly LO to HI on the X axis, e.g. 0,10.
+    --ylim LO,HI            Shows exactly LO to HI on the Y axis.
+    --zlim LO,HI            Shows exactly LO to HI on the color axis.
+    --equal-aspect          Starts with the same data units per pixel on X and Y, e.g. for coordinates, --xlim and --ylim win.
+    --pca SPEC              Adds principal components PC1, PC2, ... of the columns in SPEC, e.g. \"std, k=2, a, b, c\".
+                            std standardizes the columns, impute replaces NaN values by the column mean
+                            instead of dropping the row and no column names uses all numeric columns.
+    --tsne SPEC             Embeds the columns in SPEC with t-SNE as tSNE1 and tSNE2 while showing the progress,
+                            e.g. \"perplexity=30, seed=1, iter=1000, theta=0.5, std, a, b, c\".
+    --sample N              Loads a uniform random sample of N rows, press V to switch to the full data.
+    --sample-fraction P     Loads every row with probability P, e.g. 0.01.
+    --listen ADDRESS        Reads CSV or NDJSON rows from tcp:HOST:PORT, unix:PATH or a FIFO instead of a file.
+    --window N              Only keeps the last N rows of a stream.
+    --auto-range            Widens the axis ranges when new rows fall outside of them.
+    --control PATH          Accepts JSON-RPC commands on the Unix socket PATH, see README.
+    --session SESSION       Restores the view from SESSION if it exists, Ctrl+S saves to it (default: FILE.session).
+                            FILE can be omitted if SESSION names it.
+    --export OUTPUT         Saves the view to OUTPUT and quits, .svg and .pdf get vector axes, .png and .ppm
+                            an image of the whole window.
+    --export-scale S        Enlarges a .png or .ppm export S times, e.g. 15 for posters, or sets the density
+                            raster resolution of .svg and .pdf (default: 1 for images, 4 for vectors).
+    --config FILE           Reads settings and key bindings from FILE (default: ~/.config/fluxcore/config.toml).
     -h, --help              Print help.
 ")
 
 fn main() {
     let args: Args = docopt::FlagParser::parse().unwrap_or_else(|e| e.exit());
 
-    let path = Path::new(args.arg_FILE);
-    let mut reader = csv::Decoder::from_file(&path);
-    reader.has_headers(true);
+    let mut options = loader::Options::new();
     if !args.flag_separator.is_empty() {
-        let mut s = args.flag_separator.clone();
-        reader.separator(s.shift_char().unwrap());
+        options.hints.separator = dialect::parse_separator(args.flag_separator.as_slice());
+    }
+    if !args.flag_quote.is_empty() {
+        options.hints.quote = args.flag_quote.as_slice().chars().next();
+    }
+    if !args.flag_comment.is_empty() {
+        options.hints.comment = args.flag_comment.as_slice().chars().next();
+    }
+    if args.flag_decimal_comma {
+        options.hints.decimalComma = Some(true);
+    }
+    if args.flag_no_header && args.flag_units {
+        fail!("--no-header and --units cannot be combined");
+    }
+    if args.flag_no_header {
+        options.hints.hasHeader = Some(false);
+        options.hints.hasUnits = Some(false);
+    }
+    if args.flag_units {
+        options.hints.hasHeader = Some(true);
+        options.hints.hasUnits = Some(true);
+    }
+    if !args.flag_names.is_empty() {
+        options.names = Some(args.flag_names.as_slice().split(',').map(|s| s.trim().to_string()).collect());
     }
 
-    let columns: TreeSet<String> = FromIterator::from_iter(reader.headers().unwrap().move_iter());
-    let mut table = data::Table::new(path.as_str().unwrap().to_string(), columns);
+    if !args.flag_time.is_empty() {
+        options.timeColumns = args.flag_time.as_slice().split(',').map(|s| s.trim().to_string()).collect();
+    }
 
-    let mut positions: HashMap<uint, uint> = HashMap::new();
-    for (orig_pos, orig_value) in reader.headers().unwrap().iter().enumerate() {
-        let (target_pos, _target_value) = table.columns().iter().enumerate().find(|x| x.val1() == orig_value).unwrap();
-        positions.insert(target_pos, orig_pos);
+    if !args.flag_where.is_empty() {
+        options.filter = Some(expr::parse(args.flag_where.as_slice()).unwrap_or_else(|e| fail!("--where: {}", e)));
     }
 
-    let mut n: uint = 0;
-    let mut rowVec: Vec<f32> = Vec::new();
-    rowVec.grow(positions.len(), &0f32);
-    for row in reader.decode_iter::<Vec<Option<f32>>>() {
-        assert!(row.len() == rowVec.len());
-        for x in range(0, row.len()) {
-            let value = row[positions.find(&x).unwrap().clone()];
-            *rowVec.get_mut(x) = match value {
-                Some(value) => value,
-                None => Float::nan()
-            };
+    if !args.flag_sample.is_empty() {
+        let n = from_str::<uint>(args.flag_sample.as_slice()).unwrap_or_else(|| fail!("--sample: invalid row count {}", args.flag_sample));
+        options.sample = Some(loader::SampleCount(n));
+    }
+    if !args.flag_sample_fraction.is_empty() {
+        if options.sample.is_some() {
+            fail!("--sample and --sample-fraction cannot be combined");
         }
-        table.push(&rowVec);
+        let p = match from_str::<f64>(args.flag_sample_fraction.as_slice()) {
+            Some(p) if p > 0f64 && p <= 1f64 => p,
+            _ => fail!("--sample-fraction: expected a fraction in (0, 1], got {}", args.flag_sample_fraction)
+        };
+        options.sample = Some(loader::SampleFraction(p));
+    }
 
-        if n % 100 == 0 {
-            print!("\rParsed {} lines", n);
-            stdio::flush();
+    let definitions = if args.flag_derive.is_empty() {
+        Vec::new()
+    } else {
+        expr::parse_definitions(args.flag_derive.as_slice()).unwrap_or_else(|e| fail!("--derive: {}", e))
+    };
+    let window = if args.flag_window.is_empty() {
+        None
+    } else {
+        match from_str::<uint>(args.flag_window.as_slice()) {
+            Some(n) if n > 0 => Some(n),
+            _ => fail!("--window: invalid row count {}", args.flag_window)
+        }
+    };
+
+    let sessionFile = if !args.flag_session.is_empty() {
+        Some(Path::new(args.flag_session.as_slice()))
+    } else if !args.arg_FILE.is_empty() {
+        Some(Path::new(format!("{}.session", args.arg_FILE)))
+    } else {
+        None
+    };
+    let session = match sessionFile {
+        Some(ref path) if path.exists() => Some(session::Session::load(path).unwrap_or_else(|e| fail!("--session: {}", e))),
+        _ => None
+    };
+
+    let (mut table, source, resume, stream, streamDefinitions) = if args.flag_listen.is_empty() {
+        let path = if !args.arg_FILE.is_empty() {
+            Path::new(args.arg_FILE.as_slice())
+        } else {
+            match session {
+                Some(session::Session{file: Some(ref file), ..}) => file.clone(),
+                _ => fail!("--session: {} does not name an input file", args.flag_session)
+            }
+        };
+        let source = source::Source {
+            path: path,
+            options: options,
+            definitions: definitions,
+        };
+        let (table, resume) = source.load().unwrap_or_else(|e| fail!("{}", e));
+        (table, Some(source), Some(resume), None, Vec::new())
+    } else {
+        if options.sample.is_some() || !args.flag_pca.is_empty() {
+            fail!("--listen cannot be combined with --sample, --sample-fraction or --pca");
+        }
+        let address = stream::parse_address(args.flag_listen.as_slice()).unwrap_or_else(|e| fail!("--listen: {}", e));
+        let mut stream = stream::Stream::listen(&address, &options, window).unwrap_or_else(|e| fail!("--listen: {}", e));
+        println!("Waiting for data on {}", address);
+        let mut table = stream.first_table().unwrap_or_else(|e| fail!("--listen: {}", e));
+        // applied again by the renderer whenever rows arrive
+        for definition in definitions.iter() {
+            expr::apply(definition, &mut table).unwrap_or_else(|e| fail!("--derive {}: {}", definition.name, e));
+        }
+        (table, None, None, Some(stream), definitions)
+    };
+
+    let pcaSpec = if args.flag_pca.is_empty() {
+        None
+    } else {
+        let spec = pca::parse_spec(args.flag_pca.as_slice()).unwrap_or_else(|e| fail!("--pca: {}", e));
+        let input = pca::prepare(&spec, &table).unwrap_or_else(|e| fail!("--pca: {}", e));
+        if args.cmd_stats {
+            let result = pca::compute(input).unwrap_or_else(|e| fail!("--pca: {}", e));
+            println!("PCA: {}", result.summary());
+            pca::apply(&result, &mut table);
+        } else {
+            // computed in the background by the renderer like the embedding
+            let n = table.len();
+            for name in pca::component_names(&input).iter() {
+                table.add_column(name, Vec::from_elem(n, f64::NAN));
+            }
         }
-        n += 1;
+        Some(spec)
+    };
+
+    if args.cmd_stats {
+        print_stats(&table);
+        return;
     }
-    println!("\rParsed {} lines", n);
 
-    println!("Render!");
-    let dimx = if args.arg_X.is_empty() {
-        table.columns().iter().next().unwrap().clone()
+    let tsneSpec = if args.flag_tsne.is_empty() {
+        None
+    } else {
+        // the embedding is shown while it is computed, so its columns exist from the start
+        let n = table.len();
+        tsne::apply(Vec::from_elem(n, f64::NAN), Vec::from_elem(n, f64::NAN), &mut table);
+        Some(tsne::parse_spec(args.flag_tsne.as_slice()).unwrap_or_else(|e| fail!("--tsne: {}", e)))
+    };
+
+    let config = if args.flag_config.is_empty() {
+        match config::Config::path() {
+            Some(ref path) if path.exists() => config::Config::load(path).unwrap_or_else(|e| fail!("{}", e)),
+            _ => config::Config::default()
+        }
     } else {
+        config::Config::load(&Path::new(args.flag_config.as_slice())).unwrap_or_else(|e| fail!("--config: {}", e))
+    };
+
+    println!("Render!");
+    let dimx = if !args.arg_X.is_empty() {
         args.arg_X
+    } else if tsneSpec.is_some() {
+        tsne::COLUMN_X.to_string()
+    } else {
+        table.columns().iter().next().unwrap().clone()
     };
-    let dimy = if args.arg_Y.is_empty() {
+    let dimy = if !args.arg_Y.is_empty() {
+        args.arg_Y
+    } else if tsneSpec.is_some() {
+        tsne::COLUMN_Y.to_string()
+    } else {
         match table.columns().iter().skip(1).next() {
             Some(s) => s,
             None => &dimx
         }.clone()
-    } else {
-        args.arg_Y
     };
     let dimz = if args.arg_Z.is_empty() {
         match table.columns().iter().skip(2).next() {
@@ -108,5 +280,69 @@ fn main() {
     } else {
         args.arg_Z
     };
-    render::render(table, &dimx, &dimy, &dimz);
+    let renderOptions = render::Options {
+        rangesFile: if args.flag_ranges.is_empty() {
+            None
+        } else {
+            Some(Path::new(args.flag_ranges.as_slice()))
+        },
+        rangeMode: if args.flag_range.is_empty() {
+            stats::RangeFull
+        } else {
+            stats::parse_range_mode(args.flag_range.as_slice()).unwrap_or_else(|e| fail!("--range: {}", e))
+        },
+        hideOutliers: args.flag_hide_outliers,
+        xlim: parse_limits_flag("--xlim", &args.flag_xlim),
+        ylim: parse_limits_flag("--ylim", &args.flag_ylim),
+        zlim: parse_limits_flag("--zlim", &args.flag_zlim),
+        equalAspect: args.flag_equal_aspect,
+        pca: pcaSpec,
+        tsne: tsneSpec,
+        source: source,
+        resume: resume,
+        stream: stream,
+        window: window,
+        autoRange: args.flag_auto_range,
+        definitions: streamDefinitions,
+        control: if args.flag_control.is_empty() {
+            None
+        } else {
+            Some(control::Server::listen(&Path::new(args.flag_control.as_slice())).unwrap_or_else(|e| fail!("--control: {}", e)))
+        },
+        session: session,
+        sessionFile: sessionFile,
+        export: if args.flag_export.is_empty() {
+            None
+        } else {
+            let scale = if args.flag_export_scale.is_empty() {
+                None
+            } else {
+                match from_str::<f32>(args.flag_export_scale.as_slice()) {
+                    Some(s) if s > 0f32 => Some(s),
+                    _ => fail!("--export-scale: expected a positive factor, got {}", args.flag_export_scale)
+                }
+            };
+            Some((Path::new(args.flag_export.as_slice()), scale))
+        },
+        config: config,
+    };
+    render::render(table, &dimx, &dimy, &dimz, renderOptions);
+}
+
+fn parse_limits_flag(flag: &str, value: &String) -> Option<(f64, f64)> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(render::parse_limits(value.as_slice()).unwrap_or_else(|e| fail!("{}: {}", flag, e)))
+    }
+}
+
+fn print_stats(table: &data::Table) {
+    for name in table.columns().iter() {
+        println!("\n{}", table.title(name));
+        let s = table.stats(name).unwrap();
+        for (label, value) in stats::describe(s, table.kind(name)).move_iter() {
+            println!("    {:<12}{:>28}", label, value);
+        }
+    }
 }
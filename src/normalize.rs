@@ -0,0 +1,225 @@
+use std::f64;
+use std::num::Float;
+
+// lambdas tried when fitting Box-Cox
+static BOXCOX_MIN: f64 = -2f64;
+static BOXCOX_MAX: f64 = 2f64;
+static BOXCOX_STEP: f64 = 0.05f64;
+
+/// Transformed view of a column, the source data is never modified.
+#[deriving(Clone, PartialEq, Show)]
+pub enum Normalization {
+    NormNone,
+    NormZscore,
+    // scales to 0..1
+    NormMinMax,
+    // quantile rank in 0..1
+    NormRank,
+    NormLog1p,
+    // lambda is fitted by maximum likelihood
+    NormBoxCox,
+}
+
+impl Normalization {
+    /// Next normalization for cycling through all of them.
+    pub fn cycle(&self) -> Normalization {
+        match *self {
+            NormNone => NormZscore,
+            NormZscore => NormMinMax,
+            NormMinMax => NormRank,
+            NormRank => NormLog1p,
+            NormLog1p => NormBoxCox,
+            NormBoxCox => NormNone,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            NormNone => "none",
+            NormZscore => "z-score",
+            NormMinMax => "min-max",
+            NormRank => "rank",
+            NormLog1p => "log1p",
+            NormBoxCox => "box-cox",
+        }
+    }
+}
+
+/// Inverse of `Normalization::name`.
+pub fn parse_normalization(s: &str) -> Result<Normalization, String> {
+    match s.trim() {
+        "none" => Ok(NormNone),
+        "z-score" => Ok(NormZscore),
+        "min-max" => Ok(NormMinMax),
+        "rank" => Ok(NormRank),
+        "log1p" => Ok(NormLog1p),
+        "box-cox" => Ok(NormBoxCox),
+        _ => Err(format!("unknown normalization {}", s))
+    }
+}
+
+/// Ranks starting at 1, NaN values stay NaN.
+pub fn rank(data: &Vec<f64>) -> Vec<f64> {
+    let mut indices: Vec<uint> = range(0, data.len()).filter(|&i| !data[i].is_nan()).collect();
+    indices.sort_by(|&a, &b| data[a].partial_cmp(&data[b]).unwrap());
+
+    let mut result: Vec<f64> = Vec::from_elem(data.len(), Float::nan());
+    let mut i = 0u;
+    while i < indices.len() {
+        // ties get the average rank
+        let mut j = i;
+        while j + 1 < indices.len() && data[indices[j + 1]] == data[indices[i]] {
+            j += 1;
+        }
+        let r = (i + j) as f64 / 2f64 + 1f64;
+        for k in range(i, j + 1) {
+            *result.get_mut(indices[k]) = r;
+        }
+        i = j + 1;
+    }
+    result
+}
+
+pub fn zscore(data: &Vec<f64>) -> Vec<f64> {
+    let valid: Vec<f64> = data.iter().filter(|x| !x.is_nan()).map(|&x| x).collect();
+    let n = valid.len() as f64;
+    let mean = valid.iter().fold(0f64, |a, &b| a + b) / n;
+    let var = valid.iter().fold(0f64, |a, &b| a + (b - mean) * (b - mean)) / (n - 1f64);
+    let std = var.sqrt();
+    data.iter().map(|&x| (x - mean) / std).collect()
+}
+
+pub fn minmax(data: &Vec<f64>) -> Vec<f64> {
+    let finite = data.iter().filter(|x| x.is_finite());
+    let (min, max) = finite.fold((Float::infinity(), Float::neg_infinity()), |(lo, hi): (f64, f64), &x| (lo.min(x), hi.max(x)));
+    let span = if max > min { max - min } else { 1f64 };
+    data.iter().map(|&x| (x - min) / span).collect()
+}
+
+/// Rank scaled to 0..1, equal to the fraction of smaller values.
+pub fn quantile_rank(data: &Vec<f64>) -> Vec<f64> {
+    let ranks = rank(data);
+    let n = ranks.iter().filter(|x| !x.is_nan()).count();
+    let scale = if n > 1 { (n - 1) as f64 } else { 1f64 };
+    ranks.iter().map(|&r| (r - 1f64) / scale).collect()
+}
+
+fn box_cox_value(x: f64, lambda: f64) -> f64 {
+    if lambda.abs() < 1e-9f64 {
+        x.ln()
+    } else {
+        (x.powf(lambda) - 1f64) / lambda
+    }
+}
+
+/// Box-Cox transform with the lambda that maximizes the log-likelihood, data that is not positive
+/// is shifted to start at 1 first. Returns the result and the lambda.
+pub fn box_cox(data: &Vec<f64>) -> (Vec<f64>, f64) {
+    let finite: Vec<f64> = data.iter().filter(|x| x.is_finite()).map(|&x| x).collect();
+    if finite.len() < 2 {
+        return (data.clone(), 1f64);
+    }
+    let min = finite.iter().fold(Float::infinity(), |a: f64, &b| a.min(b));
+    let shift = if min <= 0f64 { 1f64 - min } else { 0f64 };
+    let n = finite.len() as f64;
+    let logSum = finite.iter().fold(0f64, |a, &x| a + (x + shift).ln());
+
+    let mut bestLambda = 1f64;
+    let mut best = Float::neg_infinity();
+    let mut lambda = BOXCOX_MIN;
+    while lambda <= BOXCOX_MAX + 1e-9f64 {
+        let y: Vec<f64> = finite.iter().map(|&x| box_cox_value(x + shift, lambda)).collect();
+        let mean = y.iter().fold(0f64, |a, &b| a + b) / n;
+        let var = y.iter().fold(0f64, |a, &b| a + (b - mean) * (b - mean)) / n;
+        let likelihood = -n / 2f64 * var.ln() + (lambda - 1f64) * logSum;
+        if likelihood > best {
+            best = likelihood;
+            bestLambda = lambda;
+        }
+        lambda += BOXCOX_STEP;
+    }
+
+    // snap to the grid, otherwise 0 ends up as 1e-17
+    let bestLambda = (bestLambda / BOXCOX_STEP).round() * BOXCOX_STEP;
+    (data.iter().map(|&x| box_cox_value(x + shift, bestLambda)).collect(), bestLambda)
+}
+
+/// Transforms `data`, returns None for `NormNone`. The label describes the transform for axis titles.
+pub fn apply(norm: Normalization, data: &Vec<f64>) -> Option<(Vec<f64>, String)> {
+    match norm {
+        NormNone => None,
+        NormZscore => Some((zscore(data), "z-score".to_string())),
+        NormMinMax => Some((minmax(data), "min-max".to_string())),
+        NormRank => Some((quantile_rank(data), "rank".to_string())),
+        NormLog1p => Some((data.iter().map(|&x| x.ln_1p()).collect(), "log1p".to_string())),
+        NormBoxCox => {
+            let (result, lambda) = box_cox(data);
+            Some((result, format!("box-cox lambda={}", f64::to_str_digits(lambda, 2))))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::Float;
+    use super::{apply, box_cox, minmax, parse_normalization, quantile_rank, rank, zscore, NormBoxCox, NormNone};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    fn growth() -> Vec<f64> {
+        range(0u, 20).map(|i| (0.3f64 * i as f64).exp()).collect()
+    }
+
+    #[test]
+    fn ranks_with_ties() {
+        let r = rank(&vec![30f64, 10f64, Float::nan(), 20f64, 10f64]);
+        assert_eq!(r[0], 4f64);
+        assert_eq!(r[1], 1.5f64);
+        assert!(r[2].is_nan());
+        assert_eq!(r[3], 3f64);
+        assert_eq!(r[4], 1.5f64);
+
+        let q = quantile_rank(&vec![3f64, 1f64, 2f64]);
+        assert_eq!(q, vec![1f64, 0f64, 0.5f64]);
+    }
+
+    #[test]
+    fn scaling() {
+        let z = zscore(&vec![1f64, 2f64, 3f64, Float::nan()]);
+        assert!(close(z[0], -1f64) && close(z[1], 0f64) && close(z[2], 1f64));
+        assert!(z[3].is_nan());
+
+        let m = minmax(&vec![2f64, 4f64, 6f64, Float::infinity()]);
+        assert_eq!(m.slice_to(3), [0f64, 0.5f64, 1f64].as_slice());
+        // constant columns are shifted, not divided by 0
+        assert_eq!(minmax(&vec![5f64, 5f64]), vec![0f64, 0f64]);
+    }
+
+    #[test]
+    fn box_cox_lambda() {
+        // exponential growth is straightened by the logarithm
+        let (result, lambda) = box_cox(&growth());
+        assert!(close(lambda, 0f64));
+        assert!(close(result[5], 1.5f64));
+
+        // values below 1 are shifted first
+        let (result, _) = box_cox(&vec![-3f64, 0f64, 2f64]);
+        assert!(result.iter().all(|x| x.is_finite()));
+        assert_eq!(box_cox(&vec![1f64]).val1(), 1f64);
+    }
+
+    #[test]
+    fn names() {
+        let mut norm = NormNone;
+        for _ in range(0u, 6) {
+            assert_eq!(parse_normalization(norm.name()), Ok(norm.clone()));
+            norm = norm.cycle();
+        }
+        assert_eq!(norm, NormNone);
+        assert!(parse_normalization("log").is_err());
+        assert!(apply(NormNone, &vec![1f64]).is_none());
+        assert_eq!(apply(NormBoxCox, &growth()).unwrap().val1(), "box-cox lambda=0".to_string());
+    }
+}
@@ -0,0 +1,367 @@
+use data;
+use std::cmp;
+use std::comm;
+use std::num::Float;
+
+static DEFAULT_COMPONENTS: uint = 3;
+static JACOBI_SWEEPS: uint = 100;
+
+#[deriving(Clone, PartialEq, Show)]
+pub enum NanMode {
+    // rows with NaN values are not used and get NaN components
+    NanDrop,
+    // NaN values are replaced by the column mean
+    NanImpute,
+}
+
+#[deriving(Clone, Show)]
+pub struct Spec {
+    // empty for all numeric columns
+    pub columns: Vec<String>,
+    pub components: uint,
+    pub standardize: bool,
+    pub nanMode: NanMode,
+}
+
+/// Parses a comma-separated list of column names and options, e.g. `std, k=2, impute, a, b, c`.
+pub fn parse_spec(s: &str) -> Result<Spec, String> {
+    let mut spec = Spec {
+        columns: Vec::new(),
+        components: DEFAULT_COMPONENTS,
+        standardize: false,
+        nanMode: NanDrop,
+    };
+    for part in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        if part == "std" || part == "standardize" {
+            spec.standardize = true;
+        } else if part == "impute" {
+            spec.nanMode = NanImpute;
+        } else if part == "drop" {
+            spec.nanMode = NanDrop;
+        } else if part.starts_with("k=") {
+            spec.components = match from_str::<uint>(part.slice_from(2).trim()) {
+                Some(k) if k > 0 => k,
+                _ => return Err(format!("invalid number of components {}", part.slice_from(2)))
+            };
+        } else {
+            spec.columns.push(part.to_string());
+        }
+    }
+    Ok(spec)
+}
+
+/// Whether `name` is the name of a principal component column, e.g. `PC2`.
+pub fn is_component(name: &String) -> bool {
+    name.as_slice().starts_with("PC") && from_str::<uint>(name.as_slice().slice_from(2)).is_some()
+}
+
+/// Columns and data copied from the table, so the computation can run in a background task.
+pub struct Input {
+    spec: Spec,
+    names: Vec<String>,
+    data: Vec<Vec<f64>>,
+}
+
+pub fn prepare(spec: &Spec, table: &data::Table) -> Result<Input, String> {
+    let names: Vec<String> = if spec.columns.is_empty() {
+        table.columns().iter()
+            .filter(|c| table.kind(*c) == data::KindNumeric && !is_component(*c))
+            .map(|c| c.clone())
+            .collect()
+    } else {
+        spec.columns.clone()
+    };
+    if names.len() < 2 {
+        return Err("PCA needs at least 2 columns".to_string());
+    }
+
+    let mut data = Vec::new();
+    for name in names.iter() {
+        match table.get(name) {
+            Some(d) => data.push(d.clone()),
+            None => return Err(format!("unknown column {}", name))
+        }
+    }
+    Ok(Input {
+        spec: spec.clone(),
+        names: names,
+        data: data,
+    })
+}
+
+/// Names of the columns `compute` adds for `input`.
+pub fn component_names(input: &Input) -> Vec<String> {
+    range(0, cmp::min(input.spec.components, input.names.len())).map(|i| format!("PC{}", i + 1)).collect()
+}
+
+/// Principal components as new columns, together with the explained variance ratio.
+pub struct Pca {
+    pub components: Vec<(String, Vec<f64>, f64)>,
+}
+
+impl Pca {
+    pub fn summary(&self) -> String {
+        let parts: Vec<String> = self.components.iter()
+            .map(|&(ref name, _, explained)| format!("{} {:.1}%", name, explained * 100f64))
+            .collect();
+        parts.connect(", ")
+    }
+}
+
+// eigenvalues and eigenvectors (as columns of a row-major matrix) of a symmetric matrix using
+// cyclic Jacobi rotations
+fn jacobi(mut a: Vec<f64>, n: uint) -> (Vec<f64>, Vec<f64>) {
+    let mut v = Vec::from_elem(n * n, 0f64);
+    for i in range(0, n) {
+        *v.get_mut(i * n + i) = 1f64;
+    }
+
+    for _ in range(0, JACOBI_SWEEPS) {
+        let mut off = 0f64;
+        let mut diag = 0f64;
+        for i in range(0, n) {
+            diag += a[i * n + i] * a[i * n + i];
+            for j in range(i + 1, n) {
+                off += a[i * n + j] * a[i * n + j];
+            }
+        }
+        if off <= 1e-24f64 * diag {
+            break;
+        }
+
+        for p in range(0, n) {
+            for q in range(p + 1, n) {
+                let apq = a[p * n + q];
+                if apq == 0f64 {
+                    continue;
+                }
+                let theta = (a[q * n + q] - a[p * n + p]) / (2f64 * apq);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1f64).sqrt());
+                let c = 1f64 / (t * t + 1f64).sqrt();
+                let s = t * c;
+
+                for k in range(0, n) {
+                    let akp = a[k * n + p];
+                    let akq = a[k * n + q];
+                    *a.get_mut(k * n + p) = c * akp - s * akq;
+                    *a.get_mut(k * n + q) = s * akp + c * akq;
+                }
+                for k in range(0, n) {
+                    let apk = a[p * n + k];
+                    let aqk = a[q * n + k];
+                    *a.get_mut(p * n + k) = c * apk - s * aqk;
+                    *a.get_mut(q * n + k) = s * apk + c * aqk;
+                }
+                for k in range(0, n) {
+                    let vkp = v[k * n + p];
+                    let vkq = v[k * n + q];
+                    *v.get_mut(k * n + p) = c * vkp - s * vkq;
+                    *v.get_mut(k * n + q) = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    (range(0, n).map(|i| a[i * n + i]).collect(), v)
+}
+
+pub fn compute(input: Input) -> Result<Pca, String> {
+    let Input{spec, names, mut data} = input;
+    let p = names.len();
+    let nrows = data[0].len();
+
+    // means over finite values, used for imputation
+    let mut means = Vec::new();
+    for (name, column) in names.iter().zip(data.iter()) {
+        let finite: Vec<f64> = column.iter().filter(|x| x.is_finite()).map(|&x| x).collect();
+        if finite.is_empty() {
+            return Err(format!("column {} has no values", name));
+        }
+        means.push(finite.iter().fold(0f64, |a, &b| a + b) / finite.len() as f64);
+    }
+    if spec.nanMode == NanImpute {
+        for (column, &mean) in data.mut_iter().zip(means.iter()) {
+            for x in column.mut_iter().filter(|x| !x.is_finite()) {
+                *x = mean;
+            }
+        }
+    }
+
+    let valid: Vec<bool> = range(0, nrows).map(|r| data.iter().all(|c| c[r].is_finite())).collect();
+    let m = valid.iter().filter(|&&v| v).count();
+    if m < 2 {
+        return Err("not enough rows without NaN values".to_string());
+    }
+
+    // center (and scale) on the rows that are used
+    let mut center = Vec::new();
+    let mut scale = Vec::new();
+    for column in data.iter() {
+        let mean = column.iter().zip(valid.iter()).filter(|&(_, &v)| v).fold(0f64, |a, (&x, _)| a + x) / m as f64;
+        let var = column.iter().zip(valid.iter()).filter(|&(_, &v)| v).fold(0f64, |a, (&x, _)| a + (x - mean) * (x - mean)) / (m - 1) as f64;
+        center.push(mean);
+        scale.push(if spec.standardize && var > 0f64 { var.sqrt() } else { 1f64 });
+    }
+
+    let mut cov = Vec::from_elem(p * p, 0f64);
+    for r in range(0, nrows).filter(|&r| valid[r]) {
+        let row: Vec<f64> = range(0, p).map(|j| (data[j][r] - center[j]) / scale[j]).collect();
+        for i in range(0, p) {
+            for j in range(i, p) {
+                *cov.get_mut(i * p + j) += row[i] * row[j];
+            }
+        }
+    }
+    for i in range(0, p) {
+        for j in range(i, p) {
+            let c = cov[i * p + j] / (m - 1) as f64;
+            *cov.get_mut(i * p + j) = c;
+            *cov.get_mut(j * p + i) = c;
+        }
+    }
+
+    let (values, vectors) = jacobi(cov, p);
+    let total = values.iter().fold(0f64, |a, &b| a + b.max(0f64));
+    let mut order: Vec<uint> = range(0, p).collect();
+    order.sort_by(|&a, &b| values[b].partial_cmp(&values[a]).unwrap());
+
+    let mut components = Vec::new();
+    for (i, &e) in order.iter().take(spec.components).enumerate() {
+        let mut loadings: Vec<f64> = range(0, p).map(|j| vectors[j * p + e]).collect();
+
+        // eigenvectors have no sign, make the largest loading positive so results are stable
+        let largest = loadings.iter().fold(0f64, |a, &b| if b.abs() > a.abs() { b } else { a });
+        if largest < 0f64 {
+            for l in loadings.mut_iter() {
+                *l = -*l;
+            }
+        }
+
+        let projected: Vec<f64> = range(0, nrows).map(|r| {
+            if valid[r] {
+                range(0, p).fold(0f64, |a, j| a + loadings[j] * (data[j][r] - center[j]) / scale[j])
+            } else {
+                Float::nan()
+            }
+        }).collect();
+        let explained = if total > 0f64 { values[e].max(0f64) / total } else { 0f64 };
+        components.push((format!("PC{}", i + 1), projected, explained));
+    }
+
+    Ok(Pca {
+        components: components,
+    })
+}
+
+/// Runs `compute` in a background task, the result is sent once it is done.
+pub fn start(input: Input) -> comm::Receiver<Result<Pca, String>> {
+    let (tx, rx) = comm::channel();
+    spawn(proc() {
+        tx.send(compute(input));
+    });
+    rx
+}
+
+/// Adds the components as columns, the explained variance becomes their unit.
+pub fn apply(pca: &Pca, table: &mut data::Table) {
+    for &(ref name, ref values, explained) in pca.components.iter() {
+        table.add_column(name, values.clone());
+        table.set_unit(name, Some(format!("{:.1}% var", explained * 100f64)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use data;
+    use std::collections::TreeSet;
+    use std::iter::FromIterator;
+    use std::num::Float;
+    use super::{apply, compute, is_component, jacobi, parse_spec, prepare, NanDrop, NanImpute};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    // points on the line b = 2 a, plus the given extra rows
+    fn line(extra: &[(f64, f64)]) -> data::Table {
+        let columns: TreeSet<String> = FromIterator::from_iter(vec!["a".to_string(), "b".to_string()].move_iter());
+        let mut table = data::Table::new("test".to_string(), columns);
+        for i in range(0u, 10) {
+            table.push(&vec![i as f64, 2f64 * i as f64]);
+        }
+        for &(a, b) in extra.iter() {
+            table.push(&vec![a, b]);
+        }
+        table
+    }
+
+    #[test]
+    fn eigen_decomposition() {
+        let (values, vectors) = jacobi(vec![2f64, 1f64, 1f64, 2f64], 2);
+        let mut sorted = values.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        assert!(close(sorted[0], 1f64) && close(sorted[1], 3f64));
+        // the eigenvectors are orthonormal columns
+        assert!(close(vectors[0] * vectors[1] + vectors[2] * vectors[3], 0f64));
+        assert!(close(vectors[0] * vectors[0] + vectors[2] * vectors[2], 1f64));
+    }
+
+    #[test]
+    fn components_of_a_line() {
+        let pca = compute(prepare(&parse_spec("").unwrap(), &line(&[])).unwrap()).unwrap();
+        assert_eq!(pca.components.len(), 2);
+        let (ref name, ref values, explained) = pca.components[0];
+        assert_eq!(name, &"PC1".to_string());
+        assert!(close(explained, 1f64));
+        // the largest loading is positive, so PC1 grows with a
+        for (i, &x) in values.iter().enumerate() {
+            assert!(close(x, 5f64.sqrt() * (i as f64 - 4.5f64)));
+        }
+        assert!(pca.components[1].val1().iter().all(|&x| close(x, 0f64)));
+        assert!(close(pca.components[1].val2(), 0f64));
+    }
+
+    #[test]
+    fn missing_values() {
+        let table = line(&[(Float::nan(), 1f64)]);
+        let dropped = compute(prepare(&parse_spec("k=1").unwrap(), &table).unwrap()).unwrap();
+        assert_eq!(dropped.components.len(), 1);
+        assert!(dropped.components[0].val1()[10].is_nan());
+        assert!(dropped.components[0].val1()[9].is_finite());
+
+        let imputed = compute(prepare(&parse_spec("k=1, impute").unwrap(), &table).unwrap()).unwrap();
+        assert!(imputed.components[0].val1().iter().all(|x| x.is_finite()));
+
+        let mut table = table;
+        apply(&dropped, &mut table);
+        assert!(table.get(&"PC1".to_string()).is_some());
+        assert_eq!(table.unit(&"PC1".to_string()), Some(&"100.0% var".to_string()));
+        // components of an earlier run are not used as input
+        assert_eq!(prepare(&parse_spec("").unwrap(), &table).unwrap().names, vec!["a".to_string(), "b".to_string()]);
+    }
+
+    #[test]
+    fn specs() {
+        let spec = parse_spec("std, k=2, impute, a, b").unwrap();
+        assert_eq!(spec.columns, vec!["a".to_string(), "b".to_string()]);
+        assert_eq!(spec.components, 2);
+        assert!(spec.standardize);
+        assert_eq!(spec.nanMode, NanImpute);
+        assert_eq!(parse_spec("impute, drop").unwrap().nanMode, NanDrop);
+        assert!(parse_spec("k=0").is_err());
+        assert!(parse_spec("k=x").is_err());
+
+        let table = line(&[]);
+        assert!(prepare(&parse_spec("a").unwrap(), &table).is_err());
+        assert!(prepare(&parse_spec("a, c").unwrap(), &table).is_err());
+    }
+
+    #[test]
+    fn component_names() {
+        assert!(is_component(&"PC1".to_string()));
+        assert!(is_component(&"PC12".to_string()));
+        assert!(!is_component(&"PC".to_string()));
+        assert!(!is_component(&"PCA".to_string()));
+        assert!(!is_component(&"xPC1".to_string()));
+    }
+}
@@ -0,0 +1,114 @@
+// score bonus for matches at the start of the name and right after the previous match
+static BONUS_START: int = 8;
+static BONUS_CONSECUTIVE: int = 4;
+
+/// Scores `name` for the search `query`, None if the characters of `query` do not appear
+/// in this order. Case is ignored, higher is better.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<int> {
+    let name: Vec<char> = name.chars().map(|c| c.to_lowercase()).collect();
+    let mut score = 0i;
+    let mut pos = 0u;
+    let mut last: Option<uint> = None;
+    for q in query.chars().map(|c| c.to_lowercase()) {
+        let found = match name.slice_from(pos).iter().position(|&c| c == q) {
+            Some(i) => pos + i,
+            None => return None
+        };
+        score += 1;
+        if found == 0 {
+            score += BONUS_START;
+        }
+        match last {
+            Some(l) if l + 1 == found => score += BONUS_CONSECUTIVE,
+            Some(l) => score -= (found - l - 1) as int,
+            None => score -= found as int
+        }
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+/// Column list filtered by a search text.
+pub struct Picker {
+    // all columns in the order they are listed without a query
+    columns: Vec<String>,
+    pub query: String,
+    // best match first
+    pub matches: Vec<String>,
+    // index into `matches`
+    pub selected: uint,
+}
+
+impl Picker {
+    pub fn new(columns: Vec<String>) -> Picker {
+        let mut picker = Picker {
+            columns: columns,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+        };
+        picker.update();
+        picker
+    }
+
+    /// Matches the columns against the query again, the selection goes back to the best match.
+    pub fn update(&mut self) {
+        let mut scored: Vec<(int, &String)> = self.columns.iter()
+            .filter_map(|c| fuzzy_score(self.query.as_slice(), c.as_slice()).map(|s| (s, c)))
+            .collect();
+        // columns keep their order on equal scores
+        scored.sort_by(|&(a, _), &(b, _)| b.cmp(&a));
+        self.matches = scored.move_iter().map(|(_, c)| c.clone()).collect();
+        self.selected = 0;
+    }
+
+    /// Moves the selection by `delta` rows, stopping at both ends.
+    pub fn step(&mut self, delta: int) {
+        if self.matches.is_empty() {
+            return;
+        }
+        let last = (self.matches.len() - 1) as int;
+        self.selected = (self.selected as int + delta).max(0).min(last) as uint;
+    }
+
+    pub fn current<'a>(&'a self) -> Option<&'a String> {
+        self.matches.as_slice().get(self.selected)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fuzzy_score, Picker};
+
+    #[test]
+    fn scores() {
+        assert_eq!(fuzzy_score("", "abc"), Some(0));
+        assert_eq!(fuzzy_score("temp", "temperature"), Some(24));
+        assert_eq!(fuzzy_score("TEMP", "temperature"), fuzzy_score("temp", "Temperature"));
+        assert_eq!(fuzzy_score("temp", "max_temp"), Some(12));
+        assert_eq!(fuzzy_score("abc", "xaxbxc"), Some(0));
+        assert_eq!(fuzzy_score("tp", "pt"), None);
+        assert_eq!(fuzzy_score("x", ""), None);
+    }
+
+    #[test]
+    fn matches_and_selection() {
+        let mut picker = Picker::new(vec!["b_temp".to_string(), "a".to_string(), "temperature".to_string()]);
+        // without a query the given order is kept
+        assert_eq!(picker.matches, vec!["b_temp".to_string(), "a".to_string(), "temperature".to_string()]);
+
+        picker.query = "temp".to_string();
+        picker.update();
+        assert_eq!(picker.matches, vec!["temperature".to_string(), "b_temp".to_string()]);
+        picker.step(10);
+        assert_eq!(picker.current(), Some(&"b_temp".to_string()));
+        picker.step(-5);
+        assert_eq!(picker.selected, 0);
+
+        picker.query = "zz".to_string();
+        picker.update();
+        picker.step(1);
+        assert_eq!(picker.current(), None);
+    }
+}
@@ -0,0 +1,65 @@
+/// What the text of a prompt is used for.
+#[deriving(Clone, PartialEq, Show)]
+pub enum PromptKind {
+    PromptDerive,
+    PromptFilter,
+    PromptPca,
+    PromptTsne,
+    PromptCluster,
+    PromptRange,
+}
+
+/// A line of text entered at the bottom of the window.
+pub struct Prompt {
+    pub kind: PromptKind,
+    pub text: String,
+    // error of the last submit, the prompt stays open until it succeeds
+    pub error: Option<String>,
+}
+
+impl Prompt {
+    pub fn new(kind: PromptKind) -> Prompt {
+        Prompt::with_text(kind, "")
+    }
+
+    pub fn with_text(kind: PromptKind, text: &str) -> Prompt {
+        Prompt {
+            kind: kind,
+            text: text.to_string(),
+            error: None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self.kind {
+            PromptDerive => "define",
+            PromptFilter => "filter",
+            PromptPca => "pca",
+            PromptTsne => "t-sne",
+            PromptCluster => "cluster",
+            PromptRange => "range",
+        }
+    }
+
+    /// Label, text with a cursor and the error, if any.
+    pub fn display(&self) -> String {
+        match self.error {
+            Some(ref e) => format!("{}: {}_\nerror: {}", self.label(), self.text, e),
+            None => format!("{}: {}_", self.label(), self.text)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Prompt, PromptFilter, PromptRange};
+
+    #[test]
+    fn display() {
+        let mut prompt = Prompt::with_text(PromptFilter, "a > 1");
+        assert_eq!(prompt.display(), "filter: a > 1_".to_string());
+        prompt.error = Some("unknown column: a".to_string());
+        assert_eq!(prompt.display(), "filter: a > 1_\nerror: unknown column: a".to_string());
+        assert_eq!(Prompt::new(PromptRange).display(), "range: _".to_string());
+    }
+}
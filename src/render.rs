@@ -1,21 +1,46 @@
 use cgmath;
 use cgmath::FixedArray;
-use cgmath::Matrix;
+use cluster;
+use config;
+use control;
+use crossfilter;
 use data;
+use datetime;
+use export;
+use expr;
 use gl;
 use glfw;
 use glfw::Context;
 use graphics;
-use graphics::{AddLine, AddRoundBorder, AddColor, Draw, RelativeTransform2d};
+use graphics::{AddLine, AddRectangle, AddRoundBorder, AddColor, Draw, RelativeTransform2d};
 use hgl;
+use keymap;
+use loader;
+use normalize;
 use opengl_graphics;
+use pca;
+use picker;
+use prompt::{Prompt, PromptCluster, PromptDerive, PromptFilter, PromptPca, PromptRange, PromptTsne};
+use serialize::json;
+use serialize::json::ToJson;
+use session;
+use source;
+use stats;
+use std::cmp;
+use std::collections::{HashMap, TreeMap, TreeSet};
 use std::comm;
-use std::f32;
+use std::f64;
 use std::io;
 use std::mem;
+use std::num::Float;
+use std::path::Path;
 use std::ptr;
 use std::time;
+use stream;
 use textdrawer;
+use tsne;
+use undo;
+use watch;
 
 static FONT_DATA: &'static [u8] = include_bin!("../res/DejaVuSansCondensed-Bold.ttf");
 static LIB_SHADER_GRADIENT: &'static str = include_str!("../res/gradient.lib.glsl");
@@ -25,7 +50,7 @@ static VERTEX_SHADER_TEXTURE: &'static str = include_str!("../res/texture.vertex
 static FRAGMENT_SHADER_TEXTURE: &'static str = include_str!("../res/texture.fragment.glsl");
 static VERTEX_SHADER_LEGEND: &'static str = include_str!("../res/legend.vertex.glsl");
 static FRAGMENT_SHADER_LEGEND: &'static str = include_str!("../res/legend.fragment.glsl");
-static HELP_TEXT: &'static str = include_str!("../res/help.txt");
+static MOUSE_HELP: &'static str = include_str!("../res/mouse-help.txt");
 
 static VERTEX_DATA_TEXTURE: [gl::types::GLfloat, ..12] = [
     -1.0, -1.0,
@@ -36,107 +61,418 @@ static VERTEX_DATA_TEXTURE: [gl::types::GLfloat, ..12] = [
     1.0, 1.0,
 ];
 
-static MARGIN: f32 = 130f32;
-static TICK_DISTANCE: i32 = 60i32;
-static FONT_SIZE: u32 = 16u32;
 static LABEL_MARGIN: f64 = 50f64;
 static INFO_MARGIN: f64 = 2f64;
 static TICK_LENGTH: f64 = 6f64;
 static TICK_WIDTH: f64 = 0.5f64;
 static PAUSE_MS: i32 = 20;
+static REBASE_FACTOR: f64 = 4f64;
+static PANEL_WIDTH: f64 = 240f64;
+static PANEL_ROW_HEIGHT: f64 = 48f64;
+static PANEL_PADDING: f64 = 8f64;
+static CENTROID_SIZE: f64 = 6f64;
+static STATS_LABEL_WIDTH: f64 = 80f64;
+static STATS_VALUE_WIDTH: f64 = 200f64;
+static PANEL_HISTOGRAM_HEIGHT: f64 = 20f64;
+// zoom factor of one wheel step
+static WHEEL_ZOOM: f64 = 1.1f64;
+// smaller zoom rectangles are ignored, e.g. plain clicks
+static MIN_ZOOM_PIXELS: f64 = 4f64;
+static PICKER_ROW_HEIGHT: f64 = 20f64;
+// rows skipped by PageUp/PageDown in the column picker
+static PICKER_PAGE: int = 10;
+// exports draw the points as circles up to this number of visible points, a density raster above
+static EXPORT_CIRCLE_LIMIT: uint = 20000;
+// resolution of the exported density raster relative to the window
+static EXPORT_RASTER_SCALE: f32 = 4f32;
+// largest offscreen framebuffer used for exports, larger images are rendered in tiles
+static EXPORT_TILE_SIZE: uint = 2048;
+// colors of the exported Z legend
+static EXPORT_LEGEND_STEPS: uint = 64;
+
+// writes `data` into the buffer starting at element `offset`
+fn upload_f32(vbo: &hgl::buffer::Vbo, offset: uint, data: &Vec<f32>) {
+    vbo.bind();
+    unsafe {
+        gl::BufferSubData(gl::ARRAY_BUFFER, (offset * mem::size_of::<f32>()) as gl::types::GLintptr, (data.len() * mem::size_of::<f32>()) as gl::types::GLsizeiptr, mem::transmute(data.as_ptr()));
+    }
+}
 
-fn range_vec(vec: &Vec<f32>) -> (f32, f32) {
-    let min = vec.tail().iter().fold(vec[0] + 0.0, |a, &b| a.min(b));
-    let max = vec.tail().iter().fold(vec[0] + 0.0, |a, &b| a.max(b));
+fn range_vec(vec: &[f64]) -> (f64, f64) {
+    let mut min = Float::nan();
+    let mut max = Float::nan();
+    for &x in vec.iter().filter(|x| x.is_finite()) {
+        // NaN.min(x) returns x, so the first finite value initializes both
+        min = min.min(x);
+        max = max.max(x);
+    }
     (min, max)
 }
 
-fn nice_num(x: f32, round: bool) -> f32 {
+fn rebase_data(data: &[f64], origin: f64, clamp: Option<(f64, f64)>) -> Vec<f32> {
+    match clamp {
+        Some((min, max)) => data.iter().map(|&x| {
+            if x.is_nan() {
+                x as f32
+            } else {
+                (x.max(min).min(max) - origin) as f32
+            }
+        }).collect(),
+        None => data.iter().map(|&x| (x - origin) as f32).collect()
+    }
+}
+
+/// Parses `LO,HI` with LO < HI.
+pub fn parse_limits(s: &str) -> Result<(f64, f64), String> {
+    let parts: Vec<&str> = s.split(',').map(|p| p.trim()).collect();
+    if parts.len() != 2 {
+        return Err(format!("expected LO,HI, got {}", s));
+    }
+    match (from_str::<f64>(parts[0]), from_str::<f64>(parts[1])) {
+        (Some(lo), Some(hi)) if lo < hi => Ok((lo, hi)),
+        (Some(_), Some(_)) => Err(format!("lower bound of {} is not below the upper one", s)),
+        _ => Err(format!("invalid range {}", s))
+    }
+}
+
+fn nice_num(x: f64, round: bool) -> f64 {
     let exp = x.log10().floor() as i32;
-    let f = x / 10f32.powi(exp);
+    let f = x / 10f64.powi(exp);
 
     let nf = if round {
-        if f < 1.5f32 {
-            1f32
-        } else if f < 3f32 {
-            2f32
-        } else if f < 7f32 {
-            5f32
+        if f < 1.5f64 {
+            1f64
+        } else if f < 3f64 {
+            2f64
+        } else if f < 7f64 {
+            5f64
         } else {
-            10f32
+            10f64
         }
     } else {
-        if f < 1f32 {
-            1f32
-        } else if f < 2f32 {
-            2f32
-        } else if f < 5f32 {
-            5f32
+        if f < 1f64 {
+            1f64
+        } else if f < 2f64 {
+            2f64
+        } else if f < 5f64 {
+            5f64
         } else {
-            10f32
+            10f64
         }
     };
 
-    nf * 10f32.powi(exp)
+    nf * 10f64.powi(exp)
+}
+
+// framebuffer drawing into a new texture of `width` x `height` pixels, stays bound
+fn create_target(width: i32, height: i32, format: gl::types::GLenum) -> (gl::types::GLuint, gl::types::GLuint) {
+    let mut framebuffer = 0;
+    unsafe {
+        gl::GenFramebuffers(1, &mut framebuffer);
+    }
+    gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
+
+    let mut texture = 0;
+    unsafe {
+        gl::GenTextures(1, &mut texture);
+    }
+    gl::BindTexture(gl::TEXTURE_2D, texture);
+    unsafe {
+        gl::TexImage2D(gl::TEXTURE_2D, 0, format as i32, width, height, 0, gl::RGBA, gl::FLOAT, ptr::null());
+    }
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
+    gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+
+    gl::FramebufferTexture(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, texture, 0);
+    let drawBuffers = [gl::COLOR_ATTACHMENT0];
+    unsafe {
+        gl::DrawBuffers(drawBuffers.len() as i32, mem::transmute(&drawBuffers[0]));
+    }
+    (framebuffer, texture)
+}
+
+fn delete_target((framebuffer, texture): (gl::types::GLuint, gl::types::GLuint)) {
+    gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+    unsafe {
+        gl::DeleteTextures(1, &texture);
+        gl::DeleteFramebuffers(1, &framebuffer);
+    }
+}
+
+fn std_scale(renderLength: i32, margin: f32) -> f64 {
+    1f64 - 2f64 * margin as f64 / renderLength as f64
 }
 
-fn std_scale(renderLength: i32) -> f32 {
-    1f32 - 2f32 * MARGIN / renderLength as f32
+enum Axis {
+    AxisX,
+    AxisY,
+    AxisZ,
 }
 
 enum ActiveTransform {
     TransformMove,
     TransformScale,
+    // row of the crossfilter panel and if the upper bound is dragged
+    TransformRange(uint, bool),
+    // corner where the zoom rectangle started
+    TransformZoomBox(f64, f64),
+    // axis strip and position where the zoom interval started
+    TransformZoomAxis(Axis, f64),
     TransformNone,
 }
 
+pub struct Options {
+    pub rangesFile: Option<Path>,
+    pub rangeMode: stats::RangeMode,
+    pub hideOutliers: bool,
+    // exact axis ranges, applied after the session
+    pub xlim: Option<(f64, f64)>,
+    pub ylim: Option<(f64, f64)>,
+    pub zlim: Option<(f64, f64)>,
+    pub equalAspect: bool,
+    pub pca: Option<pca::Spec>,
+    pub tsne: Option<tsne::Spec>,
+    // where the table came from, used to load it again, None for streams
+    pub source: Option<source::Source>,
+    pub resume: Option<loader::Resume>,
+    pub stream: Option<stream::Stream>,
+    // only the last rows are kept
+    pub window: Option<uint>,
+    // axis ranges grow with new rows
+    pub autoRange: bool,
+    // re-applied whenever rows are added
+    pub definitions: Vec<expr::Definition>,
+    pub control: Option<control::Server>,
+    pub config: config::Config,
+    // restored after the window is opened
+    pub session: Option<session::Session>,
+    pub sessionFile: Option<Path>,
+    // written once the view is set up, then the renderer quits
+    pub export: Option<(Path, Option<f32>)>,
+}
+
 struct Dimension {
     renderLength: i32,
-    d: f32,
-    s: f32,
+    // space for the axes at both ends in pixels
+    margin: f32,
+    d: f64,
+    s: f64,
     vbo: hgl::buffer::Vbo,
-    min: f32,
-    max: f32,
+    min: f64,
+    max: f64,
+    // subtracted before upload, follows the view when zooming in so f32 keeps enough precision
+    origin: f64,
+    // values outside of this range are moved to the border
+    clamp: Option<(f64, f64)>,
+    // transformed data, None if the column is shown as it is
+    values: Option<Vec<f64>>,
     name: String,
+    title: String,
+    kind: data::ColumnKind,
 }
 
 impl Dimension {
-    fn new(renderLength: i32, table: &data::Table, name: &String) -> Dimension {
-        let data = table.get(name).unwrap();
-        let (min, max) = range_vec(data);
-        let vbo = hgl::Vbo::from_data(data.as_slice(), hgl::StaticDraw);
+    // the buffer has room for `capacity` rows, so appended rows can be uploaded in place
+    fn new(renderLength: i32, margin: f32, capacity: uint, table: &data::Table, name: &String, norm: normalize::Normalization, mode: stats::RangeMode, clamp: bool) -> Dimension {
+        let source = table.get(name).unwrap();
+        let (values, title, (min, max)) = match normalize::apply(norm, source) {
+            Some((values, label)) => {
+                let range = stats::compute(&values).range(mode);
+                (Some(values), format!("{} ({})", table.title(name), label), range)
+            },
+            None => {
+                let range = match table.stats(name) {
+                    Some(s) => s.range(mode),
+                    None => range_vec(source.as_slice())
+                };
+                (None, table.title(name), range)
+            }
+        };
+        let origin = if min.is_nan() || max.is_nan() {
+            0f64
+        } else {
+            (min + max) / 2f64
+        };
+        let clamp = if clamp && min <= max { Some((min, max)) } else { None };
+        let mut rebased = rebase_data(values.as_ref().unwrap_or(source).as_slice(), origin, clamp);
+        if capacity > rebased.len() {
+            let padding = capacity - rebased.len();
+            rebased.grow(padding, &0f32);
+        }
+        let vbo = hgl::Vbo::from_data(rebased.as_slice(), hgl::StaticDraw);
         Dimension{
             renderLength: renderLength,
-            d: 0f32,
-            s: std_scale(renderLength),
+            margin: margin,
+            d: 0f64,
+            s: std_scale(renderLength, margin),
             vbo: vbo,
             min: min,
             max: max,
-            name: name.clone()
+            origin: origin,
+            clamp: clamp,
+            values: values,
+            name: name.clone(),
+            title: title,
+            kind: if norm == normalize::NormNone { table.kind(name) } else { data::KindNumeric },
+        }
+    }
+
+    // the shown values, transformed if a normalization is active
+    fn data<'a>(&'a self, table: &'a data::Table) -> &'a Vec<f64> {
+        match self.values {
+            Some(ref v) => v,
+            None => table.get(&self.name).unwrap()
+        }
+    }
+
+    // clears `mask` for values outside of min..max
+    fn hide_outliers(&self, table: &data::Table, mask: &mut Vec<bool>) {
+        for (m, &x) in mask.mut_iter().zip(self.data(table).iter()) {
+            if x < self.min || x > self.max {
+                *m = false;
+            }
         }
     }
 
     fn reset(&mut self) {
-        self.d = 0f32;
-        self.s = std_scale(self.renderLength);
+        self.d = 0f64;
+        self.s = self.edge();
+    }
+
+    // normalized device coordinate of the plot border
+    fn edge(&self) -> f64 {
+        std_scale(self.renderLength, self.margin)
+    }
+
+    // span and center used for the projection, degenerated data gets a unit range
+    fn extent(&self) -> (f64, f64) {
+        if self.min.is_nan() || self.max.is_nan() || self.min == self.max {
+            (2f64, self.origin)
+        } else {
+            (self.max - self.min, (self.max + self.min) / 2f64)
+        }
+    }
+
+    // normalized device coordinate of `x` in the current view
+    fn ndc(&self, x: f64) -> f64 {
+        let (span, center) = self.extent();
+        2f64 * self.s * (x - center) / span + 2f64 * self.d / span
+    }
+
+    fn span(&self) -> f64 {
+        self.extent().val0()
+    }
+
+    // returns (a, b) so that a * (x - origin) + b maps x to normalized device coordinates
+    fn coefficients(&self, s: f64, d: f64) -> (f32, f32) {
+        let (span, center) = self.extent();
+        let a = 2f64 * s / span;
+        let b = a * (self.origin - center) + 2f64 * d / span;
+        (a as f32, b as f32)
+    }
+
+    fn upload(&self, table: &data::Table) {
+        let rebased = rebase_data(self.data(table).as_slice(), self.origin, self.clamp);
+        upload_f32(&self.vbo, 0, &rebased);
+    }
+
+    // widens the range to the rows starting at `from`, returns true if it changed
+    fn expand(&mut self, table: &data::Table, from: uint) -> bool {
+        let (lo, hi) = range_vec(self.data(table).slice_from(from));
+        if lo.is_nan() {
+            return false;
+        }
+        let (min, max) = if self.min.is_nan() {
+            (lo, hi)
+        } else {
+            (self.min.min(lo), self.max.max(hi))
+        };
+        if min == self.min && max == self.max {
+            return false;
+        }
+        self.min = min;
+        self.max = max;
+        if self.clamp.is_some() {
+            self.clamp = Some((min, max));
+        }
+        true
+    }
+
+    // data range shown between the normalized device coordinates -edge and edge
+    fn visible(&self, s: f64, d: f64, edge: f64) -> (f64, f64) {
+        let (span, center) = self.extent();
+        let a = center + (-edge * span / 2f64 - d) / s;
+        let b = center + (edge * span / 2f64 - d) / s;
+        (a.min(b), a.max(b))
+    }
+
+    // inverse of `ndc` for the given scale and offset
+    fn value(&self, ndc: f64, s: f64, d: f64) -> f64 {
+        let (span, center) = self.extent();
+        center + (ndc * span / 2f64 - d) / s
     }
 
-    fn calc_axis_markers(&self, pixelsPerTick: i32) -> (u32, f32, f32, Vec<f32>) {
+    // scales the view by `factor` while `anchor` stays at the same position
+    fn zoom(&mut self, anchor: f64, factor: f64) {
+        let center = self.extent().val1();
+        let s = self.s * factor;
+        self.d += (self.s - s) * (anchor - center);
+        self.s = s;
+    }
+
+    // scale and offset that show lo..hi between -edge and edge
+    fn fit(&self, lo: f64, hi: f64, edge: f64) -> (f64, f64) {
+        let (span, center) = self.extent();
+        let s = edge * span / (hi - lo);
+        (s, -s * ((lo + hi) / 2f64 - center))
+    }
+
+    // uploads the rows starting at `from`, the buffer needs room for them
+    fn append(&self, table: &data::Table, from: uint) {
+        let rebased = rebase_data(self.data(table).slice_from(from), self.origin, self.clamp);
+        upload_f32(&self.vbo, from, &rebased);
+    }
+
+    // moves the origin to the view center when zoomed in far enough that the distance
+    // between both loses precision in f32
+    fn rebase(&mut self, table: &data::Table) {
+        let (span, center) = self.extent();
+        let viewCenter = center - self.d / self.s;
+        if (viewCenter - self.origin).abs() > REBASE_FACTOR * span / self.s.abs() && !viewCenter.is_nan() {
+            self.origin = viewCenter;
+            self.upload(table);
+        }
+    }
+
+    fn calc_axis_markers(&self, pixelsPerTick: i32) -> (f64, f64, Vec<(f64, String)>) {
         // precalc projection
-        let minVar = (self.min - self.d / std_scale(self.renderLength) - (self.max + self.min) / 2f32) / self.s * std_scale(self.renderLength) + (self.max + self.min) / 2f32;
-        let maxVar = (self.max - self.d / std_scale(self.renderLength) - (self.max + self.min) / 2f32) / self.s * std_scale(self.renderLength) + (self.max + self.min) / 2f32;
+        let std = self.edge();
+        let center = (self.max + self.min) / 2f64;
+        let minVar = (self.min - self.d / std - center) / self.s * std + center;
+        let maxVar = (self.max - self.d / std - center) / self.s * std + center;
 
         // calc ticks, borders, steps
-        let ntick = ((self.renderLength as f32 - 2f32 * MARGIN) / pixelsPerTick as f32) as i32;
+        let ntick = ((self.renderLength as f32 - 2f32 * self.margin) / pixelsPerTick as f32) as i32;
+
+        if self.kind == data::KindTime {
+            return (minVar, maxVar, datetime::calc_time_markers(minVar, maxVar, ntick));
+        }
+
         let range = nice_num(maxVar - minVar, false);
-        let d = nice_num(range / (ntick - 1) as f32, true);
+        let mut d = nice_num(range / (ntick - 1) as f64, true);
+        if self.kind == data::KindCategorical {
+            // labels are integers, there is nothing in between
+            d = d.max(1f64);
+        }
         let graphMin = (minVar / d).floor() * d;
         let graphMax = (maxVar / d).ceil() * d;
-        let nfrac = [0u32, -d.log10().floor() as u32].iter().max().unwrap().clone();
+        let nfrac = [0i32, -d.log10().floor() as i32].iter().max().unwrap().clone() as uint;
 
         // generate markers
-        let mut markers: Vec<f32> = Vec::new();
-        cfor!{let mut m = graphMin; m < graphMax + 0.5f32 * d; (m += d) {
+        let mut markers: Vec<(f64, String)> = Vec::new();
+        cfor!{let mut m = graphMin; m < graphMax + 0.5f64 * d; (m += d) {
             let marker = if m < minVar {
                 minVar
             } else if m > maxVar {
@@ -144,49 +480,25 @@ impl Dimension {
             } else {
                 m
             };
-            markers.push(marker);
+            markers.push((marker, f64::to_str_digits(marker, nfrac + 1)));
+            // the step is lost in the rounding of large values
+            if m + d == m {
+                break;
+            }
         }}
 
-        (nfrac, minVar, maxVar, markers)
+        (minVar, maxVar, markers)
     }
 }
 
-fn calc_projection(dimx: &Dimension, dimy: &Dimension, dimz: &Dimension) -> cgmath::Matrix4<f32> {
-    let (xmin, xmax) = if dimx.min.is_nan() || dimx.max.is_nan() || dimx.min == dimx.max {
-        (-1f32, 1f32)
-    } else {
-        (dimx.min, dimx.max)
-    };
-    let (ymin, ymax) = if dimy.min.is_nan() || dimy.max.is_nan() || dimy.min == dimy.max {
-        (-1f32, 1f32)
-    } else {
-        (dimy.min, dimy.max)
-    };
-    let (zmin, zmax) = if dimz.min.is_nan() || dimz.max.is_nan() || dimz.min == dimz.max {
-        (-1f32, 1f32)
-    } else {
-        (dimz.min, dimz.max)
-    };
-
-    let mut result = cgmath::ortho(
-        xmin, xmax,
-        ymin, ymax,
-        zmin, zmax
-    );
-
-    // fix z projection
-    result.as_mut_fixed()[2][3] = 0f32;
-    result.as_mut_fixed()[3][2] *= -1f32;
-
-    result
-}
-
 struct UniformLocationPoints {
     width: gl::types::GLint,
     height: gl::types::GLint,
     pointScale: gl::types::GLint,
     transformation: gl::types::GLint,
     margin: gl::types::GLint,
+    tileOrigin: gl::types::GLint,
+    tileSize: gl::types::GLint,
 }
 
 struct UniformLocationTexture {
@@ -199,6 +511,7 @@ struct UniformLocationLegend {
     width: gl::types::GLint,
     height: gl::types::GLint,
     margin: gl::types::GLint,
+    tile: gl::types::GLint,
 }
 
 struct Renderer {
@@ -209,35 +522,92 @@ struct Renderer {
     dimx: Dimension,
     dimy: Dimension,
     dimz: Dimension,
-    dimzDelta: f32,
-    dimzScale: f32,
+    dimzDelta: f64,
+    dimzScale: f64,
     activeTransform: ActiveTransform,
     mouseX: f32,
     mouseY: f32,
     pointScale: f32,
     alphaScale: f32,
-    projection: cgmath::Matrix4<f32>,
     ulocationPoints: UniformLocationPoints,
     ulocationTexture: UniformLocationTexture,
     ulocationLegend: UniformLocationLegend,
     vaoPoints: hgl::vao::Vao,
     vaoTexture: hgl::vao::Vao,
     vboTexture: hgl::buffer::Vbo,
+    vboMask: hgl::buffer::Vbo,
+    filter: Option<expr::Expr>,
+    filterText: String,
+    filterMask: Vec<bool>,
+    crossfilter: crossfilter::Crossfilter,
+    rangesPath: Path,
+    sessionPath: Path,
+    history: undo::History,
+    // visible X, Y and Z ranges before each zoom
+    zoomStack: Vec<((f64, f64), (f64, f64), (f64, f64))>,
+    showPanel: bool,
+    panelScroll: uint,
+    visibleCount: uint,
+    rangeMode: stats::RangeMode,
+    hideOutliers: bool,
+    // same data units per pixel on X and Y
+    equalAspect: bool,
+    source: Option<source::Source>,
+    // where reading the shown table stopped
+    resume: Option<loader::Resume>,
+    stream: Option<stream::Stream>,
+    control: Option<control::Server>,
+    autoRange: bool,
+    watcher: Option<watch::Watcher>,
+    // the file changed while it was loaded
+    reloadPending: bool,
+    // rows that fit into the buffers
+    capacity: uint,
+    // the current table is a sample
+    sampled: bool,
+    // the table in the background and whether it is a sample
+    loadTask: Option<(comm::Receiver<Result<(data::Table, loader::Resume), String>>, bool)>,
+    // the sample or full table that is not shown
+    otherTable: Option<(data::Table, loader::Resume)>,
+    loadStatus: Option<String>,
+    // definitions from the prompt, applied again when the table is swapped
+    definitions: Vec<expr::Definition>,
+    normalizations: HashMap<String, normalize::Normalization>,
+    pcaTask: Option<comm::Receiver<Result<pca::Pca, String>>>,
+    pcaStatus: Option<String>,
+    tsneTask: Option<comm::Receiver<tsne::Message>>,
+    tsneStatus: Option<String>,
+    clusterTask: Option<comm::Receiver<Result<Vec<f64>, String>>>,
+    clusterStatus: Option<String>,
+    // analyses from the prompt and --tsne, run again when the table is swapped
+    pcaSpec: Option<pca::Spec>,
+    tsneSpec: Option<tsne::Spec>,
+    clusterSpec: Option<cluster::Spec>,
+    // clustering waits for the columns of a running PCA or t-SNE
+    clusterPending: bool,
     programPoints: hgl::program::Program,
     programTexture: hgl::program::Program,
     programLegend: hgl::program::Program,
     textdrawer: textdrawer::TextDrawer,
     gl2d: opengl_graphics::Gl,
     showHelp: bool,
+    showStats: bool,
+    showCentroids: bool,
+    prompt: Option<Prompt>,
+    picker: Option<picker::Picker>,
+    // axis that gets the column chosen in the picker
+    pickerAxis: Axis,
     changed: bool,
     framebuffer: gl::types::GLuint,
     texture: gl::types::GLuint,
+    config: config::Config,
 }
 
 impl Renderer {
-    fn new(table: data::Table, column_x: &String, column_y: &String, column_z: &String) -> Renderer {
-        let width = 800i32;
-        let height = 600i32;
+    fn new(table: data::Table, column_x: &String, column_y: &String, column_z: &String, options: Options) -> Renderer {
+        let config = options.config;
+        let width = config.width;
+        let height = config.height;
 
         let glfw = glfw::init(glfw::FAIL_ON_ERRORS).unwrap();
         glfw.window_hint(glfw::ContextVersion(3, 1));
@@ -263,22 +633,32 @@ impl Renderer {
             pointScale: programPoints.uniform("pointScale"),
             transformation: programPoints.uniform("transformation"),
             margin: programPoints.uniform("margin"),
+            tileOrigin: programPoints.uniform("tileOrigin"),
+            tileSize: programPoints.uniform("tileSize"),
         };
         programPoints.bind_frag(0, "out_color");
         programPoints.bind();
 
-        let dimx = Dimension::new(width, &table, column_x);
+        // streams fill the window without growing the buffers
+        let capacity = cmp::max(table.len(), options.window.unwrap_or(0));
+
+        let dimx = Dimension::new(width, config.margin, capacity, &table, column_x, normalize::NormNone, options.rangeMode, !options.hideOutliers);
         vaoPoints.enable_attrib(&programPoints, "position_x", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
         dimx.vbo.bind();
 
-        let dimy = Dimension::new(height, &table, column_y);
+        let dimy = Dimension::new(height, config.margin, capacity, &table, column_y, normalize::NormNone, options.rangeMode, !options.hideOutliers);
         vaoPoints.enable_attrib(&programPoints, "position_y", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
         dimy.vbo.bind();
 
-        let mut dimz = Dimension::new(width, &table, column_z);
+        let mut dimz = Dimension::new(width, config.margin, capacity, &table, column_z, normalize::NormNone, options.rangeMode, !options.hideOutliers);
         vaoPoints.enable_attrib(&programPoints, "position_z", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
         dimz.vbo.bind();
 
+        let mask: Vec<f32> = Vec::from_elem(capacity, 1f32);
+        let vboMask = hgl::Vbo::from_data(mask.as_slice(), hgl::StaticDraw);
+        vaoPoints.enable_attrib(&programPoints, "visible", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
+        vboMask.bind();
+
         let vaoTexture = hgl::Vao::new();
         vaoTexture.bind();
 
@@ -307,6 +687,7 @@ impl Renderer {
             width: programLegend.uniform("width"),
             height: programLegend.uniform("height"),
             margin: programLegend.uniform("margin"),
+            tile: programLegend.uniform("tile"),
         };
         programLegend.bind_frag(0, "out_color");
         programLegend.bind();
@@ -314,433 +695,2505 @@ impl Renderer {
         vaoTexture.enable_attrib(&programLegend, "v_coord", gl::FLOAT, 2, (1 * mem::size_of::<f32>()) as i32, 0);
         vboTexture.bind();
 
-        let projection = calc_projection(&dimx, &dimy, &dimz);
-
-        let mut framebuffer = 0;
-        unsafe {
-            gl::GenFramebuffers(1, &mut framebuffer);
-        }
-        gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer);
-
-        let mut texture = 0;
-        unsafe {
-            gl::GenTextures(1, &mut texture);
-        }
-        gl::BindTexture(gl::TEXTURE_2D, texture);
-        unsafe {
-            gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA32F as i32, dimx.renderLength, dimy.renderLength, 0, gl::RGBA, gl::FLOAT, ptr::null());
-        }
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-        gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
+        let (framebuffer, texture) = create_target(dimx.renderLength, dimy.renderLength, gl::RGBA32F);
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
 
-        gl::FramebufferTexture(gl::FRAMEBUFFER, gl::COLOR_ATTACHMENT0, texture, 0);
-        let drawBuffers = [gl::COLOR_ATTACHMENT0];
-        unsafe {
-            gl::DrawBuffers(drawBuffers.len() as i32, mem::transmute(&drawBuffers[0]));
+        let mut crossfilter = crossfilter::Crossfilter::new(&table);
+        let rangesPath = match options.rangesFile {
+            Some(ref path) => path.clone(),
+            None => Path::new(format!("{}.ranges", table.name()))
+        };
+        // a missing file means no ranges were saved yet, a broken one is reported and ignored
+        if rangesPath.exists() {
+            match crossfilter.load(&rangesPath) {
+                Ok(()) => (),
+                Err(e) => {
+                    println!("Ranges: {}", e);
+                    crossfilter = crossfilter::Crossfilter::new(&table);
+                }
+            }
         }
-        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
 
-        Renderer {
+        let mut renderer = Renderer {
             glfw: glfw,
             window: window,
             events: events,
             dimx: dimx,
             dimy: dimy,
             dimz: dimz,
-            dimzDelta: 0f32,
-            dimzScale: 1f32,
+            dimzDelta: 0f64,
+            dimzScale: 1f64,
             activeTransform: TransformNone,
             mouseX: 0f32,
             mouseY: 0f32,
-            pointScale: 4f32,
-            alphaScale: 1f32,
-            projection: projection,
+            pointScale: config.pointScale,
+            alphaScale: config.alphaScale,
             ulocationPoints: ulocationPoints,
             ulocationTexture: ulocationTexture,
             ulocationLegend: ulocationLegend,
             vaoPoints: vaoPoints,
             vaoTexture: vaoTexture,
             vboTexture: vboTexture,
+            vboMask: vboMask,
+            filter: None,
+            filterText: String::new(),
+            filterMask: Vec::from_elem(table.len(), true),
+            crossfilter: crossfilter,
+            rangesPath: rangesPath,
+            sessionPath: match options.sessionFile {
+                Some(ref path) => path.clone(),
+                None => Path::new(format!("{}.session", table.name()))
+            },
+            history: undo::History::new(),
+            zoomStack: Vec::new(),
+            showPanel: false,
+            panelScroll: 0,
+            visibleCount: table.len(),
+            rangeMode: options.rangeMode,
+            hideOutliers: options.hideOutliers,
+            equalAspect: options.equalAspect,
+            watcher: match options.source {
+                Some(ref source) => match watch::Watcher::new(&source.path) {
+                    Ok(w) => Some(w),
+                    Err(e) => {
+                        println!("Not watching {}: {}", source.path.display(), e);
+                        None
+                    }
+                },
+                None => None
+            },
+            reloadPending: false,
+            capacity: capacity,
+            sampled: match options.source {
+                Some(ref source) => source.options.sample.is_some(),
+                None => false
+            },
+            source: options.source,
+            resume: options.resume,
+            stream: options.stream,
+            control: options.control,
+            autoRange: options.autoRange,
+            loadTask: None,
+            otherTable: None,
+            loadStatus: None,
+            definitions: options.definitions,
+            normalizations: HashMap::new(),
+            pcaTask: None,
+            pcaStatus: None,
+            tsneTask: None,
+            tsneStatus: None,
+            clusterTask: None,
+            clusterStatus: None,
+            pcaSpec: options.pca.clone(),
+            tsneSpec: options.tsne.clone(),
+            clusterSpec: None,
+            clusterPending: false,
             programPoints: programPoints,
             programTexture: programTexture,
             programLegend: programLegend,
             table: table,
-            textdrawer: textdrawer::TextDrawer::new(FONT_DATA, FONT_SIZE),
+            textdrawer: textdrawer::TextDrawer::new(FONT_DATA, config.fontSize),
             gl2d: opengl_graphics::Gl::new(),
             showHelp: false,
+            showStats: false,
+            showCentroids: false,
+            prompt: None,
+            picker: None,
+            pickerAxis: AxisX,
             changed: true,
             framebuffer: framebuffer,
             texture: texture,
+            config: config,
+        };
+        renderer.update_mask();
+        match options.session {
+            Some(ref session) => match renderer.restore(session) {
+                Ok(()) => (),
+                Err(e) => println!("Session: {}", e)
+            },
+            None => ()
+        }
+        for &(axis, limits) in [(AxisX, options.xlim), (AxisY, options.ylim), (AxisZ, options.zlim)].iter() {
+            match limits {
+                Some((lo, hi)) => renderer.set_view(axis, lo, hi),
+                None => ()
+            }
         }
+        // explicit limits win over the aspect
+        if renderer.equalAspect {
+            match (options.xlim, options.ylim) {
+                (Some(_), Some(_)) => (),
+                (Some(_), None) => renderer.keep_aspect(Some(AxisX)),
+                (None, Some(_)) => renderer.keep_aspect(Some(AxisY)),
+                (None, None) => renderer.keep_aspect(None)
+            }
+        }
+        match options.pca {
+            Some(ref spec) => renderer.run_pca(spec).unwrap_or_else(|e| fail!("--pca: {}", e)),
+            None => ()
+        }
+        match options.tsne {
+            Some(ref spec) => {
+                let input = tsne::prepare(spec, &renderer.table).unwrap_or_else(|e| fail!("--tsne: {}", e));
+                renderer.tsneTask = Some(tsne::start(input));
+            },
+            None => ()
+        }
+        renderer
     }
 
     fn draw_x_axis(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
-        let line = c.line(MARGIN as f64, MARGIN as f64, self.dimx.renderLength as f64 - MARGIN as f64, MARGIN as f64)
+        let margin = self.config.margin;
+        let line = c.line(margin as f64, margin as f64, self.dimx.renderLength as f64 - margin as f64, margin as f64)
             .round_border_radius(1.0);
 
 
         line.draw(&mut self.gl2d);
-        line.trans(0f64, self.dimy.renderLength as f64 - 2f64 * MARGIN as f64)
+        line.trans(0f64, self.dimy.renderLength as f64 - 2f64 * margin as f64)
             .draw(&mut self.gl2d);
 
         let text_c1 = c.trans((self.dimx.renderLength as f64 / 2f64).floor(), LABEL_MARGIN);
         let text_c2 = c.trans((self.dimx.renderLength as f64 / 2f64).floor(), self.dimy.renderLength as f64 - LABEL_MARGIN);
-        let text = self.dimx.name.clone();
+        let text = self.dimx.title.clone();
         self.textdrawer.render(&text_c1, &mut self.gl2d, &text, textdrawer::Center, textdrawer::Top);
         self.textdrawer.render(&text_c2, &mut self.gl2d, &text, textdrawer::Center, textdrawer::Bottom);
 
-        let (nfrac, mmin, mmax, marksers) = self.dimx.calc_axis_markers(TICK_DISTANCE);
-        for m in marksers.iter() {
-            let pos = (MARGIN + (m - mmin) / (mmax - mmin) * (self.dimx.renderLength as f32 - 2f32 * MARGIN)).floor();
-            let marker_text = f32::to_str_digits(m.clone(), nfrac as uint + 1);
-            let marker_c1 = c.trans(pos as f64, MARGIN as f64 - 10f64)
+        let (mmin, mmax, marksers) = self.dimx.calc_axis_markers(self.config.tickDistance);
+        for &(m, ref marker_text) in marksers.iter() {
+            let pos = (margin + ((m - mmin) / (mmax - mmin)) as f32 * (self.dimx.renderLength as f32 - 2f32 * margin)).floor();
+            let marker_c1 = c.trans(pos as f64, margin as f64 - 10f64)
                 .rot_deg(270f64);
-            let marker_c2 = c.trans(pos as f64, self.dimy.renderLength as f64 - MARGIN as f64 + 10f64)
+            let marker_c2 = c.trans(pos as f64, self.dimy.renderLength as f64 - margin as f64 + 10f64)
                 .rot_deg(90f64);
 
-            self.textdrawer.render(&marker_c1, &mut self.gl2d, &marker_text, textdrawer::Left, textdrawer::Middle);
-            self.textdrawer.render(&marker_c2, &mut self.gl2d, &marker_text, textdrawer::Left, textdrawer::Middle);
+            self.textdrawer.render(&marker_c1, &mut self.gl2d, marker_text, textdrawer::Left, textdrawer::Middle);
+            self.textdrawer.render(&marker_c2, &mut self.gl2d, marker_text, textdrawer::Left, textdrawer::Middle);
 
-            c.line(pos as f64, MARGIN as f64 - TICK_LENGTH, pos as f64, MARGIN as f64)
+            c.line(pos as f64, margin as f64 - TICK_LENGTH, pos as f64, margin as f64)
                 .round_border_radius(TICK_WIDTH)
                 .draw(&mut self.gl2d);
-            c.line(pos as f64, self.dimy.renderLength as f64 - MARGIN as f64 + TICK_LENGTH, pos as f64, self.dimy.renderLength as f64 - MARGIN as f64)
+            c.line(pos as f64, self.dimy.renderLength as f64 - margin as f64 + TICK_LENGTH, pos as f64, self.dimy.renderLength as f64 - margin as f64)
                 .round_border_radius(TICK_WIDTH)
                 .draw(&mut self.gl2d);
         }
     }
 
     fn draw_y_axis(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
-        let line = c.line(MARGIN as f64, MARGIN as f64, MARGIN as f64, self.dimy.renderLength as f64 - MARGIN as f64)
+        let margin = self.config.margin;
+        let line = c.line(margin as f64, margin as f64, margin as f64, self.dimy.renderLength as f64 - margin as f64)
             .round_border_radius(1.0);
 
         line.draw(&mut self.gl2d);
-        line.trans(self.dimx.renderLength as f64 - 2f64 * MARGIN as f64, 0f64)
+        line.trans(self.dimx.renderLength as f64 - 2f64 * margin as f64, 0f64)
             .draw(&mut self.gl2d);
 
         let text_c1 = c.trans(LABEL_MARGIN, (self.dimy.renderLength as f64 / 2f64).floor())
             .rot_deg(270f64);
         let text_c2 = c.trans(self.dimx.renderLength as f64 - LABEL_MARGIN, (self.dimy.renderLength as f64 / 2f64).floor())
             .rot_deg(90f64);
-        let text = self.dimy.name.clone();
+        let text = self.dimy.title.clone();
         self.textdrawer.render(&text_c1, &mut self.gl2d, &text, textdrawer::Center, textdrawer::Top);
         self.textdrawer.render(&text_c2, &mut self.gl2d, &text, textdrawer::Center, textdrawer::Top);
 
-        let (nfrac, mmin, mmax, marksers) = self.dimy.calc_axis_markers(TICK_DISTANCE);
-        for m in marksers.iter() {
-            let pos = (MARGIN + (1.0 - (m - mmin) / (mmax - mmin)) * (self.dimy.renderLength as f32 - 2f32 * MARGIN)).floor();
-            let marker_text = f32::to_str_digits(m.clone(), nfrac as uint + 1);
-            let marker_c1 = c.trans(MARGIN as f64 - 10f64, pos as f64);
-            let marker_c2 = c.trans(self.dimx.renderLength as f64 - MARGIN as f64 + 10f64, pos as f64);
+        let (mmin, mmax, marksers) = self.dimy.calc_axis_markers(self.config.tickDistance);
+        for &(m, ref marker_text) in marksers.iter() {
+            let pos = (margin + (1.0 - ((m - mmin) / (mmax - mmin)) as f32) * (self.dimy.renderLength as f32 - 2f32 * margin)).floor();
+            let marker_c1 = c.trans(margin as f64 - 10f64, pos as f64);
+            let marker_c2 = c.trans(self.dimx.renderLength as f64 - margin as f64 + 10f64, pos as f64);
 
-            self.textdrawer.render(&marker_c1, &mut self.gl2d, &marker_text, textdrawer::Right, textdrawer::Middle);
-            self.textdrawer.render(&marker_c2, &mut self.gl2d, &marker_text, textdrawer::Left, textdrawer::Middle);
+            self.textdrawer.render(&marker_c1, &mut self.gl2d, marker_text, textdrawer::Right, textdrawer::Middle);
+            self.textdrawer.render(&marker_c2, &mut self.gl2d, marker_text, textdrawer::Left, textdrawer::Middle);
 
-            c.line(MARGIN as f64 - TICK_LENGTH, pos as f64, MARGIN as f64, pos as f64)
+            c.line(margin as f64 - TICK_LENGTH, pos as f64, margin as f64, pos as f64)
                 .round_border_radius(TICK_WIDTH)
                 .draw(&mut self.gl2d);
-            c.line(self.dimx.renderLength as f64 - MARGIN as f64 + TICK_LENGTH, pos as f64, self.dimx.renderLength as f64 - MARGIN as f64, pos as f64)
+            c.line(self.dimx.renderLength as f64 - margin as f64 + TICK_LENGTH, pos as f64, self.dimx.renderLength as f64 - margin as f64, pos as f64)
                 .round_border_radius(TICK_WIDTH)
                 .draw(&mut self.gl2d);
         }
     }
 
     fn draw_z_axis(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
-        let line = c.line(MARGIN as f64, self.dimy.renderLength as f64 - MARGIN as f64 / 5f64, self.dimz.renderLength as f64 - MARGIN as f64, self.dimy.renderLength as f64 - MARGIN as f64 / 5f64)
+        let margin = self.config.margin;
+        let line = c.line(margin as f64, self.dimy.renderLength as f64 - margin as f64 / 5f64, self.dimz.renderLength as f64 - margin as f64, self.dimy.renderLength as f64 - margin as f64 / 5f64)
             .round_border_radius(1.0);
 
 
         line.draw(&mut self.gl2d);
 
-        self.textdrawer.render(&c.trans(INFO_MARGIN, self.dimy.renderLength as f64 - INFO_MARGIN), &mut self.gl2d, &format!("z: {}", self.dimz.name), textdrawer::Left, textdrawer::Bottom);
+        self.textdrawer.render(&c.trans(INFO_MARGIN, self.dimy.renderLength as f64 - INFO_MARGIN), &mut self.gl2d, &format!("z: {}", self.dimz.title), textdrawer::Left, textdrawer::Bottom);
 
-        let (nfrac, mmin, mmax, marksers) = self.dimz.calc_axis_markers(TICK_DISTANCE);
-        for m in marksers.iter() {
-            let pos = (MARGIN + (m - mmin) / (mmax - mmin) * (self.dimz.renderLength as f32 - 2f32 * MARGIN)).floor();
-            let marker_text = f32::to_str_digits(m.clone(), nfrac as uint + 1);
-            let marker_c = c.trans(pos as f64, self.dimy.renderLength as f64 - MARGIN as f64 / 5f64 - 10f64);
+        let (mmin, mmax, marksers) = self.dimz.calc_axis_markers(self.config.tickDistance);
+        for &(m, ref marker_text) in marksers.iter() {
+            let pos = (margin + ((m - mmin) / (mmax - mmin)) as f32 * (self.dimz.renderLength as f32 - 2f32 * margin)).floor();
+            let marker_c = c.trans(pos as f64, self.dimy.renderLength as f64 - margin as f64 / 5f64 - 10f64);
 
-            self.textdrawer.render(&marker_c, &mut self.gl2d, &marker_text, textdrawer::Center, textdrawer::Bottom);
+            self.textdrawer.render(&marker_c, &mut self.gl2d, marker_text, textdrawer::Center, textdrawer::Bottom);
 
-            c.line(pos as f64, self.dimy.renderLength as f64 - MARGIN as f64 / 5f64 - TICK_LENGTH, pos as f64, self.dimy.renderLength as f64 - MARGIN as f64 / 5f64)
+            c.line(pos as f64, self.dimy.renderLength as f64 - margin as f64 / 5f64 - TICK_LENGTH, pos as f64, self.dimy.renderLength as f64 - margin as f64 / 5f64)
                 .round_border_radius(TICK_WIDTH)
                 .draw(&mut self.gl2d);
         }
     }
 
-    fn handle_event(&mut self, event: glfw::WindowEvent) {
-        match event {
-            glfw::SizeEvent(w, h) => {
-                self.dimx.renderLength = w;
-                self.dimy.renderLength = h;
-                self.dimz.renderLength = w;
-                gl::Viewport(0, 0, self.dimx.renderLength, self.dimy.renderLength);
-
-                gl::BindTexture(gl::TEXTURE_2D, self.texture);
-                unsafe {
-                    gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA32F as i32, self.dimx.renderLength, self.dimy.renderLength, 0, gl::RGBA, gl::FLOAT, ptr::null());
-                }
-                gl::BindTexture(gl::TEXTURE_2D, 0);
-            },
-            glfw::CursorPosEvent(xpos, ypos) => {
-                match self.activeTransform {
-                    TransformMove => {
-                        let xdiff = (xpos as f32 - self.mouseX) / self.dimx.renderLength as f32;
-                        let ydiff = (self.mouseY - ypos as f32) / self.dimy.renderLength as f32;
-
-                        self.dimx.d += xdiff * (self.dimx.max - self.dimx.min);
-                        self.dimy.d += ydiff * (self.dimy.max - self.dimy.min);
-                    },
-                    TransformScale => {
-                        let x1 = self.mouseX - self.dimx.renderLength as f32 / 2.0f32;
-                        let x2 = xpos as f32 - self.dimx.renderLength as f32 / 2.0f32;
-                        let y1 = self.mouseY - self.dimy.renderLength as f32 / 2.0f32;
-                        let y2 = ypos as f32 - self.dimy.renderLength as f32 / 2.0f32;
-
-
-                        self.dimx.d = self.dimx.d / self.dimx.s;
-                        self.dimy.d = self.dimy.d / self.dimy.s;
-                        self.dimx.s *= x2 / x1;
-                        self.dimy.s *= y2 / y1;
-                        self.dimx.d = self.dimx.d * self.dimx.s;
-                        self.dimy.d = self.dimy.d * self.dimy.s;
-                    },
-                    TransformNone => ()
-                }
-                self.mouseX = xpos as f32;
-                self.mouseY = ypos as f32;
+    fn select_column(&mut self, axis: Axis, name: &String) {
+        let norm = self.normalization(name);
+        match axis {
+            AxisX => {
+                let dim = Dimension::new(self.dimx.renderLength, self.config.margin, self.capacity, &self.table, name, norm, self.rangeMode, !self.hideOutliers);
+                self.vaoPoints.enable_attrib(&self.programPoints, "position_x", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
+                dim.vbo.bind();
+                self.dimx = dim;
             },
-            glfw::MouseButtonEvent(button, action, _mods) => {
-                match (button, action, self.activeTransform) {
-                    (glfw::MouseButton1, glfw::Press, TransformNone) => {
-                        self.activeTransform = TransformMove;
-                    },
-                    (glfw::MouseButton1, glfw::Release, TransformMove) => {
-                        self.activeTransform = TransformNone;
-                    },
-                    (glfw::MouseButton2, glfw::Press, TransformNone) => {
-                        self.activeTransform = TransformScale;
-                    },
-                    (glfw::MouseButton2, glfw::Release, TransformScale) => {
-                        self.activeTransform = TransformNone;
-                    },
-                    _ => ()
-                }
+            AxisY => {
+                let dim = Dimension::new(self.dimy.renderLength, self.config.margin, self.capacity, &self.table, name, norm, self.rangeMode, !self.hideOutliers);
+                self.vaoPoints.enable_attrib(&self.programPoints, "position_y", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
+                dim.vbo.bind();
+                self.dimy = dim;
             },
-            glfw::ScrollEvent(dx, dy) => {
-                if dx > 0.0 {
-                    self.dimz.d -= 0.05 * (self.dimz.max - self.dimz.min) * self.dimz.s;
-                    self.dimzDelta += 0.05 * (self.dimz.max - self.dimz.min) * self.dimzScale;
-                } else if dx < 0.0 {
-                    self.dimz.d += 0.05 * (self.dimz.max - self.dimz.min) * self.dimz.s;
-                    self.dimzDelta -= 0.05 * (self.dimz.max - self.dimz.min) * self.dimzScale;
-                }
-                if dy > 0.0 {
-                    self.dimz.d = self.dimz.d / self.dimz.s;
-                    self.dimzDelta = self.dimzDelta / self.dimzScale;
+            AxisZ => {
+                let dim = Dimension::new(self.dimz.renderLength, self.config.margin, self.capacity, &self.table, name, norm, self.rangeMode, !self.hideOutliers);
+                self.vaoPoints.enable_attrib(&self.programPoints, "position_z", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
+                dim.vbo.bind();
+                self.dimz = dim;
+                self.dimzDelta = 0f64;
+                self.dimzScale = 1f64;
+            }
+        }
+        if self.hideOutliers {
+            self.update_mask();
+        }
+    }
 
-                    self.dimz.s *= 1.05;
-                    self.dimzScale *= 1.05;
+    fn normalization(&self, name: &String) -> normalize::Normalization {
+        match self.normalizations.find(name) {
+            Some(norm) => norm.clone(),
+            None => normalize::NormNone
+        }
+    }
 
-                    self.dimz.d = self.dimz.d * self.dimz.s;
-                    self.dimzDelta = self.dimzDelta * self.dimzScale;
-                } else if dy < 0.0 {
-                    self.dimz.d = self.dimz.d / self.dimz.s;
-                    self.dimzDelta = self.dimzDelta / self.dimzScale;
+    // switches to the next normalization of the column shown on `axis`
+    fn cycle_normalization(&mut self, axis: Axis) {
+        let name = match axis {
+            AxisX => self.dimx.name.clone(),
+            AxisY => self.dimy.name.clone(),
+            AxisZ => self.dimz.name.clone(),
+        };
+        let norm = self.normalization(&name).cycle();
+        self.normalizations.insert(name.clone(), norm);
+        self.reload_column(&name);
+    }
 
-                    self.dimz.s /= 1.05;
-                    self.dimzScale /= 1.05;
+    // recreates all dimensions after the range mode or outlier handling changed, zoom and pan
+    // stay relative to the new ranges
+    fn reload_dimensions(&mut self) {
+        let (x, y, z) = (self.dimx.name.clone(), self.dimy.name.clone(), self.dimz.name.clone());
+        self.refresh_column(AxisX, &x);
+        self.refresh_column(AxisY, &y);
+        self.refresh_column(AxisZ, &z);
+        self.update_mask();
+    }
 
-                    self.dimz.d = self.dimz.d * self.dimz.s;
-                    self.dimzDelta = self.dimzDelta * self.dimzScale;
-                }
-            }
-            glfw::KeyEvent(key, _scancode, action, _mods) => {
-                match (key, action) {
-                    (glfw::KeyEscape, glfw::Press) => self.window.set_should_close(true),
-                    (glfw::KeyW, glfw::Press) => self.pointScale *= 1.5f32,
-                    (glfw::KeyQ, glfw::Press) => self.pointScale = 1f32.max(self.pointScale / 1.5f32),
-                    (glfw::KeyA, glfw::Press) => self.alphaScale = 0f32.max(self.alphaScale - 0.02f32),
-                    (glfw::KeyS, glfw::Press) => self.alphaScale = 1f32.min(self.alphaScale + 0.02f32),
-                    (glfw::KeyH, glfw::Press) => self.showHelp = !self.showHelp,
-                    (glfw::KeyR, glfw::Press) => {
-                        self.pointScale = 4f32;
-                        self.alphaScale = 1f32;
-                        self.dimx.reset();
-                        self.dimy.reset();
-                        self.dimz.reset();
-                        self.dimzDelta = 0f32;
-                        self.dimzScale = 1f32;
-                    },
-                    (glfw::KeyRight, glfw::Press) => {
-                        {
-                            let next = match self.table.columns().iter().skip_while(|&s| s != &self.dimx.name).skip(1).next() {
-                                Some(element) => element,
-                                None => self.table.columns().iter().next().unwrap()
-                            };
-                            let dim = Dimension::new(self.dimx.renderLength, &self.table, next);
-                            self.vaoPoints.enable_attrib(&self.programPoints, "position_x", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
-                            dim.vbo.bind();
-                            self.dimx = dim;
-                        }
-                        self.projection = calc_projection(&self.dimx, &self.dimy, &self.dimz);
-                    },
-                    (glfw::KeyLeft, glfw::Press) => {
-                        {
-                            let next = match self.table.columns().rev_iter().skip_while(|&s| s != &self.dimx.name).skip(1).next() {
-                                Some(element) => element,
-                                None => self.table.columns().rev_iter().next().unwrap()
-                            };
-                            let dim = Dimension::new(self.dimx.renderLength, &self.table, next);
-                            self.vaoPoints.enable_attrib(&self.programPoints, "position_x", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
-                            dim.vbo.bind();
-                            self.dimx = dim;
-                        }
-                        self.projection = calc_projection(&self.dimx, &self.dimy, &self.dimz);
-                    },
-                    (glfw::KeyDown, glfw::Press) => {
-                        {
-                            let next = match self.table.columns().iter().skip_while(|&s| s != &self.dimy.name).skip(1).next() {
-                                Some(element) => element,
-                                None => self.table.columns().iter().next().unwrap()
-                            };
-                            let dim = Dimension::new(self.dimy.renderLength, &self.table, next);
-                            self.vaoPoints.enable_attrib(&self.programPoints, "position_y", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
-                            dim.vbo.bind();
-                            self.dimy = dim;
-                        }
-                        self.projection = calc_projection(&self.dimx, &self.dimy, &self.dimz);
-                    },
-                    (glfw::KeyUp, glfw::Press) => {
-                        {
-                            let next = match self.table.columns().rev_iter().skip_while(|&s| s != &self.dimy.name).skip(1).next() {
-                                Some(element) => element,
-                                None => self.table.columns().rev_iter().next().unwrap()
-                            };
-                            let dim = Dimension::new(self.dimy.renderLength, &self.table, next);
-                            self.vaoPoints.enable_attrib(&self.programPoints, "position_y", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
-                            dim.vbo.bind();
-                            self.dimy = dim;
-                        }
-                        self.projection = calc_projection(&self.dimx, &self.dimy, &self.dimz);
-                    },
-                    (glfw::KeyPageDown, glfw::Press) => {
-                        {
-                            let next = match self.table.columns().iter().skip_while(|&s| s != &self.dimz.name).skip(1).next() {
-                                Some(element) => element,
-                                None => self.table.columns().iter().next().unwrap()
-                            };
-                            let dim = Dimension::new(self.dimz.renderLength, &self.table, next);
-                            self.vaoPoints.enable_attrib(&self.programPoints, "position_z", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
-                            dim.vbo.bind();
-                            self.dimz = dim;
-                            self.dimzDelta = 0f32;
-                            self.dimzScale = 1f32;
-                        }
-                        self.projection = calc_projection(&self.dimx, &self.dimy, &self.dimz);
-                    },
-                    (glfw::KeyPageUp, glfw::Press) => {
-                        {
-                            let next = match self.table.columns().rev_iter().skip_while(|&s| s != &self.dimz.name).skip(1).next() {
-                                Some(element) => element,
-                                None => self.table.columns().rev_iter().next().unwrap()
-                            };
-                            let dim = Dimension::new(self.dimz.renderLength, &self.table, next);
-                            self.vaoPoints.enable_attrib(&self.programPoints, "position_z", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
-                            dim.vbo.bind();
-                            self.dimz = dim;
-                            self.dimzDelta = 0f32;
-                            self.dimzScale = 1f32;
-                        }
-                        self.projection = calc_projection(&self.dimx, &self.dimy, &self.dimz);
-                    },
-                    _ => ()
-                }
+    fn step_column(&mut self, axis: Axis, forward: bool) {
+        let next = {
+            let current = match axis {
+                AxisX => &self.dimx.name,
+                AxisY => &self.dimy.name,
+                AxisZ => &self.dimz.name,
+            };
+            if forward {
+                match self.table.columns().iter().skip_while(|&s| s != current).skip(1).next() {
+                    Some(element) => element,
+                    None => self.table.columns().iter().next().unwrap()
+                }.clone()
+            } else {
+                match self.table.columns().rev_iter().skip_while(|&s| s != current).skip(1).next() {
+                    Some(element) => element,
+                    None => self.table.columns().rev_iter().next().unwrap()
+                }.clone()
             }
-            _ => ()
-        }
+        };
+        self.select_column(axis, &next);
     }
 
-    fn redraw(&mut self) {
-        // draw to texture
-        gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
-        gl::Viewport(0, 0, self.dimx.renderLength, self.dimy.renderLength);
-        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
-        gl::Clear(gl::COLOR_BUFFER_BIT);
-        gl::Enable(gl::VERTEX_PROGRAM_POINT_SIZE);
-        gl::Enable(gl::BLEND);
-        gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE, gl::ONE, gl::ONE);
+    // reloads the dimensions that show `name` after the column was replaced
+    fn reload_column(&mut self, name: &String) {
+        if &self.dimx.name == name {
+            self.select_column(AxisX, name);
+        }
+        if &self.dimy.name == name {
+            self.select_column(AxisY, name);
+        }
+        if &self.dimz.name == name {
+            self.select_column(AxisZ, name);
+        }
+    }
 
-        self.vaoPoints.bind();
-        self.programPoints.bind();
+    fn derive(&mut self, text: &str) -> Result<(), String> {
+        let definitions = try!(expr::parse_definitions(text));
+        for definition in definitions.iter() {
+            try!(expr::apply(definition, &mut self.table));
+            self.definitions.push(definition.clone());
+            self.crossfilter.sync(&self.table);
+            try!(self.update_filter());
+            self.reload_column(&definition.name);
+        }
+        Ok(())
+    }
 
-        let translation = cgmath::Matrix4::<f32>::from_translation(
-            &cgmath::Vector3::<f32>::new(
-                self.dimx.d / (self.dimx.max - self.dimx.min) * 2.0,
-                self.dimy.d / (self.dimy.max - self.dimy.min) * 2.0,
-                self.dimzDelta / (self.dimz.max - self.dimz.min) * 2.0
-            )
-        );
-        let scale = cgmath::Matrix4::<f32>::new(
-            self.dimx.s, 0.0f32, 0.0f32, 0.0f32,
-            0.0f32, self.dimy.s, 0.0f32, 0.0f32,
-            0.0f32, 0.0f32, self.dimzScale, 0.0f32,
-            0.0f32, 0.0f32, 0.0f32, 1.0f32
-        );
-        let finalTransformation = translation.mul_m(&scale).mul_m(&self.projection);
-        unsafe {
-            gl::UniformMatrix4fv(self.ulocationPoints.transformation, 1, gl::FALSE, mem::transmute(&finalTransformation.as_fixed()[0][0]));
+    fn start_pca(&mut self, text: &str) -> Result<(), String> {
+        if self.pcaTask.is_some() {
+            return Err("PCA is still running".to_string());
         }
+        let spec = try!(pca::parse_spec(text));
+        try!(self.run_pca(&spec));
+        self.pcaSpec = Some(spec);
+        Ok(())
+    }
 
-        gl::Uniform1f(self.ulocationPoints.width, self.dimx.renderLength as f32);
-        gl::Uniform1f(self.ulocationPoints.height, self.dimy.renderLength as f32);
-        gl::Uniform1f(self.ulocationPoints.pointScale, self.pointScale);
-        gl::Uniform1f(self.ulocationPoints.margin, MARGIN);
+    fn run_pca(&mut self, spec: &pca::Spec) -> Result<(), String> {
+        let input = try!(pca::prepare(spec, &self.table));
+        self.pcaTask = Some(pca::start(input));
+        self.pcaStatus = Some("pca: computing...".to_string());
+        Ok(())
+    }
 
-        self.vaoPoints.draw_array(hgl::Points, 0, self.table.len() as i32);
+    // adds the result of a finished PCA task, returns true if the task is done
+    fn poll_pca(&mut self) -> bool {
+        let result = match self.pcaTask {
+            Some(ref rx) => match rx.try_recv() {
+                Ok(r) => r,
+                Err(comm::Empty) => return false,
+                Err(comm::Disconnected) => Err("task failed".to_string())
+            },
+            None => return false
+        };
+        self.pcaTask = None;
+        self.finish_pca(result);
+        true
+    }
 
-        // render to texture to viewport
-        self.vaoTexture.bind();
-        self.programTexture.bind();
+    // blocks until the PCA task is done, an export has to show the components
+    fn wait_pca(&mut self) {
+        let result = match self.pcaTask.take() {
+            Some(rx) => rx.recv_opt().unwrap_or_else(|_| Err("task failed".to_string())),
+            None => return
+        };
+        self.finish_pca(result);
+    }
 
-        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
-        gl::ClearColor(0.1, 0.1, 0.1, 1.0);
-        gl::Clear(gl::COLOR_BUFFER_BIT);
-        gl::Enable(gl::BLEND);
-        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+    fn finish_pca(&mut self, result: Result<pca::Pca, String>) {
+        self.pcaStatus = match result {
+            Ok(result) => {
+                pca::apply(&result, &mut self.table);
+                self.crossfilter.sync(&self.table);
+                for &(ref name, _, _) in result.components.iter() {
+                    self.reload_column(name);
+                }
+                match self.update_filter() {
+                    Ok(()) => Some(format!("pca: {}", result.summary())),
+                    Err(e) => Some(format!("where: {}", e))
+                }
+            },
+            Err(e) => Some(format!("pca: {}", e))
+        };
+        self.run_pending_cluster();
+    }
 
-        gl::BindTexture(gl::TEXTURE_2D, self.texture);
-        gl::Uniform1i(self.ulocationTexture.fboTexture, 0);
-        gl::Uniform1f(self.ulocationTexture.count, self.table.len() as f32);
-        gl::Uniform1f(self.ulocationTexture.alpha, self.alphaScale);
-        self.vaoTexture.draw_array(hgl::Triangles, 0, VERTEX_DATA_TEXTURE.len() as i32 / 2);
+    fn cluster(&mut self, text: &str) -> Result<(), String> {
+        if self.clusterTask.is_some() {
+            return Err("clustering is still running".to_string());
+        }
+        let spec = try!(cluster::parse_spec(text));
+        try!(self.run_cluster(&spec));
+        self.clusterSpec = Some(spec);
+
+        // the labels are NaN until the task is done
+        let name = cluster::COLUMN.to_string();
+        if self.table.get(&name).is_none() {
+            let n = self.table.len();
+            cluster::apply(Vec::from_elem(n, Float::nan()), &mut self.table);
+            self.crossfilter.sync(&self.table);
+        }
+        if self.dimz.name != name {
+            self.select_column(AxisZ, &name);
+        }
+        self.showCentroids = true;
+        Ok(())
+    }
 
-        // draw legend (reuse vaoTexture)
-        self.programLegend.bind();
-        gl::Uniform1f(self.ulocationLegend.width, self.dimx.renderLength as f32);
-        gl::Uniform1f(self.ulocationLegend.height, self.dimy.renderLength as f32);
-        gl::Uniform1f(self.ulocationLegend.margin, MARGIN as f32);
-        self.vaoTexture.draw_array(hgl::Triangles, 0, VERTEX_DATA_TEXTURE.len() as i32 / 2);
+    fn run_cluster(&mut self, spec: &cluster::Spec) -> Result<(), String> {
+        let input = try!(cluster::prepare(spec, &self.table));
+        self.clusterTask = Some(cluster::start(input));
+        self.clusterStatus = Some("cluster: computing...".to_string());
+        Ok(())
+    }
 
-        gl::BindVertexArray(0);
+    // adds the labels of a finished clustering task, returns true if the task is done
+    fn poll_cluster(&mut self) -> bool {
+        let result = match self.clusterTask {
+            Some(ref rx) => match rx.try_recv() {
+                Ok(r) => r,
+                Err(comm::Empty) => return false,
+                Err(comm::Disconnected) => Err("task failed".to_string())
+            },
+            None => return false
+        };
+        self.clusterTask = None;
+
+        self.clusterStatus = match result {
+            Ok(labels) => {
+                cluster::apply(labels, &mut self.table);
+                self.crossfilter.sync(&self.table);
+                self.reload_column(&cluster::COLUMN.to_string());
+                match self.update_filter() {
+                    Ok(()) => None,
+                    Err(e) => Some(format!("where: {}", e))
+                }
+            },
+            Err(e) => Some(format!("cluster: {}", e))
+        };
+        true
+    }
+
+    // clusters again once the PCA and t-SNE started by `rerun_analyses` are done
+    fn run_pending_cluster(&mut self) {
+        if !self.clusterPending || self.pcaTask.is_some() || self.tsneTask.is_some() {
+            return;
+        }
+        self.clusterPending = false;
+        match self.clusterSpec.clone() {
+            Some(spec) => match self.run_cluster(&spec) {
+                Ok(()) => (),
+                Err(e) => self.clusterStatus = Some(format!("cluster: {}", e))
+            },
+            None => ()
+        }
+    }
+
+    // computes the analyses of the previous table on the current one, their columns are NaN
+    // until the results arrive
+    fn rerun_analyses(&mut self, previous: &TreeSet<String>) {
+        let n = self.table.len();
+        for name in previous.iter() {
+            let analysis = (self.pcaSpec.is_some() && pca::is_component(name))
+                || (self.tsneSpec.is_some() && (name.as_slice() == tsne::COLUMN_X || name.as_slice() == tsne::COLUMN_Y))
+                || (self.clusterSpec.is_some() && name.as_slice() == cluster::COLUMN);
+            if analysis && self.table.get(name).is_none() {
+                self.table.add_column(name, Vec::from_elem(n, Float::nan()));
+            }
+        }
+        match self.pcaSpec.clone() {
+            Some(spec) => match self.run_pca(&spec) {
+                Ok(()) => (),
+                Err(e) => self.pcaStatus = Some(format!("pca: {}", e))
+            },
+            None => ()
+        }
+        match self.tsneSpec.clone() {
+            Some(spec) => match self.run_tsne(&spec) {
+                Ok(()) => (),
+                Err(e) => self.tsneStatus = Some(format!("t-sne: {}", e))
+            },
+            None => ()
+        }
+        self.clusterPending = self.clusterSpec.is_some();
+        self.run_pending_cluster();
+    }
+
+    fn start_load(&mut self, sampled: bool) {
+        let source = match self.source {
+            Some(ref s) => if sampled { s.clone() } else { s.full() },
+            None => return
+        };
+        let (tx, rx) = comm::channel();
+        spawn(proc() {
+            tx.send(source.load());
+        });
+        self.loadTask = Some((rx, sampled));
+    }
+
+    // switches between sample and full data, the other one is loaded in the background on first use
+    fn toggle_sample(&mut self) {
+        let sampling = match self.source {
+            Some(ref s) => s.options.sample.is_some(),
+            None => false
+        };
+        if self.loadTask.is_some() || !sampling {
+            return;
+        }
+        match self.otherTable.take() {
+            Some((table, resume)) => {
+                let sampled = !self.sampled;
+                self.swap_table(table, resume, sampled);
+            },
+            None => {
+                let sampled = !self.sampled;
+                self.start_load(sampled);
+                self.loadStatus = Some(if sampled { "loading sample..." } else { "loading full data..." }.to_string());
+            }
+        }
+    }
+
+    fn poll_load(&mut self) -> bool {
+        let (result, sampled) = match self.loadTask {
+            Some((ref rx, sampled)) => match rx.try_recv() {
+                Ok(r) => (Some(r), sampled),
+                Err(comm::Empty) => return false,
+                Err(comm::Disconnected) => (None, sampled)
+            },
+            None => return false
+        };
+        self.loadTask = None;
+
+        match result {
+            Some(Ok((table, resume))) => self.swap_table(table, resume, sampled),
+            Some(Err(e)) => self.loadStatus = Some(e),
+            None => self.loadStatus = Some("loading failed".to_string())
+        }
+        if self.reloadPending {
+            self.reloadPending = false;
+            self.reload();
+        }
+        true
+    }
+
+    // follows changes of the input file, returns true if anything changed
+    fn poll_watch(&mut self) -> bool {
+        let changed = match self.watcher {
+            Some(ref mut w) => w.changed(),
+            None => false
+        };
+        if !changed {
+            return false;
+        }
+        if self.loadTask.is_some() {
+            self.reloadPending = true;
+            return false;
+        }
+        self.reload();
+        true
+    }
+
+    // reads appended rows in place, anything else loads the file again in the background
+    fn reload(&mut self) {
+        // every principal component depends on all rows
+        if !self.sampled && self.pcaSpec.is_none() {
+            let before = self.table.len();
+            let result = match (&self.source, &mut self.resume) {
+                (&Some(ref source), &Some(ref mut resume)) => source.full().append(resume, &mut self.table),
+                _ => return
+            };
+            // rows may be added even if updating derived columns failed afterwards
+            if self.table.len() > before {
+                self.otherTable = None;
+                self.rows_changed(before);
+            }
+            if result.is_ok() {
+                return;
+            }
+        }
+        let sampled = self.sampled;
+        self.start_load(sampled);
+        self.loadStatus = Some("reloading...".to_string());
+    }
+
+    // adds the rows received from the stream, returns true if there were any
+    fn poll_stream(&mut self) -> bool {
+        let now = self.glfw.get_time();
+        let from = match self.stream {
+            Some(ref mut stream) => stream.poll(&mut self.table, now),
+            None => return false
+        };
+        match from {
+            Some(from) => {
+                self.rows_changed(from);
+                true
+            },
+            None => false
+        }
+    }
+
+    // columns of the input in file order, followed by derived columns
+    fn column_order(&self) -> Vec<String> {
+        let mut order: Vec<String> = match self.resume {
+            Some(ref resume) => resume.file_columns().move_iter().filter(|c| self.table.get(c).is_some()).collect(),
+            None => Vec::new()
+        };
+        for name in self.table.columns().iter() {
+            if !order.contains(name) {
+                order.push(name.clone());
+            }
+        }
+        order
+    }
+
+    // whether values of existing rows stay the same when rows are appended
+    fn is_stable(&self, name: &String) -> bool {
+        let fromInput = match self.resume {
+            Some(ref resume) => resume.columns().contains(name),
+            None => self.table.get(name).is_some()
+        };
+        let derived = match self.source {
+            Some(ref source) => source.definitions.iter().any(|d| &d.name == name),
+            None => false
+        };
+        fromInput && !derived && !self.definitions.iter().any(|d| &d.name == name)
+    }
+
+    // results of running tasks do not fit other rows
+    fn cancel_tasks(&mut self) {
+        if self.pcaTask.take().is_some() {
+            self.pcaStatus = None;
+        }
+        if self.tsneTask.take().is_some() {
+            self.tsneStatus = None;
+        }
+        if self.clusterTask.take().is_some() {
+            self.clusterStatus = None;
+        }
+    }
+
+    // the point buffers are recreated by `select_column`
+    fn create_mask_buffer(&mut self) {
+        let mask: Vec<f32> = Vec::from_elem(self.capacity, 1f32);
+        let vboMask = hgl::Vbo::from_data(mask.as_slice(), hgl::StaticDraw);
+        self.vaoPoints.bind();
+        self.vaoPoints.enable_attrib(&self.programPoints, "visible", gl::FLOAT, 1, (1 * mem::size_of::<f32>()) as i32, 0);
+        vboMask.bind();
+        self.vboMask = vboMask;
+    }
+
+    fn apply_definitions(&mut self) {
+        for definition in self.definitions.iter() {
+            match expr::apply(definition, &mut self.table) {
+                Ok(()) => (),
+                Err(e) => self.loadStatus = Some(format!("define {}: {}", definition.name, e))
+            }
+        }
+    }
+
+    // updates buffers and derived columns after rows were added or overwritten, rows before
+    // `from` kept their values and positions
+    fn rows_changed(&mut self, from: uint) {
+        self.cancel_tasks();
+        self.apply_definitions();
+        self.crossfilter.sync(&self.table);
+
+        let grow = self.table.len() > self.capacity;
+        if grow {
+            // grow geometrically, so following appends fit
+            self.capacity = 2 * self.table.len();
+            self.create_mask_buffer();
+        }
+        for &axis in [AxisX, AxisY, AxisZ].iter() {
+            let name = match axis {
+                AxisX => self.dimx.name.clone(),
+                AxisY => self.dimy.name.clone(),
+                AxisZ => self.dimz.name.clone(),
+            };
+            let normalized = match axis {
+                AxisX => self.dimx.values.is_some(),
+                AxisY => self.dimy.values.is_some(),
+                AxisZ => self.dimz.values.is_some(),
+            };
+            if grow || normalized || !self.is_stable(&name) {
+                self.refresh_column(axis, &name);
+                continue;
+            }
+            let dim = match axis {
+                AxisX => &mut self.dimx,
+                AxisY => &mut self.dimy,
+                AxisZ => &mut self.dimz,
+            };
+            // values clamped to the old range have to be uploaded again
+            let expanded = self.autoRange && dim.expand(&self.table, from);
+            if expanded || from == 0 {
+                dim.upload(&self.table);
+            } else {
+                dim.append(&self.table, from);
+            }
+        }
+
+        match self.update_filter() {
+            Ok(()) => (),
+            Err(e) => self.loadStatus = Some(format!("where: {}", e))
+        }
+    }
+
+    // like `select_column`, but keeps zoom and pan if the axis shows the same column
+    fn refresh_column(&mut self, axis: Axis, name: &String) {
+        let (old, d, s) = match axis {
+            AxisX => (self.dimx.name.clone(), self.dimx.d, self.dimx.s),
+            AxisY => (self.dimy.name.clone(), self.dimy.d, self.dimy.s),
+            AxisZ => (self.dimz.name.clone(), self.dimzDelta, self.dimzScale),
+        };
+        self.select_column(axis, name);
+        if &old != name {
+            return;
+        }
+        match axis {
+            AxisX => {
+                self.dimx.d = d;
+                self.dimx.s = s;
+            },
+            AxisY => {
+                self.dimy.d = d;
+                self.dimy.s = s;
+            },
+            AxisZ => {
+                self.dimzDelta = d;
+                self.dimzScale = s;
+            }
+        }
+    }
+
+    // shows another table, `sampled` tells if it is a sample
+    fn swap_table(&mut self, table: data::Table, resume: loader::Resume, sampled: bool) {
+        let previous = self.table.columns().clone();
+        let old = mem::replace(&mut self.table, table);
+        let oldResume = mem::replace(&mut self.resume, Some(resume));
+        self.otherTable = match oldResume {
+            Some(r) if sampled != self.sampled => Some((old, r)),
+            // the file was loaded again, the other table is outdated as well
+            _ => None
+        };
+        self.sampled = sampled;
+        self.loadStatus = None;
+
+        self.cancel_tasks();
+        self.apply_definitions();
+        self.rerun_analyses(&previous);
+
+        // buffers have the size of the table
+        self.capacity = self.table.len();
+        self.create_mask_buffer();
+
+        self.crossfilter.sync(&self.table);
+        match self.update_filter() {
+            Ok(()) => (),
+            Err(e) => {
+                self.filter = None;
+                self.filterText = String::new();
+                self.loadStatus = Some(format!("where: {}", e));
+                let _ = self.update_filter();
+            }
+        }
+
+        let first = self.table.columns().iter().next().unwrap().clone();
+        for &axis in [AxisX, AxisY, AxisZ].iter() {
+            let name = match axis {
+                AxisX => self.dimx.name.clone(),
+                AxisY => self.dimy.name.clone(),
+                AxisZ => self.dimz.name.clone(),
+            };
+            if self.table.get(&name).is_some() {
+                self.refresh_column(axis, &name);
+            } else {
+                self.select_column(axis, &first);
+            }
+        }
+    }
+
+    // a running embedding is replaced, its task stops once it notices the dropped receiver
+    fn start_tsne(&mut self, text: &str) -> Result<(), String> {
+        let spec = try!(tsne::parse_spec(text));
+        try!(self.run_tsne(&spec));
+        self.tsneSpec = Some(spec);
+        Ok(())
+    }
+
+    fn run_tsne(&mut self, spec: &tsne::Spec) -> Result<(), String> {
+        let input = try!(tsne::prepare(spec, &self.table));
+        self.tsneTask = Some(tsne::start(input));
+        self.tsneStatus = Some("t-sne: computing neighbors...".to_string());
+        Ok(())
+    }
+
+    // shows the latest embedding of the running task, returns true if anything changed
+    fn poll_tsne(&mut self) -> bool {
+        let mut latest = None;
+        match self.tsneTask {
+            Some(ref rx) => loop {
+                match rx.try_recv() {
+                    Ok(message) => latest = Some(message),
+                    Err(comm::Empty) => break,
+                    Err(comm::Disconnected) => {
+                        if latest.is_none() {
+                            latest = Some(tsne::TsneFailed("task failed".to_string()));
+                        }
+                        break;
+                    }
+                }
+            },
+            None => return false
+        }
+
+        let (x, y, done) = match latest {
+            Some(tsne::TsneProgress(iter, x, y)) => {
+                self.tsneStatus = Some(format!("t-sne: iteration {}", iter));
+                (x, y, false)
+            },
+            Some(tsne::TsneDone(x, y)) => {
+                self.tsneTask = None;
+                self.tsneStatus = None;
+                (x, y, true)
+            },
+            Some(tsne::TsneFailed(e)) => {
+                self.tsneTask = None;
+                self.tsneStatus = Some(format!("t-sne: {}", e));
+                self.run_pending_cluster();
+                return true;
+            },
+            None => return false
+        };
+
+        let columnX = tsne::COLUMN_X.to_string();
+        let columnY = tsne::COLUMN_Y.to_string();
+        // the placeholder columns of --tsne have no range yet
+        let ranged = [&self.dimx, &self.dimy, &self.dimz].iter()
+            .all(|d| (d.name != columnX && d.name != columnY) || !d.min.is_nan());
+        if !done && ranged && self.table.get(&columnX).is_some() && self.table.get(&columnY).is_some() {
+            // intermediate steps only replace the values, ranges, filters and the view stay
+            self.overwrite_column(&columnX, x);
+            self.overwrite_column(&columnY, y);
+            return true;
+        }
+
+        tsne::apply(x, y, &mut self.table);
+        self.crossfilter.sync(&self.table);
+        match self.update_filter() {
+            Ok(()) => (),
+            Err(e) => self.tsneStatus = Some(format!("where: {}", e))
+        }
+        for &axis in [AxisX, AxisY, AxisZ].iter() {
+            let name = match axis {
+                AxisX => self.dimx.name.clone(),
+                AxisY => self.dimy.name.clone(),
+                AxisZ => self.dimz.name.clone(),
+            };
+            if name == columnX || name == columnY {
+                self.refresh_column(axis, &name);
+            }
+        }
+        if done {
+            self.run_pending_cluster();
+        }
+        true
+    }
+
+    // replaces the values of a column and uploads them where shown, without recomputing
+    // statistics or ranges
+    fn overwrite_column(&mut self, name: &String, values: Vec<f64>) {
+        match self.table.get_mut(name) {
+            Some(data) => *data = values,
+            None => return
+        }
+        for dim in [&self.dimx, &self.dimy, &self.dimz].iter() {
+            // normalized values are only updated once the column is reloaded
+            if &dim.name == name && dim.values.is_none() {
+                dim.upload(&self.table);
+            }
+        }
+    }
+
+    fn reset_view(&mut self) {
+        self.pointScale = self.config.pointScale;
+        self.alphaScale = self.config.alphaScale;
+        self.dimx.reset();
+        self.dimy.reset();
+        self.dimz.reset();
+        self.dimzDelta = 0f64;
+        self.dimzScale = 1f64;
+    }
+
+    // data range visible on `axis`
+    fn view(&self, axis: Axis) -> (f64, f64) {
+        match axis {
+            AxisX => self.dimx.visible(self.dimx.s, self.dimx.d, self.dimx.edge()),
+            AxisY => self.dimy.visible(self.dimy.s, self.dimy.d, self.dimy.edge()),
+            AxisZ => self.dimz.visible(-self.dimzScale, self.dimzDelta, 1f64),
+        }
+    }
+
+    // zooms and pans `axis` so that it shows lo..hi
+    fn set_view(&mut self, axis: Axis, lo: f64, hi: f64) {
+        match axis {
+            AxisX => {
+                let (s, d) = self.dimx.fit(lo, hi, self.dimx.edge());
+                self.dimx.s = s;
+                self.dimx.d = d;
+            },
+            AxisY => {
+                let (s, d) = self.dimy.fit(lo, hi, self.dimy.edge());
+                self.dimy.s = s;
+                self.dimy.d = d;
+            },
+            AxisZ => {
+                // the color axis runs from max to min
+                let (s, d) = self.dimz.fit(lo, hi, 1f64);
+                self.dimzScale = s;
+                self.dimzDelta = -d;
+                // the legend ticks follow the same view
+                let std = self.dimz.edge();
+                self.dimz.s = std * s;
+                self.dimz.d = std * d;
+            }
+        }
+    }
+
+    // parses `x LO,HI; y LO,HI; z LO,HI`, each axis is optional
+    fn set_ranges(&mut self, text: &str) -> Result<(), String> {
+        let mut ranges = Vec::new();
+        for part in text.split(';').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+            let fields: Vec<&str> = part.splitn(' ', 1).collect();
+            let axis = match fields[0] {
+                "x" => AxisX,
+                "y" => AxisY,
+                "z" => AxisZ,
+                a => return Err(format!("unknown axis {}", a))
+            };
+            if fields.len() < 2 {
+                return Err(format!("missing range of {}", fields[0]));
+            }
+            ranges.push((axis, try!(parse_limits(fields[1]))));
+        }
+        self.push_zoom();
+        for &(axis, (lo, hi)) in ranges.iter() {
+            self.set_view(axis, lo, hi);
+        }
+        Ok(())
+    }
+
+    fn range_prompt(&self) -> Prompt {
+        let parts: Vec<String> = [("x", AxisX), ("y", AxisY), ("z", AxisZ)].iter().map(|&(key, axis)| {
+            let (lo, hi) = self.view(axis);
+            format!("{} {},{}", key, f64::to_str_digits(lo, 6), f64::to_str_digits(hi, 6))
+        }).collect();
+        Prompt::with_text(PromptRange, parts.connect("; ").as_slice())
+    }
+
+    // widens X or Y around its center until both show the same data units per pixel, or only
+    // adapts the other axis if `fixed` has to stay as it is
+    fn keep_aspect(&mut self, fixed: Option<Axis>) {
+        let margin = 2f64 * self.config.margin as f64;
+        let width = self.dimx.renderLength as f64 - margin;
+        let height = self.dimy.renderLength as f64 - margin;
+        let (xlo, xhi) = self.view(AxisX);
+        let (ylo, yhi) = self.view(AxisY);
+        let ux = (xhi - xlo) / width;
+        let uy = (yhi - ylo) / height;
+        if !(ux > 0f64 && uy > 0f64) || (ux - uy).abs() <= 1e-9 * ux.max(uy) {
+            return;
+        }
+        let fitY = match fixed {
+            Some(AxisX) => true,
+            Some(AxisY) => false,
+            _ => ux > uy
+        };
+        if fitY {
+            let center = (ylo + yhi) / 2f64;
+            self.set_view(AxisY, center - ux * height / 2f64, center + ux * height / 2f64);
+        } else {
+            let center = (xlo + xhi) / 2f64;
+            self.set_view(AxisX, center - uy * width / 2f64, center + uy * width / 2f64);
+        }
+    }
+
+    // data value at window position `pos` along `axis`
+    fn value_at(&self, axis: Axis, pos: f64) -> f64 {
+        match axis {
+            AxisX => self.dimx.value(2f64 * pos / self.dimx.renderLength as f64 - 1f64, self.dimx.s, self.dimx.d),
+            AxisY => self.dimy.value(1f64 - 2f64 * pos / self.dimy.renderLength as f64, self.dimy.s, self.dimy.d),
+            AxisZ => {
+                // the legend spans the plot width and shows the color of -ndc
+                let margin = self.config.margin as f64;
+                let p = 2f64 * (pos - margin) / (self.dimz.renderLength as f64 - 2f64 * margin) - 1f64;
+                self.dimz.value(-p, -self.dimzScale, self.dimzDelta)
+            }
+        }
+    }
+
+    // axis strip under the cursor, the color legend belongs to Z
+    fn strip_hit(&self, mx: f64, my: f64) -> Option<Axis> {
+        let w = self.dimx.renderLength as f64;
+        let h = self.dimy.renderLength as f64;
+        let margin = self.config.margin as f64;
+        let inX = mx >= margin && mx <= w - margin;
+        let inY = my >= margin && my <= h - margin;
+        if inX && my >= h - margin / 2f64 {
+            Some(AxisZ)
+        } else if inX && (my < margin || my > h - margin) {
+            Some(AxisX)
+        } else if inY && (mx < margin || mx > w - margin) {
+            Some(AxisY)
+        } else {
+            None
+        }
+    }
+
+    // remembers the current view for `zoom_back`
+    fn push_zoom(&mut self) {
+        if self.zoomStack.len() == undo::LIMIT {
+            self.zoomStack.remove(0);
+        }
+        let views = (self.view(AxisX), self.view(AxisY), self.view(AxisZ));
+        self.zoomStack.push(views);
+    }
+
+    fn zoom_back(&mut self) {
+        match self.zoomStack.pop() {
+            Some(((xlo, xhi), (ylo, yhi), (zlo, zhi))) => {
+                self.set_view(AxisX, xlo, xhi);
+                self.set_view(AxisY, ylo, yhi);
+                self.set_view(AxisZ, zlo, zhi);
+            },
+            None => ()
+        }
+    }
+
+    // zooms `axis` to the data between the window positions `from` and `to`
+    fn zoom_axis(&mut self, axis: Axis, from: f64, to: f64) {
+        let a = self.value_at(axis, from);
+        let b = self.value_at(axis, to);
+        if a != b && !a.is_nan() && !b.is_nan() {
+            self.set_view(axis, a.min(b), a.max(b));
+        }
+    }
+
+    fn zoom_box(&mut self, x0: f64, y0: f64, x1: f64, y1: f64) {
+        if (x1 - x0).abs() < MIN_ZOOM_PIXELS || (y1 - y0).abs() < MIN_ZOOM_PIXELS {
+            return;
+        }
+        self.push_zoom();
+        self.zoom_axis(AxisX, x0, x1);
+        self.zoom_axis(AxisY, y0, y1);
+    }
+
+    fn zoom_strip(&mut self, axis: Axis, from: f64, to: f64) {
+        if (to - from).abs() < MIN_ZOOM_PIXELS {
+            return;
+        }
+        self.push_zoom();
+        self.zoom_axis(axis, from, to);
+    }
+
+    // zooms the axes under the cursor around the data point under it, the legend scrolls Z
+    fn scroll(&mut self, dx: f64, dy: f64) {
+        let (mx, my) = (self.mouseX as f64, self.mouseY as f64);
+        let (zoomX, zoomY) = match self.strip_hit(mx, my) {
+            Some(AxisZ) => {
+                self.scroll_z(dx, dy);
+                return;
+            },
+            Some(AxisX) => (true, false),
+            Some(AxisY) => (false, true),
+            None => (true, true)
+        };
+        if dy == 0.0 {
+            return;
+        }
+        let factor = WHEEL_ZOOM.powf(dy);
+        if zoomX {
+            let anchor = self.value_at(AxisX, mx);
+            self.dimx.zoom(anchor, factor);
+        }
+        if zoomY {
+            let anchor = self.value_at(AxisY, my);
+            self.dimy.zoom(anchor, factor);
+        }
+    }
+
+    // moves Z with the horizontal and scales it with the vertical wheel
+    fn scroll_z(&mut self, dx: f64, dy: f64) {
+        if dx > 0.0 {
+            self.dimz.d -= 0.05 * self.dimz.span() * self.dimz.s;
+            self.dimzDelta += 0.05 * self.dimz.span() * self.dimzScale;
+        } else if dx < 0.0 {
+            self.dimz.d += 0.05 * self.dimz.span() * self.dimz.s;
+            self.dimzDelta -= 0.05 * self.dimz.span() * self.dimzScale;
+        }
+        if dy > 0.0 {
+            self.dimz.d = self.dimz.d / self.dimz.s;
+            self.dimzDelta = self.dimzDelta / self.dimzScale;
+
+            self.dimz.s *= 1.05;
+            self.dimzScale *= 1.05;
+
+            self.dimz.d = self.dimz.d * self.dimz.s;
+            self.dimzDelta = self.dimzDelta * self.dimzScale;
+        } else if dy < 0.0 {
+            self.dimz.d = self.dimz.d / self.dimz.s;
+            self.dimzDelta = self.dimzDelta / self.dimzScale;
+
+            self.dimz.s /= 1.05;
+            self.dimzScale /= 1.05;
+
+            self.dimz.d = self.dimz.d * self.dimz.s;
+            self.dimzDelta = self.dimzDelta * self.dimzScale;
+        }
+    }
+
+    fn state(&self) -> json::Json {
+        let mut columns = TreeMap::new();
+        let mut ranges = TreeMap::new();
+        for &(key, axis) in [("x", AxisX), ("y", AxisY), ("z", AxisZ)].iter() {
+            let name = match axis {
+                AxisX => &self.dimx.name,
+                AxisY => &self.dimy.name,
+                AxisZ => &self.dimz.name,
+            };
+            let (lo, hi) = self.view(axis);
+            columns.insert(key.to_string(), name.to_json());
+            ranges.insert(key.to_string(), vec![lo, hi].to_json());
+        }
+        let mut state = TreeMap::new();
+        state.insert("columns".to_string(), columns.to_json());
+        state.insert("ranges".to_string(), ranges.to_json());
+        state.insert("rows".to_string(), self.table.len().to_json());
+        state.insert("visible".to_string(), self.visibleCount.to_json());
+        state.insert("pointSize".to_string(), (self.pointScale as f64).to_json());
+        state.insert("alpha".to_string(), (self.alphaScale as f64).to_json());
+        state.insert("filter".to_string(), self.filterText.to_json());
+        state.to_json()
+    }
+
+    // runs a remote command, every command returns the resulting state
+    fn execute(&mut self, method: &str, params: &json::Json) -> Result<json::Json, String> {
+        match method {
+            "state" => (),
+            "set_columns" => {
+                for &(key, axis) in [("x", AxisX), ("y", AxisY), ("z", AxisZ)].iter() {
+                    match control::param_string(params, key) {
+                        Some(name) => {
+                            if self.table.get(&name).is_none() {
+                                return Err(format!("unknown column {}", name));
+                            }
+                            self.select_column(axis, &name);
+                        },
+                        None => ()
+                    }
+                }
+            },
+            "set_range" => {
+                let axis = match control::param_string(params, "axis") {
+                    Some(ref a) if a.as_slice() == "x" => AxisX,
+                    Some(ref a) if a.as_slice() == "y" => AxisY,
+                    Some(ref a) if a.as_slice() == "z" => AxisZ,
+                    _ => return Err("axis must be x, y or z".to_string())
+                };
+                match (control::param_f64(params, "min"), control::param_f64(params, "max")) {
+                    (Some(lo), Some(hi)) if lo < hi => self.set_view(axis, lo, hi),
+                    _ => return Err("expected min < max".to_string())
+                }
+            },
+            "reset" => self.reset_view(),
+            "set_point_size" => match control::param_f64(params, "size") {
+                Some(x) if x > 0f64 => self.pointScale = x as f32,
+                _ => return Err("expected a positive size".to_string())
+            },
+            "set_alpha" => match control::param_f64(params, "alpha") {
+                Some(x) if x >= 0f64 && x <= 1f64 => self.alphaScale = x as f32,
+                _ => return Err("expected an alpha between 0 and 1".to_string())
+            },
+            "filter" => {
+                let text = control::param_string(params, "where").unwrap_or(String::new());
+                try!(self.set_filter(text.as_slice()));
+            },
+            "screenshot" => match control::param_string(params, "path") {
+                Some(path) => try!(self.screenshot(&Path::new(path))),
+                None => return Err("missing path".to_string())
+            },
+            "export" => {
+                let scale = match control::param_f64(params, "scale") {
+                    Some(x) if x > 0f64 => Some(x as f32),
+                    Some(_) => return Err("expected a positive scale".to_string()),
+                    None => None
+                };
+                match control::param_string(params, "path") {
+                    Some(path) => try!(self.export(&Path::new(path), scale)),
+                    None => return Err("missing path".to_string())
+                }
+            },
+            "quit" => self.window.set_should_close(true),
+            _ => return Err(format!("unknown method {}", method))
+        }
+        Ok(self.state())
+    }
+
+    fn axis_state(&self, axis: Axis) -> session::AxisState {
+        let (lo, hi) = self.view(axis);
+        session::AxisState {
+            column: match axis {
+                AxisX => self.dimx.name.clone(),
+                AxisY => self.dimy.name.clone(),
+                AxisZ => self.dimz.name.clone(),
+            },
+            lo: lo,
+            hi: hi,
+        }
+    }
+
+    fn session(&self) -> session::Session {
+        session::Session {
+            file: self.source.as_ref().map(|s| s.path.clone()),
+            x: self.axis_state(AxisX),
+            y: self.axis_state(AxisY),
+            z: self.axis_state(AxisZ),
+            pointScale: self.pointScale as f64,
+            alphaScale: self.alphaScale as f64,
+            rangeMode: self.rangeMode.spec(),
+            hideOutliers: self.hideOutliers,
+            equalAspect: self.equalAspect,
+            filter: self.filterText.clone(),
+            definitions: self.definitions.iter().map(|d| d.source.clone()).collect(),
+            normalizations: self.normalizations.iter()
+                .filter(|&(_, norm)| *norm != normalize::NormNone)
+                .map(|(name, norm)| (name.clone(), norm.name().to_string()))
+                .collect(),
+            selection: self.crossfilter.ranges.iter()
+                .filter(|r| r.is_active())
+                .map(|r| (r.name.clone(), r.lo, r.hi))
+                .collect(),
+        }
+    }
+
+    fn save_session(&self) {
+        match self.session().save(&self.sessionPath) {
+            Ok(()) => println!("Saved session to {}", self.sessionPath.display()),
+            Err(e) => println!("Cannot save session to {}: {}", self.sessionPath.display(), e)
+        }
+    }
+
+    fn load_session(&mut self) {
+        let path = self.sessionPath.clone();
+        match session::Session::load(&path).and_then(|s| self.restore(&s)) {
+            Ok(()) => println!("Loaded session from {}", path.display()),
+            Err(e) => println!("Session: {}", e)
+        }
+    }
+
+    // applies as much of `session` as possible, columns that do not exist are reported and skipped
+    fn restore(&mut self, session: &session::Session) -> Result<(), String> {
+        let mut problems = Vec::new();
+
+        match stats::parse_range_mode(session.rangeMode.as_slice()) {
+            Ok(mode) => self.rangeMode = mode,
+            Err(e) => problems.push(format!("range: {}", e))
+        }
+        self.hideOutliers = session.hideOutliers;
+        self.equalAspect = session.equalAspect;
+
+        for text in session.definitions.iter() {
+            let exists = match expr::parse_definitions(text.as_slice()) {
+                Ok(definitions) => definitions.iter().all(|d| self.table.get(&d.name).is_some()),
+                Err(_) => false
+            };
+            if !exists {
+                match self.derive(text.as_slice()) {
+                    Ok(()) => (),
+                    Err(e) => problems.push(format!("define {}: {}", text, e))
+                }
+            }
+        }
+
+        self.normalizations.clear();
+        for &(ref name, ref norm) in session.normalizations.iter() {
+            match normalize::parse_normalization(norm.as_slice()) {
+                Ok(n) => {
+                    self.normalizations.insert(name.clone(), n);
+                },
+                Err(e) => problems.push(e)
+            }
+        }
+
+        // columns are selected again in any case, the range mode and normalizations may have changed
+        let mut found = [false, false, false];
+        for (i, &(state, axis)) in [(&session.x, AxisX), (&session.y, AxisY), (&session.z, AxisZ)].iter().enumerate() {
+            let name = if self.table.get(&state.column).is_some() {
+                found[i] = true;
+                state.column.clone()
+            } else {
+                problems.push(format!("unknown column {}", state.column));
+                match axis {
+                    AxisX => self.dimx.name.clone(),
+                    AxisY => self.dimy.name.clone(),
+                    AxisZ => self.dimz.name.clone(),
+                }
+            };
+            self.select_column(axis, &name);
+        }
+        for (i, &(state, axis)) in [(&session.x, AxisX), (&session.y, AxisY), (&session.z, AxisZ)].iter().enumerate() {
+            if found[i] && state.lo < state.hi {
+                self.set_view(axis, state.lo, state.hi);
+            }
+        }
+        self.pointScale = session.pointScale as f32;
+        self.alphaScale = session.alphaScale as f32;
+
+        match self.set_filter(session.filter.as_slice()) {
+            Ok(()) => (),
+            Err(e) => problems.push(format!("where: {}", e))
+        }
+
+        match self.restore_selection(&session.selection) {
+            Ok(()) => (),
+            Err(e) => problems.push(e)
+        }
+        self.update_mask();
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.connect(", "))
+        }
+    }
+
+    // answers pending remote commands, returns true if there were any
+    fn poll_control(&mut self) -> bool {
+        let mut any = false;
+        loop {
+            let command = match self.control {
+                Some(ref server) => match server.next() {
+                    Some(c) => c,
+                    None => break
+                },
+                None => break
+            };
+            let result = self.execute(command.method.as_slice(), &command.params);
+            let _ = command.reply.send_opt(result);
+            any = true;
+        }
+        any
+    }
+
+    fn update_filter(&mut self) -> Result<(), String> {
+        self.filterMask = match self.filter {
+            Some(ref f) => try!(expr::eval(f, &self.table)).iter().map(|&x| expr::is_true(x)).collect(),
+            None => Vec::from_elem(self.table.len(), true)
+        };
+        self.update_mask();
+        Ok(())
+    }
+
+    // rows passing the filter, the crossfilter and, if hidden, the outlier ranges
+    fn visible_mask(&self) -> Vec<bool> {
+        let mut mask = self.filterMask.clone();
+        self.crossfilter.apply(&self.table, &mut mask);
+        if self.hideOutliers {
+            self.dimx.hide_outliers(&self.table, &mut mask);
+            self.dimy.hide_outliers(&self.table, &mut mask);
+            self.dimz.hide_outliers(&self.table, &mut mask);
+        }
+        mask
+    }
+
+    fn update_mask(&mut self) {
+        let mask = self.visible_mask();
+        self.visibleCount = mask.iter().filter(|&&m| m).count();
+        let data: Vec<f32> = mask.iter().map(|&m| if m { 1f32 } else { 0f32 }).collect();
+        upload_f32(&self.vboMask, 0, &data);
+    }
+
+    fn set_filter(&mut self, text: &str) -> Result<(), String> {
+        let filter = if text.trim().is_empty() {
+            None
+        } else {
+            Some(try!(expr::parse(text)))
+        };
+        let old = mem::replace(&mut self.filter, filter);
+        match self.update_filter() {
+            Ok(()) => {
+                self.filterText = text.trim().to_string();
+                Ok(())
+            },
+            Err(e) => {
+                self.filter = old;
+                Err(e)
+            }
+        }
+    }
+
+    fn submit_prompt(&mut self) {
+        let prompt = self.prompt.take().unwrap();
+        let result = match prompt.kind {
+            PromptDerive => self.derive(prompt.text.as_slice()),
+            PromptFilter => self.set_filter(prompt.text.as_slice()),
+            PromptPca => self.start_pca(prompt.text.as_slice()),
+            PromptTsne => self.start_tsne(prompt.text.as_slice()),
+            PromptCluster => self.cluster(prompt.text.as_slice()),
+            PromptRange => self.set_ranges(prompt.text.as_slice()),
+        };
+        match result {
+            Ok(()) => (),
+            Err(e) => {
+                self.prompt = Some(Prompt {
+                    kind: prompt.kind,
+                    text: prompt.text,
+                    error: Some(e),
+                });
+            }
+        }
+    }
+
+    fn handle_prompt_key(&mut self, key: glfw::Key, action: glfw::Action) {
+        match (key, action) {
+            (glfw::KeyEscape, glfw::Press) => self.prompt = None,
+            (glfw::KeyEnter, glfw::Press) => self.submit_prompt(),
+            (glfw::KeyBackspace, glfw::Press) | (glfw::KeyBackspace, glfw::Repeat) => {
+                match self.prompt {
+                    Some(ref mut prompt) => {
+                        prompt.text.pop_char();
+                    },
+                    None => ()
+                }
+            },
+            _ => ()
+        }
+    }
+
+    fn panel_rect(&self) -> (f64, f64, f64, f64) {
+        let x = self.dimx.renderLength as f64 - self.config.margin as f64 - PANEL_WIDTH;
+        (x, self.config.margin as f64, PANEL_WIDTH, self.dimy.renderLength as f64 - 2f64 * self.config.margin as f64)
+    }
+
+    fn panel_rows(&self) -> uint {
+        let (_x, _y, _w, h) = self.panel_rect();
+        (h / PANEL_ROW_HEIGHT).floor().max(0f64) as uint
+    }
+
+    fn in_panel(&self, mx: f64, my: f64) -> bool {
+        let (x, y, w, h) = self.panel_rect();
+        self.showPanel && mx >= x && mx <= x + w && my >= y && my <= y + h
+    }
+
+    // position relative to the slider width
+    fn panel_relative(&self, mx: f64) -> f64 {
+        let (x, _y, w, _h) = self.panel_rect();
+        (mx - x - PANEL_PADDING) / (w - 2f64 * PANEL_PADDING)
+    }
+
+    // crossfilter row under the cursor
+    fn panel_hit(&self, mx: f64, my: f64) -> Option<uint> {
+        if !self.in_panel(mx, my) {
+            return None;
+        }
+        let (_x, y, _w, _h) = self.panel_rect();
+        let row = ((my - y) / PANEL_ROW_HEIGHT).floor() as uint + self.panelScroll;
+        if row < self.crossfilter.ranges.len() {
+            Some(row)
+        } else {
+            None
+        }
+    }
+
+    fn draw_panel(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
+        if !self.showPanel {
+            return;
+        }
+        let bg = self.config.background;
+        let fg = self.config.foreground;
+
+        let (x, y, w, h) = self.panel_rect();
+        c.rect(x, y, w, h).rgba(bg[0], bg[1], bg[2], 0.85).draw(&mut self.gl2d);
+
+        let innerWidth = w - 2f64 * PANEL_PADDING;
+        let nrows = self.panel_rows();
+        for (i, range) in self.crossfilter.ranges.iter().enumerate().skip(self.panelScroll).take(nrows) {
+            let top = y + (i - self.panelScroll) as f64 * PANEL_ROW_HEIGHT;
+            let base = top + PANEL_ROW_HEIGHT - 10f64;
+
+            let maxCount = [range.histogram.iter().max().map(|x| x.clone()).unwrap_or(0), 1].iter().max().unwrap().clone();
+            let binWidth = innerWidth / range.histogram.len() as f64;
+            for (b, &count) in range.histogram.iter().enumerate() {
+                let binHeight = PANEL_HISTOGRAM_HEIGHT * count as f64 / maxCount as f64;
+                c.rect(x + PANEL_PADDING + b as f64 * binWidth, base - binHeight, binWidth - 1f64, binHeight)
+                    .rgba(fg[0], fg[1], fg[2], 0.5)
+                    .draw(&mut self.gl2d);
+            }
+
+            let lo = x + PANEL_PADDING + range.relative(range.lo) * innerWidth;
+            let hi = x + PANEL_PADDING + range.relative(range.hi) * innerWidth;
+            c.rect(x + PANEL_PADDING, base + 1f64, innerWidth, 2f64).rgba(0.5, 0.5, 0.5, 1.0).draw(&mut self.gl2d);
+            c.rect(lo, base, hi - lo, 4f64).draw(&mut self.gl2d);
+            c.rect(lo - 2f64, base - 4f64, 4f64, 12f64).draw(&mut self.gl2d);
+            c.rect(hi - 2f64, base - 4f64, 4f64, 12f64).draw(&mut self.gl2d);
+
+            self.textdrawer.render(&c.trans(x + PANEL_PADDING, top + 4f64), &mut self.gl2d, &range.name, textdrawer::Left, textdrawer::Top);
+        }
+    }
+
+    // area that is zoomed to when the mouse button is released
+    fn draw_zoom(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
+        let fg = self.config.foreground;
+        let w = self.dimx.renderLength as f64;
+        let h = self.dimy.renderLength as f64;
+        let margin = self.config.margin as f64;
+        let (mx, my) = (self.mouseX as f64, self.mouseY as f64);
+        let (x0, y0, x1, y1) = match self.activeTransform {
+            TransformZoomBox(x, y) => (x, y, mx, my),
+            TransformZoomAxis(AxisX, x) => (x, margin, mx, h - margin),
+            TransformZoomAxis(AxisY, y) => (margin, y, w - margin, my),
+            TransformZoomAxis(AxisZ, x) => (x, h - margin / 5f64, mx, h),
+            _ => return
+        };
+        c.rect(x0.min(x1), y0.min(y1), (x1 - x0).abs(), (y1 - y0).abs())
+            .rgba(fg[0], fg[1], fg[2], 0.25)
+            .draw(&mut self.gl2d);
+    }
+
+    fn draw_centroids(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
+        if !self.showCentroids {
+            return;
+        }
+        let bg = self.config.background;
+        let centroids = match self.table.get(&cluster::COLUMN.to_string()) {
+            Some(labels) => cluster::centroids(labels, self.dimx.data(&self.table), self.dimy.data(&self.table)),
+            None => return
+        };
+
+        let width = self.dimx.renderLength as f64;
+        let height = self.dimy.renderLength as f64;
+        let margin = self.config.margin as f64;
+        for &(label, x, y) in centroids.iter() {
+            let px = ((self.dimx.ndc(x) + 1f64) / 2f64 * width).floor();
+            let py = ((1f64 - self.dimy.ndc(y)) / 2f64 * height).floor();
+            if px < margin || px > width - margin || py < margin || py > height - margin {
+                continue;
+            }
+            c.rect(px - CENTROID_SIZE - 1f64, py - 2f64, 2f64 * CENTROID_SIZE + 2f64, 4f64).rgba(bg[0], bg[1], bg[2], 1.0).draw(&mut self.gl2d);
+            c.rect(px - 2f64, py - CENTROID_SIZE - 1f64, 4f64, 2f64 * CENTROID_SIZE + 2f64).rgba(bg[0], bg[1], bg[2], 1.0).draw(&mut self.gl2d);
+            c.rect(px - CENTROID_SIZE, py - 1f64, 2f64 * CENTROID_SIZE, 2f64).draw(&mut self.gl2d);
+            c.rect(px - 1f64, py - CENTROID_SIZE, 2f64, 2f64 * CENTROID_SIZE).draw(&mut self.gl2d);
+            self.textdrawer.render(&c.trans(px + CENTROID_SIZE, py - CENTROID_SIZE), &mut self.gl2d, &label.to_string(), textdrawer::Left, textdrawer::Bottom);
+        }
+    }
+
+    fn draw_stats(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
+        if !self.showStats {
+            return;
+        }
+        let bg = self.config.background;
+        // only columns that changed since the last frame are computed again
+        for name in [self.dimx.name.clone(), self.dimy.name.clone(), self.dimz.name.clone()].iter() {
+            self.table.compute_column_stats(name);
+        }
+
+        let mut blocks = Vec::new();
+        for name in [&self.dimx.name, &self.dimy.name, &self.dimz.name].iter() {
+            match self.table.stats(*name) {
+                Some(s) => blocks.push(((*name).clone(), stats::describe(s, self.table.kind(*name)))),
+                None => ()
+            }
+        }
+        if blocks.is_empty() {
+            return;
+        }
+
+        let nrows = blocks[0].val1().len() + 1;
+        let width = STATS_LABEL_WIDTH + blocks.len() as f64 * STATS_VALUE_WIDTH + 2f64 * PANEL_PADDING;
+        let height = nrows as f64 * self.config.fontSize as f64 + 2f64 * PANEL_PADDING;
+        let x = self.config.margin as f64 + PANEL_PADDING;
+        let y = ((self.dimy.renderLength as f64 - height) / 2f64).floor();
+        c.rect(x, y, width, height).rgba(bg[0], bg[1], bg[2], 0.85).draw(&mut self.gl2d);
+
+        let labels: Vec<String> = blocks[0].val1().iter().map(|&(ref label, _)| label.clone()).collect();
+        let top = y + PANEL_PADDING;
+        self.textdrawer.render(&c.trans(x + PANEL_PADDING, top + self.config.fontSize as f64), &mut self.gl2d, &labels.connect("\n"), textdrawer::Left, textdrawer::Top);
+        for (i, &(ref name, ref rows)) in blocks.iter().enumerate() {
+            let right = x + PANEL_PADDING + STATS_LABEL_WIDTH + (i + 1) as f64 * STATS_VALUE_WIDTH;
+            let values: Vec<String> = rows.iter().map(|&(_, ref value)| value.clone()).collect();
+            self.textdrawer.render(&c.trans(right, top), &mut self.gl2d, name, textdrawer::Right, textdrawer::Top);
+            self.textdrawer.render(&c.trans(right, top + self.config.fontSize as f64), &mut self.gl2d, &values.connect("\n"), textdrawer::Right, textdrawer::Top);
+        }
+    }
+
+    fn draw_prompt(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
+        let text = match self.prompt {
+            Some(ref prompt) => prompt.display(),
+            None => return
+        };
+        self.textdrawer.render(&c.trans(INFO_MARGIN, INFO_MARGIN + self.config.fontSize as f64), &mut self.gl2d, &text, textdrawer::Left, textdrawer::Bottom);
+    }
+
+    fn draw_picker(&mut self, c: &graphics::Context<(),[f32, ..4]>) {
+        let bg = self.config.background;
+        let fg = self.config.foreground;
+        let (header, matches, selected) = match self.picker {
+            Some(ref p) => {
+                let axis = match self.pickerAxis {
+                    AxisX => "x",
+                    AxisY => "y",
+                    AxisZ => "z",
+                };
+                let header = format!("column for {} (tab to change): {}_    {}/{}", axis, p.query, p.matches.len(), self.table.columns().len());
+                (header, p.matches.clone(), p.selected)
+            },
+            None => return
+        };
+
+        let x = self.config.margin as f64 + PANEL_PADDING;
+        let y = self.config.margin as f64;
+        let width = self.dimx.renderLength as f64 - 2f64 * x;
+        let height = self.dimy.renderLength as f64 - 2f64 * self.config.margin as f64;
+        c.rect(x, y, width, height).rgba(bg[0], bg[1], bg[2], 0.9).draw(&mut self.gl2d);
+        self.textdrawer.render(&c.trans(x + PANEL_PADDING, y + PANEL_PADDING), &mut self.gl2d, &header, textdrawer::Left, textdrawer::Top);
+
+        // the selected row stays visible
+        let nrows = cmp::max(((height - 2f64 * PANEL_PADDING) / PICKER_ROW_HEIGHT) as uint, 2) - 1;
+        let first = if selected >= nrows { selected - nrows + 1 } else { 0 };
+        for name in matches.iter().skip(first).take(nrows) {
+            self.table.compute_column_stats(name);
+        }
+        for (i, name) in matches.iter().enumerate().skip(first).take(nrows) {
+            let top = y + PANEL_PADDING + (i - first + 1) as f64 * PICKER_ROW_HEIGHT;
+            if i == selected {
+                c.rect(x, top, width, PICKER_ROW_HEIGHT).rgba(fg[0], fg[1], fg[2], 0.25).draw(&mut self.gl2d);
+            }
+            let kind = self.table.kind(name);
+            let details = match self.table.stats(name) {
+                Some(s) => {
+                    let (lo, hi) = if kind == data::KindTime {
+                        (datetime::format_iso8601(s.min), datetime::format_iso8601(s.max))
+                    } else {
+                        (f64::to_str_digits(s.min, 4), f64::to_str_digits(s.max, 4))
+                    };
+                    format!("{}    NaN: {}    {} .. {}", kind.label(), s.nanCount, lo, hi)
+                },
+                None => kind.label().to_string()
+            };
+            self.textdrawer.render(&c.trans(x + PANEL_PADDING, top + 2f64), &mut self.gl2d, name, textdrawer::Left, textdrawer::Top);
+            self.textdrawer.render(&c.trans(x + width - PANEL_PADDING, top + 2f64), &mut self.gl2d, &details, textdrawer::Right, textdrawer::Top);
+        }
+    }
+
+    fn handle_picker_key(&mut self, key: glfw::Key, action: glfw::Action) {
+        if action == glfw::Release {
+            return;
+        }
+        match key {
+            glfw::KeyEscape => self.picker = None,
+            glfw::KeyEnter => {
+                let name = match self.picker {
+                    Some(ref p) => p.current().map(|c| c.clone()),
+                    None => None
+                };
+                match name {
+                    Some(name) => {
+                        self.picker = None;
+                        let axis = self.pickerAxis;
+                        self.select_column(axis, &name);
+                    },
+                    None => ()
+                }
+            },
+            glfw::KeyTab => {
+                self.pickerAxis = match self.pickerAxis {
+                    AxisX => AxisY,
+                    AxisY => AxisZ,
+                    AxisZ => AxisX,
+                };
+            },
+            _ => match self.picker {
+                Some(ref mut p) => match key {
+                    glfw::KeyUp => p.step(-1),
+                    glfw::KeyDown => p.step(1),
+                    glfw::KeyPageUp => p.step(-PICKER_PAGE),
+                    glfw::KeyPageDown => p.step(PICKER_PAGE),
+                    glfw::KeyBackspace => {
+                        p.query.pop_char();
+                        p.update();
+                    },
+                    _ => ()
+                },
+                None => ()
+            }
+        }
+    }
+
+    // selects the saved ranges clamped to the data, fails with the columns that no longer exist
+    fn restore_selection(&mut self, selection: &Vec<(String, f64, f64)>) -> Result<(), String> {
+        let mut unknown = Vec::new();
+        for range in self.crossfilter.ranges.mut_iter() {
+            range.reset();
+        }
+        for &(ref name, lo, hi) in selection.iter() {
+            match self.crossfilter.find_mut(name) {
+                Some(r) => {
+                    r.lo = lo.max(r.min);
+                    r.hi = hi.min(r.max);
+                },
+                None => unknown.push(format!("unknown column {}", name))
+            }
+        }
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(unknown.connect(", "))
+        }
+    }
+
+    // goes back to `state`, keeping the dimensions and crossfilter when only the view differs
+    fn restore_step(&mut self, state: &session::Session) {
+        if !state.same_columns(&self.session()) {
+            let _ = self.restore(state);
+            return;
+        }
+        for &(axis, ref view) in [(AxisX, &state.x), (AxisY, &state.y), (AxisZ, &state.z)].iter() {
+            if view.lo < view.hi {
+                self.set_view(axis, view.lo, view.hi);
+            }
+        }
+        self.pointScale = state.pointScale as f32;
+        self.alphaScale = state.alphaScale as f32;
+        self.equalAspect = state.equalAspect;
+        if state.filter != self.filterText {
+            let _ = self.set_filter(state.filter.as_slice());
+        }
+        let selection: Vec<(String, f64, f64)> = self.crossfilter.ranges.iter()
+            .filter(|r| r.is_active())
+            .map(|r| (r.name.clone(), r.lo, r.hi))
+            .collect();
+        if state.selection != selection {
+            let _ = self.restore_selection(&state.selection);
+            self.update_mask();
+        }
+    }
+
+    fn undo(&mut self) {
+        let current = self.session();
+        match self.history.undo(current) {
+            Some(state) => self.restore_step(&state),
+            None => ()
+        }
+    }
+
+    fn redo(&mut self) {
+        let current = self.session();
+        match self.history.redo(current) {
+            Some(state) => self.restore_step(&state),
+            None => ()
+        }
+    }
+
+    // handles undo and redo and records every other change as an undo step, a quick series of
+    // view changes (scrolling, key repeats, resizing, ...) becomes a single step
+    fn handle_event(&mut self, event: glfw::WindowEvent) {
+        let tracked = match event {
+            glfw::KeyEvent(key, _, glfw::Press, mods) if self.prompt.is_none() && self.picker.is_none() => {
+                match self.config.keys.lookup(key, mods) {
+                    // moving through the history is not recorded itself
+                    Some(keymap::ActionUndo) | Some(keymap::ActionRedo) => false,
+                    _ => true
+                }
+            },
+            glfw::KeyEvent(..) | glfw::MouseButtonEvent(..) | glfw::ScrollEvent(..) => true,
+            // resizing changes the view to keep the aspect
+            glfw::SizeEvent(..) => self.equalAspect,
+            _ => false
+        };
+        if !tracked {
+            self.handle_input(event);
+            return;
+        }
+
+        let before = match self.history.take_drag() {
+            Some(state) => state,
+            None => self.session()
+        };
+        self.handle_input(event);
+
+        match self.activeTransform {
+            TransformNone => (),
+            _ => {
+                // the whole drag becomes one step once the button is released
+                self.history.start_drag(before);
+                return;
+            }
+        }
+        let after = self.session();
+        let now = self.glfw.get_time();
+        self.history.change(before, &after, now);
+    }
+
+    fn handle_input(&mut self, event: glfw::WindowEvent) {
+        match event {
+            glfw::SizeEvent(w, h) => {
+                self.dimx.renderLength = w;
+                self.dimy.renderLength = h;
+                self.dimz.renderLength = w;
+                gl::Viewport(0, 0, self.dimx.renderLength, self.dimy.renderLength);
+
+                gl::BindTexture(gl::TEXTURE_2D, self.texture);
+                unsafe {
+                    gl::TexImage2D(gl::TEXTURE_2D, 0, gl::RGBA32F as i32, self.dimx.renderLength, self.dimy.renderLength, 0, gl::RGBA, gl::FLOAT, ptr::null());
+                }
+                gl::BindTexture(gl::TEXTURE_2D, 0);
+                if self.equalAspect {
+                    self.keep_aspect(None);
+                }
+            },
+            glfw::CursorPosEvent(xpos, ypos) => {
+                match self.activeTransform {
+                    TransformMove => {
+                        let xdiff = (xpos as f32 - self.mouseX) / self.dimx.renderLength as f32;
+                        let ydiff = (self.mouseY - ypos as f32) / self.dimy.renderLength as f32;
+
+                        self.dimx.d += xdiff as f64 * self.dimx.span();
+                        self.dimy.d += ydiff as f64 * self.dimy.span();
+                    },
+                    TransformScale => {
+                        let x1 = self.mouseX - self.dimx.renderLength as f32 / 2.0f32;
+                        let x2 = xpos as f32 - self.dimx.renderLength as f32 / 2.0f32;
+                        let y1 = self.mouseY - self.dimy.renderLength as f32 / 2.0f32;
+                        let y2 = ypos as f32 - self.dimy.renderLength as f32 / 2.0f32;
+
+
+                        self.dimx.d = self.dimx.d / self.dimx.s;
+                        self.dimy.d = self.dimy.d / self.dimy.s;
+                        self.dimx.s *= (x2 / x1) as f64;
+                        self.dimy.s *= (y2 / y1) as f64;
+                        self.dimx.d = self.dimx.d * self.dimx.s;
+                        self.dimy.d = self.dimy.d * self.dimy.s;
+                    },
+                    TransformRange(row, upper) => {
+                        let pos = self.panel_relative(xpos);
+                        self.crossfilter.ranges.get_mut(row).set_relative(pos, upper);
+                        self.update_mask();
+                    },
+                    TransformZoomBox(..) | TransformZoomAxis(..) | TransformNone => ()
+                }
+                self.mouseX = xpos as f32;
+                self.mouseY = ypos as f32;
+            },
+            glfw::MouseButtonEvent(button, action, _mods) if self.panel_hit(self.mouseX as f64, self.mouseY as f64).is_some() && action == glfw::Press => {
+                let row = self.panel_hit(self.mouseX as f64, self.mouseY as f64).unwrap();
+                let pos = self.panel_relative(self.mouseX as f64);
+                match button {
+                    glfw::MouseButton1 => {
+                        // drag the bound that is closer to the cursor
+                        let upper = {
+                            let range = &self.crossfilter.ranges[row];
+                            (pos - range.relative(range.lo)).abs() > (pos - range.relative(range.hi)).abs()
+                        };
+                        self.crossfilter.ranges.get_mut(row).set_relative(pos, upper);
+                        self.activeTransform = TransformRange(row, upper);
+                    },
+                    _ => self.crossfilter.ranges.get_mut(row).reset()
+                }
+                self.update_mask();
+            },
+            glfw::MouseButtonEvent(button, action, mods) => {
+                let (mx, my) = (self.mouseX as f64, self.mouseY as f64);
+                match (button, action, self.activeTransform) {
+                    (glfw::MouseButton1, glfw::Press, TransformNone) if mods.contains(glfw::Shift) => {
+                        self.activeTransform = TransformZoomBox(mx, my);
+                    },
+                    (glfw::MouseButton1, glfw::Press, TransformNone) if self.strip_hit(mx, my).is_some() => {
+                        let axis = self.strip_hit(mx, my).unwrap();
+                        let pos = match axis {
+                            AxisY => my,
+                            _ => mx
+                        };
+                        self.activeTransform = TransformZoomAxis(axis, pos);
+                    },
+                    (glfw::MouseButton1, glfw::Press, TransformNone) => {
+                        self.activeTransform = TransformMove;
+                    },
+                    (glfw::MouseButton1, glfw::Release, TransformZoomBox(x0, y0)) => {
+                        self.activeTransform = TransformNone;
+                        self.zoom_box(x0, y0, mx, my);
+                    },
+                    (glfw::MouseButton1, glfw::Release, TransformZoomAxis(axis, from)) => {
+                        self.activeTransform = TransformNone;
+                        let to = match axis {
+                            AxisY => my,
+                            _ => mx
+                        };
+                        self.zoom_strip(axis, from, to);
+                    },
+                    (glfw::MouseButton1, glfw::Release, TransformMove) => {
+                        self.activeTransform = TransformNone;
+                    },
+                    (glfw::MouseButton2, glfw::Press, TransformNone) => {
+                        self.activeTransform = TransformScale;
+                    },
+                    (glfw::MouseButton2, glfw::Release, TransformScale) => {
+                        self.activeTransform = TransformNone;
+                    },
+                    (glfw::MouseButton1, glfw::Release, TransformRange(..)) => {
+                        self.activeTransform = TransformNone;
+                    },
+                    _ => ()
+                }
+            },
+            glfw::ScrollEvent(_dx, dy) if self.in_panel(self.mouseX as f64, self.mouseY as f64) => {
+                let maxScroll = self.crossfilter.ranges.len() - [self.panel_rows(), self.crossfilter.ranges.len()].iter().min().unwrap().clone();
+                if dy > 0.0 && self.panelScroll > 0 {
+                    self.panelScroll -= 1;
+                } else if dy < 0.0 && self.panelScroll < maxScroll {
+                    self.panelScroll += 1;
+                }
+            },
+            glfw::ScrollEvent(dx, dy) => self.scroll(dx, dy),
+            glfw::CharEvent(c) if self.picker.is_some() => {
+                match self.picker {
+                    Some(ref mut p) => {
+                        p.query.push_char(c);
+                        p.update();
+                    },
+                    None => ()
+                }
+            },
+            glfw::CharEvent(c) => {
+                match self.prompt {
+                    Some(ref mut prompt) => prompt.text.push_char(c),
+                    None => ()
+                }
+            },
+            glfw::KeyEvent(key, _scancode, action, _mods) if self.picker.is_some() => {
+                self.handle_picker_key(key, action);
+            },
+            glfw::KeyEvent(key, _scancode, action, _mods) if self.prompt.is_some() => {
+                self.handle_prompt_key(key, action);
+            },
+            glfw::KeyEvent(key, _scancode, action, mods) => {
+                match self.config.keys.lookup(key, mods) {
+                    Some(a) if action == (if a.on_release() { glfw::Release } else { glfw::Press }) => self.run_action(a),
+                    _ => ()
+                }
+            }
+            _ => ()
+        }
+    }
+
+    fn run_action(&mut self, action: keymap::Action) {
+        match action {
+            keymap::ActionQuit => self.window.set_should_close(true),
+            keymap::ActionPointLarger => self.pointScale *= 1.5f32,
+            keymap::ActionPointSmaller => self.pointScale = 1f32.max(self.pointScale / 1.5f32),
+            keymap::ActionAlphaLower => self.alphaScale = 0f32.max(self.alphaScale - 0.02f32),
+            keymap::ActionAlphaHigher => self.alphaScale = 1f32.min(self.alphaScale + 0.02f32),
+            keymap::ActionHelp => self.showHelp = !self.showHelp,
+            keymap::ActionStats => self.showStats = !self.showStats,
+            keymap::ActionRangeMode => {
+                self.rangeMode = self.rangeMode.cycle();
+                self.reload_dimensions();
+            },
+            keymap::ActionOutliers => {
+                self.hideOutliers = !self.hideOutliers;
+                self.reload_dimensions();
+            },
+            keymap::ActionReset => self.reset_view(),
+            keymap::ActionUndo => self.undo(),
+            keymap::ActionRedo => self.redo(),
+            keymap::ActionZoomBack => self.zoom_back(),
+            keymap::ActionDerive => self.prompt = Some(Prompt::new(PromptDerive)),
+            keymap::ActionPanel => self.showPanel = !self.showPanel,
+            keymap::ActionSaveRanges => {
+                match self.crossfilter.save(&self.rangesPath) {
+                    Ok(()) => println!("Saved ranges to {}", self.rangesPath.display()),
+                    Err(e) => println!("Cannot save ranges to {}: {}", self.rangesPath.display(), e)
+                }
+            },
+            keymap::ActionSaveSession => self.save_session(),
+            keymap::ActionLoadSession => self.load_session(),
+            keymap::ActionExport => self.export_view(),
+            keymap::ActionPca => self.prompt = Some(Prompt::new(PromptPca)),
+            keymap::ActionTsne => self.prompt = Some(Prompt::new(PromptTsne)),
+            keymap::ActionCluster => self.prompt = Some(Prompt::new(PromptCluster)),
+            keymap::ActionRangePrompt => self.prompt = Some(self.range_prompt()),
+            keymap::ActionPicker => {
+                self.picker = Some(picker::Picker::new(self.column_order()));
+                self.pickerAxis = AxisX;
+            },
+            keymap::ActionEqualAspect => {
+                self.equalAspect = !self.equalAspect;
+                if self.equalAspect {
+                    self.keep_aspect(None);
+                }
+            },
+            keymap::ActionCentroids => self.showCentroids = !self.showCentroids,
+            keymap::ActionSample => self.toggle_sample(),
+            keymap::ActionNormX => self.cycle_normalization(AxisX),
+            keymap::ActionNormY => self.cycle_normalization(AxisY),
+            keymap::ActionNormZ => self.cycle_normalization(AxisZ),
+            keymap::ActionFilter => {
+                self.prompt = Some(Prompt::with_text(PromptFilter, self.filterText.as_slice()));
+            },
+            keymap::ActionNextX => self.step_column(AxisX, true),
+            keymap::ActionPrevX => self.step_column(AxisX, false),
+            keymap::ActionNextY => self.step_column(AxisY, true),
+            keymap::ActionPrevY => self.step_column(AxisY, false),
+            keymap::ActionNextZ => self.step_column(AxisZ, true),
+            keymap::ActionPrevZ => self.step_column(AxisZ, false),
+        }
+    }
+
+    fn redraw(&mut self) {
+        self.draw();
+        self.window.swap_buffers();
+    }
+
+    // accumulates the points into the bound framebuffer, which covers `tile` (x, y, width and
+    // height, from the bottom left) of the frame enlarged by `scale`
+    fn draw_points(&mut self, scale: f32, tile: (f32, f32, f32, f32)) {
+        let (tx, ty, tw, th) = tile;
+        let width = self.dimx.renderLength as f32 * scale;
+        let height = self.dimy.renderLength as f32 * scale;
+        gl::Viewport(0, 0, tw as i32, th as i32);
+        gl::ClearColor(0.0, 0.0, 0.0, 0.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::Enable(gl::VERTEX_PROGRAM_POINT_SIZE);
+        gl::Enable(gl::BLEND);
+        gl::BlendFuncSeparate(gl::SRC_ALPHA, gl::ONE, gl::ONE, gl::ONE);
+
+        self.vaoPoints.bind();
+        self.programPoints.bind();
+
+        self.dimx.rebase(&self.table);
+        self.dimy.rebase(&self.table);
+        let (ax, bx) = self.dimx.coefficients(self.dimx.s, self.dimx.d);
+        let (ay, by) = self.dimy.coefficients(self.dimy.s, self.dimy.d);
+        let (az, bz) = self.dimz.coefficients(-self.dimzScale, self.dimzDelta);
+        let (sx, ox) = export::tile_transform(width, tx, tw);
+        let (sy, oy) = export::tile_transform(height, ty, th);
+        let finalTransformation = cgmath::Matrix4::<f32>::new(
+            ax * sx, 0.0f32, 0.0f32, 0.0f32,
+            0.0f32, ay * sy, 0.0f32, 0.0f32,
+            0.0f32, 0.0f32, az, 0.0f32,
+            bx * sx + ox, by * sy + oy, bz, 1.0f32
+        );
+        unsafe {
+            gl::UniformMatrix4fv(self.ulocationPoints.transformation, 1, gl::FALSE, mem::transmute(&finalTransformation.as_fixed()[0][0]));
+        }
+
+        gl::Uniform1f(self.ulocationPoints.width, width);
+        gl::Uniform1f(self.ulocationPoints.height, height);
+        gl::Uniform1f(self.ulocationPoints.pointScale, self.pointScale * scale);
+        gl::Uniform1f(self.ulocationPoints.margin, self.config.margin * scale);
+        gl::Uniform2f(self.ulocationPoints.tileOrigin, tx, ty);
+        gl::Uniform2f(self.ulocationPoints.tileSize, tw, th);
+
+        self.vaoPoints.draw_array(hgl::Points, 0, self.table.len() as i32);
+    }
+
+    // renders a frame into the back buffer
+    fn draw(&mut self) {
+        // draw to texture
+        gl::BindFramebuffer(gl::FRAMEBUFFER, self.framebuffer);
+        let window = (0f32, 0f32, self.dimx.renderLength as f32, self.dimy.renderLength as f32);
+        self.draw_points(1f32, window);
+
+        // render to texture to viewport
+        self.vaoTexture.bind();
+        self.programTexture.bind();
+
+        gl::BindFramebuffer(gl::FRAMEBUFFER, 0);
+        gl::Viewport(0, 0, self.dimx.renderLength, self.dimy.renderLength);
+        let bg = self.config.background;
+        gl::ClearColor(bg[0], bg[1], bg[2], 1.0);
+        gl::Clear(gl::COLOR_BUFFER_BIT);
+        gl::Enable(gl::BLEND);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        gl::BindTexture(gl::TEXTURE_2D, self.texture);
+        gl::Uniform1i(self.ulocationTexture.fboTexture, 0);
+        gl::Uniform1f(self.ulocationTexture.count, self.visibleCount as f32);
+        gl::Uniform1f(self.ulocationTexture.alpha, self.alphaScale);
+        self.vaoTexture.draw_array(hgl::Triangles, 0, VERTEX_DATA_TEXTURE.len() as i32 / 2);
+
+        // draw legend (reuse vaoTexture)
+        self.programLegend.bind();
+        gl::Uniform1f(self.ulocationLegend.width, self.dimx.renderLength as f32);
+        gl::Uniform1f(self.ulocationLegend.height, self.dimy.renderLength as f32);
+        gl::Uniform1f(self.ulocationLegend.margin, self.config.margin as f32);
+        gl::Uniform4f(self.ulocationLegend.tile, 1f32, 1f32, 0f32, 0f32);
+        self.vaoTexture.draw_array(hgl::Triangles, 0, VERTEX_DATA_TEXTURE.len() as i32 / 2);
+
+        gl::BindVertexArray(0);
         gl::UseProgram(0);
         self.gl2d.clear_shader();
         let c = graphics::Context::abs(self.dimx.renderLength as f64, self.dimy.renderLength as f64)
-            .rgb(0.23, 0.80, 0.62);
+            .rgb(self.config.foreground[0], self.config.foreground[1], self.config.foreground[2]);
 
         if self.showHelp {
             let help_c = c.trans((self.dimx.renderLength as f64 / 2f64).floor(), (self.dimy.renderLength as f64 / 2f64).floor());
-            self.textdrawer.render(&help_c, &mut self.gl2d, &HELP_TEXT.to_string(), textdrawer::Center, textdrawer::Middle);
+            self.textdrawer.render(&help_c, &mut self.gl2d, &format!("== HELP ==\n{}\n{}", self.config.keys.help(), MOUSE_HELP), textdrawer::Center, textdrawer::Middle);
         }
         self.draw_x_axis(&c);
         self.draw_y_axis(&c);
         self.draw_z_axis(&c);
+        self.draw_centroids(&c);
+        self.draw_panel(&c);
+        self.draw_zoom(&c);
+        self.draw_stats(&c);
+        self.draw_picker(&c);
+        self.draw_prompt(&c);
+
+        let mut info = if self.visibleCount != self.table.len() {
+            format!("#objects: {}/{}", self.visibleCount, self.table.len())
+        } else {
+            format!("#objects: {}", self.table.len())
+        };
+        if self.sampled {
+            info.push_str(" (sample)");
+        }
+        match self.loadStatus {
+            Some(ref status) => info.push_str(format!("\n{}", status).as_slice()),
+            None => ()
+        }
+        match self.stream {
+            Some(ref stream) => info.push_str(format!("\n{}", stream.status()).as_slice()),
+            None => ()
+        }
+        if self.rangeMode != stats::RangeFull {
+            let outliers = if self.hideOutliers { "hidden" } else { "clamped" };
+            info.push_str(format!("\nrange: {}, outliers {}", self.rangeMode.label(), outliers).as_slice());
+        }
+        if self.equalAspect {
+            info.push_str("\naspect: equal");
+        }
+        if self.filter.is_some() {
+            info.push_str(format!("\nwhere: {}", self.filterText).as_slice());
+        }
+        match self.pcaStatus {
+            Some(ref status) => info.push_str(format!("\n{}", status).as_slice()),
+            None => ()
+        }
+        match self.tsneStatus {
+            Some(ref status) => info.push_str(format!("\n{}", status).as_slice()),
+            None => ()
+        }
+        match self.clusterStatus {
+            Some(ref status) => info.push_str(format!("\n{}", status).as_slice()),
+            None => ()
+        }
+        match self.crossfilter.summary() {
+            Some(summary) => info.push_str(format!("\n{}", summary).as_slice()),
+            None => ()
+        }
+        self.textdrawer.render(&c.trans(self.dimx.renderLength as f64 - INFO_MARGIN, INFO_MARGIN), &mut self.gl2d, &info, textdrawer::Right, textdrawer::Top);
+    }
+
+    // renders a frame and saves it as binary PPM
+    fn screenshot(&mut self, path: &Path) -> Result<(), String> {
+        self.draw();
+        let width = self.dimx.renderLength as uint;
+        let height = self.dimy.renderLength as uint;
+        let mut pixels = Vec::from_elem(width * height * 3, 0u8);
+        gl::ReadBuffer(gl::BACK);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        unsafe {
+            gl::ReadPixels(0, 0, width as i32, height as i32, gl::RGB, gl::UNSIGNED_BYTE, mem::transmute(pixels.as_mut_ptr()));
+        }
 
-        self.textdrawer.render(&c.trans(self.dimx.renderLength as f64 - INFO_MARGIN, INFO_MARGIN), &mut self.gl2d, &format!("#objects: {}", self.table.len()), textdrawer::Right, textdrawer::Top);
+        let mut file = try!(io::File::create(path).map_err(|e| format!("{}", e)));
+        try!(file.write_str(format!("P6\n{} {}\n255\n", width, height).as_slice()).map_err(|e| format!("{}", e)));
+        // OpenGL rows start at the bottom
+        for y in range(0, height).rev() {
+            try!(file.write(pixels.slice(y * width * 3, (y + 1) * width * 3)).map_err(|e| format!("{}", e)));
+        }
+        Ok(())
+    }
 
-        self.window.swap_buffers();
+    // the view as vector shapes, see `export::Figure`, too many points are replaced by a density
+    // raster with `rasterScale` times the window resolution
+    fn figure(&mut self, rasterScale: f32) -> Result<export::Figure, String> {
+        let width = self.dimx.renderLength as f64;
+        let height = self.dimy.renderLength as f64;
+        let margin = self.config.margin as f64;
+        let plot = (margin, margin, width - 2f64 * margin, height - 2f64 * margin);
+        let mut figure = export::Figure::new(width, height, self.config.fontSize as f64, self.config.background, self.config.foreground, plot);
+
+        if self.visibleCount <= EXPORT_CIRCLE_LIMIT {
+            self.figure_points(&mut figure);
+        } else {
+            let raster = try!(self.render_density(rasterScale));
+            figure.plot.push(export::ShapeImage(margin, margin, width - 2f64 * margin, height - 2f64 * margin, raster));
+        }
+
+        // same layout as draw_x_axis, draw_y_axis and draw_z_axis
+        let tickDistance = self.config.tickDistance;
+        figure.line(margin, margin, width - margin, margin, 2f64);
+        figure.line(margin, height - margin, width - margin, height - margin, 2f64);
+        figure.text((width / 2f64).floor(), LABEL_MARGIN, 0f64, textdrawer::Center, textdrawer::Top, self.dimx.title.as_slice());
+        figure.text((width / 2f64).floor(), height - LABEL_MARGIN, 0f64, textdrawer::Center, textdrawer::Bottom, self.dimx.title.as_slice());
+        let (mmin, mmax, markers) = self.dimx.calc_axis_markers(tickDistance);
+        for &(m, ref text) in markers.iter() {
+            let pos = (margin + (m - mmin) / (mmax - mmin) * (width - 2f64 * margin)).floor();
+            figure.text(pos, margin - 10f64, 270f64, textdrawer::Left, textdrawer::Middle, text.as_slice());
+            figure.text(pos, height - margin + 10f64, 90f64, textdrawer::Left, textdrawer::Middle, text.as_slice());
+            figure.line(pos, margin - TICK_LENGTH, pos, margin, 2f64 * TICK_WIDTH);
+            figure.line(pos, height - margin + TICK_LENGTH, pos, height - margin, 2f64 * TICK_WIDTH);
+        }
+
+        figure.line(margin, margin, margin, height - margin, 2f64);
+        figure.line(width - margin, margin, width - margin, height - margin, 2f64);
+        figure.text(LABEL_MARGIN, (height / 2f64).floor(), 270f64, textdrawer::Center, textdrawer::Top, self.dimy.title.as_slice());
+        figure.text(width - LABEL_MARGIN, (height / 2f64).floor(), 90f64, textdrawer::Center, textdrawer::Top, self.dimy.title.as_slice());
+        let (mmin, mmax, markers) = self.dimy.calc_axis_markers(tickDistance);
+        for &(m, ref text) in markers.iter() {
+            let pos = (margin + (1f64 - (m - mmin) / (mmax - mmin)) * (height - 2f64 * margin)).floor();
+            figure.text(margin - 10f64, pos, 0f64, textdrawer::Right, textdrawer::Middle, text.as_slice());
+            figure.text(width - margin + 10f64, pos, 0f64, textdrawer::Left, textdrawer::Middle, text.as_slice());
+            figure.line(margin - TICK_LENGTH, pos, margin, pos, 2f64 * TICK_WIDTH);
+            figure.line(width - margin + TICK_LENGTH, pos, width - margin, pos, 2f64 * TICK_WIDTH);
+        }
+
+        // the legend shows z2rgb(-1) to z2rgb(1) like the legend shader
+        let legend = height - margin / 5f64;
+        let colors: Vec<export::Color> = range(0, EXPORT_LEGEND_STEPS)
+            .map(|i| export::z2rgb(2f64 * i as f64 / (EXPORT_LEGEND_STEPS - 1) as f64 - 1f64))
+            .collect();
+        figure.shapes.push(export::ShapeGradient(margin, legend, width - 2f64 * margin, margin / 5f64, colors));
+        figure.line(margin, legend, width - margin, legend, 2f64);
+        figure.text(INFO_MARGIN, height - INFO_MARGIN, 0f64, textdrawer::Left, textdrawer::Bottom, format!("z: {}", self.dimz.title).as_slice());
+        let (mmin, mmax, markers) = self.dimz.calc_axis_markers(tickDistance);
+        for &(m, ref text) in markers.iter() {
+            let pos = (margin + (m - mmin) / (mmax - mmin) * (width - 2f64 * margin)).floor();
+            figure.text(pos, legend - 10f64, 0f64, textdrawer::Center, textdrawer::Bottom, text.as_slice());
+            figure.line(pos, legend - TICK_LENGTH, pos, legend, 2f64 * TICK_WIDTH);
+        }
+        Ok(figure)
+    }
+
+    // one circle per visible point, shaded like the points around it on the screen
+    fn figure_points(&self, figure: &mut export::Figure) {
+        let width = self.dimx.renderLength as f64;
+        let height = self.dimy.renderLength as f64;
+        let clamp = |dim: &Dimension, x: f64| match dim.clamp {
+            Some((lo, hi)) => x.max(lo).min(hi),
+            None => x
+        };
+        let (span, center) = self.dimz.extent();
+        let radius = self.pointScale as f64 / 2f64;
+        let mask = self.visible_mask();
+        let rows = mask.iter()
+            .zip(self.dimx.data(&self.table).iter())
+            .zip(self.dimy.data(&self.table).iter())
+            .zip(self.dimz.data(&self.table).iter());
+        // the screen adds up the points of each pixel before shading, so circles are grouped
+        // into cells of a point diameter and shaded like the sum of their cell
+        let cell = (2f64 * radius).max(1f64);
+        let mut points = Vec::new();
+        let mut cells: HashMap<(int, int), [f64, ..4]> = HashMap::new();
+        for (((&visible, &x), &y), &z) in rows {
+            if !visible || x.is_nan() || y.is_nan() {
+                continue;
+            }
+            let px = (self.dimx.ndc(clamp(&self.dimx, x)) + 1f64) / 2f64 * width;
+            let py = (1f64 - self.dimy.ndc(clamp(&self.dimy, y))) / 2f64 * height;
+            // Z is projected with the negated scale, see `draw_points`
+            let nz = -2f64 * self.dimzScale * (clamp(&self.dimz, z) - center) / span + 2f64 * self.dimzDelta / span;
+            let c = export::z2rgb(-nz);
+            let key = ((px / cell).floor() as int, (py / cell).floor() as int);
+            if !cells.contains_key(&key) {
+                cells.insert(key, [0f64, ..4]);
+            }
+            let sum = cells.find_mut(&key).unwrap();
+            for i in range(0u, 3) {
+                sum[i] += c[i] as f64;
+            }
+            sum[3] += 1f64;
+            points.push((px, py, key));
+        }
+        for &(px, py, key) in points.iter() {
+            let sum = cells.find(&key).unwrap();
+            match export::shade(*sum, self.visibleCount as f64, self.alphaScale as f64) {
+                Some((color, opacity)) => {
+                    let each = export::stacked_opacity(opacity, sum[3]);
+                    figure.plot.push(export::ShapeCircle(px, py, radius, color, each));
+                },
+                None => ()
+            }
+        }
+    }
+
+    // padding around tiles, so points centered outside of a tile are not clipped
+    fn tile_padding(&self, scale: f32) -> uint {
+        (self.pointScale * scale / 2f32).ceil() as uint + 1
+    }
+
+    // edge length of tiles without their padding
+    fn tile_size(&self, padding: uint) -> Result<uint, String> {
+        let mut maxTexture = 0;
+        let mut maxViewport = [0i32, 0];
+        unsafe {
+            gl::GetIntegerv(gl::MAX_TEXTURE_SIZE, &mut maxTexture);
+            gl::GetIntegerv(gl::MAX_VIEWPORT_DIMS, &mut maxViewport[0]);
+        }
+        let limit = cmp::min(EXPORT_TILE_SIZE, cmp::max(0, cmp::min(maxTexture, cmp::min(maxViewport[0], maxViewport[1]))) as uint);
+        export::tile_size(limit, padding)
+    }
+
+    // renders the points of the frame enlarged by `scale` tile by tile and returns the shaded
+    // plot area
+    fn render_density(&mut self, scale: f32) -> Result<export::Raster, String> {
+        let margin = (self.config.margin * scale).round() as uint;
+        let frameWidth = (self.dimx.renderLength as f32 * scale).round() as uint;
+        let frameHeight = (self.dimy.renderLength as f32 * scale).round() as uint;
+        if frameWidth <= 2 * margin || frameHeight <= 2 * margin {
+            return Err("the margin leaves no room for the plot".to_string());
+        }
+        let width = frameWidth - 2 * margin;
+        let height = frameHeight - 2 * margin;
+        let padding = self.tile_padding(scale);
+        let tileSize = try!(self.tile_size(padding));
+        let mut raster = export::Raster {
+            width: width,
+            height: height,
+            pixels: Vec::from_elem(width * height * 4, 0u8),
+        };
+
+        // tiles are counted from the bottom left of the plot area like OpenGL rows
+        for &(x, y, w, h) in export::tiles(width, height, tileSize).iter() {
+            let (pw, ph) = (w + 2 * padding, h + 2 * padding);
+            let target = create_target(pw as i32, ph as i32, gl::RGBA32F);
+            let (ox, oy) = ((margin + x) as f32 - padding as f32, (margin + y) as f32 - padding as f32);
+            self.draw_points(scale, (ox, oy, pw as f32, ph as f32));
+
+            // the padding only catches points centered outside of the tile
+            let mut sums = Vec::from_elem(w * h * 4, 0f32);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            unsafe {
+                gl::ReadPixels(padding as i32, padding as i32, w as i32, h as i32, gl::RGBA, gl::FLOAT, mem::transmute(sums.as_mut_ptr()));
+            }
+            delete_target(target);
+
+            for row in range(0, h) {
+                let dst = ((height - 1 - y - row) * width + x) * 4;
+                for col in range(0, w) {
+                    let i = (row * w + col) * 4;
+                    let sum = [sums[i] as f64, sums[i + 1] as f64, sums[i + 2] as f64, sums[i + 3] as f64];
+                    match export::shade(sum, self.visibleCount as f64, self.alphaScale as f64) {
+                        Some((c, opacity)) => {
+                            let px = raster.pixels.mut_slice(dst + col * 4, dst + col * 4 + 4);
+                            px[0] = (c[0] * 255f32).round() as u8;
+                            px[1] = (c[1] * 255f32).round() as u8;
+                            px[2] = (c[2] * 255f32).round() as u8;
+                            px[3] = (opacity * 255f64).round() as u8;
+                        },
+                        None => ()
+                    }
+                }
+            }
+        }
+        gl::Viewport(0, 0, self.dimx.renderLength, self.dimy.renderLength);
+        Ok(raster)
+    }
+
+    // renders the frame enlarged by `scale` tile by tile, with axes and legend but without
+    // overlays like the help or the panel
+    fn render_frame(&mut self, scale: f32) -> Result<export::Raster, String> {
+        let width = (self.dimx.renderLength as f32 * scale).round() as uint;
+        let height = (self.dimy.renderLength as f32 * scale).round() as uint;
+        if width == 0 || height == 0 {
+            return Err(format!("scale {} leaves no pixels", scale));
+        }
+        let padding = self.tile_padding(scale);
+        let tileSize = try!(self.tile_size(padding));
+        let mut raster = export::Raster {
+            width: width,
+            height: height,
+            pixels: Vec::from_elem(width * height * 4, 0u8),
+        };
+        // glyphs are rendered at the enlarged size, so the text stays sharp
+        let textdrawer = mem::replace(&mut self.textdrawer, textdrawer::TextDrawer::with_scale(FONT_DATA, self.config.fontSize, scale as f64));
+        let bg = self.config.background;
+        let fg = self.config.foreground;
+
+        // tiles are counted from the bottom left like OpenGL rows
+        for &(x, y, w, h) in export::tiles(width, height, tileSize).iter() {
+            let (pw, ph) = (w + 2 * padding, h + 2 * padding);
+            let accumulation = create_target(pw as i32, ph as i32, gl::RGBA32F);
+            self.draw_points(scale, (x as f32 - padding as f32, y as f32 - padding as f32, pw as f32, ph as f32));
+
+            let target = create_target(w as i32, h as i32, gl::RGBA8);
+            gl::ClearColor(bg[0], bg[1], bg[2], 1.0);
+            gl::Clear(gl::COLOR_BUFFER_BIT);
+            gl::Enable(gl::BLEND);
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+            // the padding lies outside of the viewport
+            self.vaoTexture.bind();
+            self.programTexture.bind();
+            gl::Viewport(-(padding as i32), -(padding as i32), pw as i32, ph as i32);
+            gl::BindTexture(gl::TEXTURE_2D, accumulation.val1());
+            gl::Uniform1i(self.ulocationTexture.fboTexture, 0);
+            gl::Uniform1f(self.ulocationTexture.count, self.visibleCount as f32);
+            gl::Uniform1f(self.ulocationTexture.alpha, self.alphaScale);
+            self.vaoTexture.draw_array(hgl::Triangles, 0, VERTEX_DATA_TEXTURE.len() as i32 / 2);
+
+            gl::Viewport(0, 0, w as i32, h as i32);
+            let (sx, ox) = export::tile_transform(width as f32, x as f32, w as f32);
+            let (sy, oy) = export::tile_transform(height as f32, y as f32, h as f32);
+            self.programLegend.bind();
+            gl::Uniform1f(self.ulocationLegend.width, self.dimx.renderLength as f32);
+            gl::Uniform1f(self.ulocationLegend.height, self.dimy.renderLength as f32);
+            gl::Uniform1f(self.ulocationLegend.margin, self.config.margin as f32);
+            gl::Uniform4f(self.ulocationLegend.tile, sx, sy, ox, oy);
+            self.vaoTexture.draw_array(hgl::Triangles, 0, VERTEX_DATA_TEXTURE.len() as i32 / 2);
+
+            gl::BindVertexArray(0);
+            gl::UseProgram(0);
+            self.gl2d.clear_shader();
+            // the axes are laid out in window pixels and enlarged, as the points are
+            let top = (height - y - h) as f64;
+            let c = graphics::Context::abs(w as f64, h as f64)
+                .trans(-(x as f64), -top)
+                .zoom(scale as f64)
+                .rgb(fg[0], fg[1], fg[2]);
+            self.draw_x_axis(&c);
+            self.draw_y_axis(&c);
+            self.draw_z_axis(&c);
+            self.draw_centroids(&c);
+
+            let mut pixels = Vec::from_elem(w * h * 4, 0u8);
+            gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            unsafe {
+                gl::ReadPixels(0, 0, w as i32, h as i32, gl::RGBA, gl::UNSIGNED_BYTE, mem::transmute(pixels.as_mut_ptr()));
+            }
+            delete_target(target);
+            delete_target(accumulation);
+
+            for row in range(0, h) {
+                let dst = ((height - 1 - y - row) * width + x) * 4;
+                raster.pixels.mut_slice(dst, dst + w * 4).copy_from(pixels.slice(row * w * 4, (row + 1) * w * 4));
+            }
+        }
+        self.textdrawer = textdrawer;
+        gl::Viewport(0, 0, self.dimx.renderLength, self.dimy.renderLength);
+        Ok(raster)
+    }
+
+    // saves the view, SVG and PDF get vector axes and points or a density raster with `scale`
+    // times the window resolution, PNG and PPM the frame enlarged by `scale`
+    fn export(&mut self, path: &Path, scale: Option<f32>) -> Result<(), String> {
+        match path.extension_str() {
+            Some("png") | Some("ppm") => try!(self.render_frame(scale.unwrap_or(1f32))).save(path),
+            _ => try!(self.figure(scale.unwrap_or(EXPORT_RASTER_SCALE))).save(path)
+        }
+    }
+
+    // saves the view next to the input file in both vector formats
+    fn export_view(&mut self) {
+        let figure = match self.figure(EXPORT_RASTER_SCALE) {
+            Ok(f) => f,
+            Err(e) => {
+                println!("Cannot export view: {}", e);
+                return;
+            }
+        };
+        let stem = export::default_stem(self.source.as_ref().map(|s| &s.path), self.table.name().as_slice());
+        for extension in ["svg", "pdf"].iter() {
+            let path = Path::new(format!("{}.{}", stem, extension));
+            match figure.save(&path) {
+                Ok(()) => println!("Exported view to {}", path.display()),
+                Err(e) => println!("Cannot export view: {}", e)
+            }
+        }
     }
 
     fn renderloop(&mut self) {
@@ -751,6 +3204,28 @@ impl Renderer {
                 self.changed = true;
             }
 
+            if self.poll_pca() {
+                self.changed = true;
+            }
+            if self.poll_tsne() {
+                self.changed = true;
+            }
+            if self.poll_cluster() {
+                self.changed = true;
+            }
+            if self.poll_load() {
+                self.changed = true;
+            }
+            if self.poll_watch() {
+                self.changed = true;
+            }
+            if self.poll_stream() {
+                self.changed = true;
+            }
+            if self.poll_control() {
+                self.changed = true;
+            }
+
             if self.changed {
                 self.redraw();
                 self.changed = false;
@@ -772,7 +3247,17 @@ impl Drop for Renderer {
     }
 }
 
-pub fn render(table: data::Table, column_x: &String, column_y: &String, column_z: &String) {
-    let mut renderer = Renderer::new(table, column_x, column_y, column_z);
-    renderer.renderloop();
+pub fn render(table: data::Table, column_x: &String, column_y: &String, column_z: &String, options: Options) {
+    let export = options.export.clone();
+    let mut renderer = Renderer::new(table, column_x, column_y, column_z, options);
+    match export {
+        Some((ref path, scale)) => {
+            renderer.wait_pca();
+            match renderer.export(path, scale) {
+                Ok(()) => println!("Exported view to {}", path.display()),
+                Err(e) => fail!("--export: {}", e)
+            }
+        },
+        None => renderer.renderloop()
+    }
 }
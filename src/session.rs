@@ -0,0 +1,206 @@
+use serialize::json;
+use serialize::json::ToJson;
+use std::collections::TreeMap;
+use std::io;
+use std::os;
+use std::path::Path;
+
+/// Column and visible data range of one axis, so the view survives changes of the column range.
+#[deriving(Clone, PartialEq, Show)]
+pub struct AxisState {
+    pub column: String,
+    pub lo: f64,
+    pub hi: f64,
+}
+
+/// Everything needed to show the same view again, columns are referred to by name.
+#[deriving(Clone, PartialEq)]
+pub struct Session {
+    // None for streams
+    pub file: Option<Path>,
+    pub x: AxisState,
+    pub y: AxisState,
+    pub z: AxisState,
+    pub pointScale: f64,
+    pub alphaScale: f64,
+    pub rangeMode: String,
+    pub hideOutliers: bool,
+    pub equalAspect: bool,
+    pub filter: String,
+    // definitions entered in the prompt, e.g. `ratio = a / b`
+    pub definitions: Vec<String>,
+    // column name and normalization name
+    pub normalizations: Vec<(String, String)>,
+    // active crossfilter ranges as column name, lower and upper bound
+    pub selection: Vec<(String, f64, f64)>,
+}
+
+impl ToJson for AxisState {
+    fn to_json(&self) -> json::Json {
+        let mut o = TreeMap::new();
+        o.insert("column".to_string(), self.column.to_json());
+        o.insert("lo".to_string(), self.lo.to_json());
+        o.insert("hi".to_string(), self.hi.to_json());
+        o.to_json()
+    }
+}
+
+impl ToJson for Session {
+    fn to_json(&self) -> json::Json {
+        let mut o = TreeMap::new();
+        o.insert("file".to_string(), self.file.as_ref().map(|p| p.as_str().unwrap_or("").to_string()).to_json());
+        o.insert("x".to_string(), self.x.to_json());
+        o.insert("y".to_string(), self.y.to_json());
+        o.insert("z".to_string(), self.z.to_json());
+        o.insert("pointScale".to_string(), self.pointScale.to_json());
+        o.insert("alphaScale".to_string(), self.alphaScale.to_json());
+        o.insert("rangeMode".to_string(), self.rangeMode.to_json());
+        o.insert("hideOutliers".to_string(), self.hideOutliers.to_json());
+        o.insert("equalAspect".to_string(), self.equalAspect.to_json());
+        o.insert("filter".to_string(), self.filter.to_json());
+        o.insert("definitions".to_string(), self.definitions.to_json());
+
+        let mut normalizations = TreeMap::new();
+        for &(ref column, ref norm) in self.normalizations.iter() {
+            normalizations.insert(column.clone(), norm.to_json());
+        }
+        o.insert("normalizations".to_string(), normalizations.to_json());
+
+        let selection: Vec<json::Json> = self.selection.iter().map(|&(ref column, lo, hi)| {
+            let mut r = TreeMap::new();
+            r.insert("column".to_string(), column.to_json());
+            r.insert("lo".to_string(), lo.to_json());
+            r.insert("hi".to_string(), hi.to_json());
+            r.to_json()
+        }).collect();
+        o.insert("selection".to_string(), selection.to_json());
+        o.to_json()
+    }
+}
+
+fn field<'a>(j: &'a json::Json, key: &str) -> Result<&'a json::Json, String> {
+    match j.find(&key.to_string()) {
+        Some(v) => Ok(v),
+        None => Err(format!("missing {}", key))
+    }
+}
+
+fn field_string(j: &json::Json, key: &str) -> Result<String, String> {
+    match try!(field(j, key)).as_string() {
+        Some(s) => Ok(s.to_string()),
+        None => Err(format!("{} must be a string", key))
+    }
+}
+
+fn field_f64(j: &json::Json, key: &str) -> Result<f64, String> {
+    match try!(field(j, key)).as_f64() {
+        Some(x) => Ok(x),
+        None => Err(format!("{} must be a number", key))
+    }
+}
+
+// a missing list counts as empty
+fn field_list<'a>(j: &'a json::Json, key: &str) -> Result<&'a [json::Json], String> {
+    match j.find(&key.to_string()) {
+        Some(v) => match v.as_list() {
+            Some(l) => Ok(l.as_slice()),
+            None => Err(format!("{} must be a list", key))
+        },
+        None => Ok(&[])
+    }
+}
+
+fn axis_from_json(j: &json::Json, key: &str) -> Result<AxisState, String> {
+    let axis = try!(field(j, key));
+    let state = AxisState {
+        column: try!(field_string(axis, "column")),
+        lo: try!(field_f64(axis, "lo")),
+        hi: try!(field_f64(axis, "hi")),
+    };
+    Ok(state)
+}
+
+impl Session {
+    pub fn from_json(j: &json::Json) -> Result<Session, String> {
+        let file = match j.find(&"file".to_string()) {
+            Some(&json::String(ref s)) => Some(Path::new(s.as_slice())),
+            _ => None
+        };
+
+        let mut normalizations = Vec::new();
+        match j.find(&"normalizations".to_string()).and_then(|n| n.as_object()) {
+            Some(o) => for (column, norm) in o.iter() {
+                match norm.as_string() {
+                    Some(n) => normalizations.push((column.clone(), n.to_string())),
+                    None => return Err(format!("normalization of {} must be a string", column))
+                }
+            },
+            None => ()
+        }
+
+        let mut definitions = Vec::new();
+        for d in try!(field_list(j, "definitions")).iter() {
+            match d.as_string() {
+                Some(s) => definitions.push(s.to_string()),
+                None => return Err("definitions must be strings".to_string())
+            }
+        }
+
+        let mut selection = Vec::new();
+        for r in try!(field_list(j, "selection")).iter() {
+            selection.push((try!(field_string(r, "column")), try!(field_f64(r, "lo")), try!(field_f64(r, "hi"))));
+        }
+
+        let session = Session {
+            file: file,
+            x: try!(axis_from_json(j, "x")),
+            y: try!(axis_from_json(j, "y")),
+            z: try!(axis_from_json(j, "z")),
+            pointScale: try!(field_f64(j, "pointScale")),
+            alphaScale: try!(field_f64(j, "alphaScale")),
+            rangeMode: try!(field_string(j, "rangeMode")),
+            hideOutliers: j.find(&"hideOutliers".to_string()).and_then(|b| b.as_boolean()).unwrap_or(false),
+            equalAspect: j.find(&"equalAspect".to_string()).and_then(|b| b.as_boolean()).unwrap_or(false),
+            filter: j.find(&"filter".to_string()).and_then(|f| f.as_string()).unwrap_or("").to_string(),
+            definitions: definitions,
+            normalizations: normalizations,
+            selection: selection,
+        };
+        Ok(session)
+    }
+
+    /// Writes the session to `path`, the input file is stored relative to the directory of `path`.
+    /// Whether both sessions show the same columns the same way and only differ in the view,
+    /// point size, alpha, filter or selection.
+    pub fn same_columns(&self, other: &Session) -> bool {
+        self.x.column == other.x.column && self.y.column == other.y.column && self.z.column == other.z.column
+            && self.rangeMode == other.rangeMode && self.hideOutliers == other.hideOutliers
+            && self.definitions == other.definitions && self.normalizations == other.normalizations
+    }
+
+    pub fn save(&self, path: &Path) -> io::IoResult<()> {
+        let mut session = self.clone();
+        session.file = self.file.as_ref().map(|f| {
+            let dir = os::make_absolute(&path.dir_path());
+            let abs = os::make_absolute(f);
+            abs.path_relative_from(&dir).unwrap_or(abs)
+        });
+        let mut file = try!(io::File::create(path));
+        file.write_str(session.to_json().to_pretty_str().as_slice())
+    }
+
+    pub fn load(path: &Path) -> Result<Session, String> {
+        let content = match io::File::open(path).read_to_string() {
+            Ok(c) => c,
+            Err(e) => return Err(format!("{}: {}", path.display(), e))
+        };
+        let j = match json::from_str(content.as_slice()) {
+            Ok(j) => j,
+            Err(e) => return Err(format!("{}: {}", path.display(), e))
+        };
+        let mut session = try!(Session::from_json(&j).map_err(|e| format!("{}: {}", path.display(), e)));
+        // a relative input file is next to the session, wherever fluxcore is started
+        session.file = session.file.map(|f| path.dir_path().join(f));
+        Ok(session)
+    }
+}
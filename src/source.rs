@@ -0,0 +1,47 @@
+use data;
+use expr;
+use loader;
+use std::path::Path;
+
+/// The input file together with everything that is needed to build the table from it again.
+#[deriving(Clone)]
+pub struct Source {
+    pub path: Path,
+    pub options: loader::Options,
+    // --derive, applied in order after loading
+    pub definitions: Vec<expr::Definition>,
+}
+
+impl Source {
+    pub fn load(&self) -> Result<(data::Table, loader::Resume), String> {
+        let (mut table, resume) = try!(loader::load(&self.path, &self.options));
+
+        for definition in self.definitions.iter() {
+            try!(expr::apply(definition, &mut table).map_err(|e| format!("--derive {}: {}", definition.name, e)));
+        }
+
+        Ok((table, resume))
+    }
+
+    /// The same source without sampling.
+    pub fn full(&self) -> Source {
+        let mut source = self.clone();
+        source.options.sample = None;
+        source
+    }
+
+    /// Appends the rows added to the file since it was loaded and updates the derived columns,
+    /// returns the number of new rows. Fails if the table has to be loaded again instead.
+    pub fn append(&self, resume: &mut loader::Resume, table: &mut data::Table) -> Result<uint, String> {
+        let rows = match try!(loader::load_appended(&self.path, &self.options, resume)) {
+            Some(r) => r,
+            None => return Ok(0)
+        };
+        table.append(&rows);
+        for definition in self.definitions.iter() {
+            try!(expr::apply(definition, table));
+        }
+        table.compute_stats();
+        Ok(rows.len())
+    }
+}
@@ -0,0 +1,271 @@
+use data;
+use datetime;
+use std::f64;
+use std::num::Float;
+
+pub static QUANTILES: [f64, ..7] = [0.01, 0.05, 0.25, 0.5, 0.75, 0.95, 0.99];
+
+// resolution of the percentile table used for robust ranges
+static PERCENTILE_STEPS: uint = 1000;
+
+// scales the MAD to the standard deviation of normal distributed data
+static MAD_SCALE: f64 = 1.4826f64;
+
+pub static DEFAULT_PERCENTILE: f64 = 0.5f64;
+pub static DEFAULT_MAD: f64 = 5f64;
+
+/// How default axis ranges are chosen.
+#[deriving(Clone, PartialEq, Show)]
+pub enum RangeMode {
+    // minimum to maximum
+    RangeFull,
+    // cuts the given percentage at both ends
+    RangePercentile(f64),
+    // median +- k scaled median absolute deviations
+    RangeMad(f64),
+}
+
+impl RangeMode {
+    /// Next mode for cycling through all modes with default parameters.
+    pub fn cycle(&self) -> RangeMode {
+        match *self {
+            RangeFull => RangePercentile(DEFAULT_PERCENTILE),
+            RangePercentile(_) => RangeMad(DEFAULT_MAD),
+            RangeMad(_) => RangeFull,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match *self {
+            RangeFull => "full".to_string(),
+            RangePercentile(p) => format!("percentile {}%", p),
+            RangeMad(k) => format!("mad {}", k),
+        }
+    }
+
+    /// Inverse of `parse_range_mode`.
+    pub fn spec(&self) -> String {
+        match *self {
+            RangeFull => "full".to_string(),
+            RangePercentile(p) => format!("percentile:{}", p),
+            RangeMad(k) => format!("mad:{}", k),
+        }
+    }
+}
+
+/// Parses `full`, `percentile[:P]` or `mad[:K]`.
+pub fn parse_range_mode(s: &str) -> Result<RangeMode, String> {
+    let parts: Vec<&str> = s.trim().splitn(':', 1).collect();
+    let param = if parts.len() > 1 {
+        match from_str::<f64>(parts[1].trim()) {
+            Some(x) if x >= 0f64 => Some(x),
+            _ => return Err(format!("invalid parameter {}", parts[1]))
+        }
+    } else {
+        None
+    };
+    match (parts[0], param) {
+        ("full", None) => Ok(RangeFull),
+        ("percentile", p) => {
+            let p = p.unwrap_or(DEFAULT_PERCENTILE);
+            if p >= 50f64 {
+                Err(format!("percentile {} cuts everything", p))
+            } else {
+                Ok(RangePercentile(p))
+            }
+        },
+        ("mad", k) => Ok(RangeMad(k.unwrap_or(DEFAULT_MAD))),
+        _ => Err(format!("unknown range mode {}", s))
+    }
+}
+
+#[deriving(Clone, Show)]
+pub struct ColumnStats {
+    pub count: uint,
+    // NaN and infinite values, they are ignored by all other statistics
+    pub nanCount: uint,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub std: f64,
+    // values for `QUANTILES`
+    pub quantiles: Vec<f64>,
+    pub distinct: uint,
+    // median absolute deviation
+    pub mad: f64,
+    // quantiles in steps of 1 / PERCENTILE_STEPS
+    percentiles: Vec<f64>,
+}
+
+impl ColumnStats {
+    /// Approximate quantile, interpolated from the percentile table.
+    pub fn percentile(&self, q: f64) -> f64 {
+        quantile(self.percentiles.as_slice(), q)
+    }
+
+    /// Axis range for `mode`, always within min and max.
+    pub fn range(&self, mode: RangeMode) -> (f64, f64) {
+        let (lo, hi) = match mode {
+            RangeFull => (self.min, self.max),
+            RangePercentile(p) => (self.percentile(p / 100f64), self.percentile(1f64 - p / 100f64)),
+            RangeMad(k) => {
+                let median = self.quantiles[3];
+                (median - k * MAD_SCALE * self.mad, median + k * MAD_SCALE * self.mad)
+            }
+        };
+        if lo < hi {
+            (lo.max(self.min), hi.min(self.max))
+        } else {
+            // everything would be cut (e.g. more than half of the values are equal)
+            (self.min, self.max)
+        }
+    }
+}
+
+/// Quantile of sorted data with linear interpolation between the closest ranks.
+pub fn quantile(sorted: &[f64], q: f64) -> f64 {
+    if sorted.is_empty() {
+        return Float::nan();
+    }
+    let pos = q * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as uint;
+    let upper = pos.ceil() as uint;
+    let frac = pos - lower as f64;
+    sorted[lower] * (1f64 - frac) + sorted[upper] * frac
+}
+
+/// Returns all finite values in ascending order.
+pub fn sorted_values(data: &Vec<f64>) -> Vec<f64> {
+    let mut result: Vec<f64> = data.iter().filter(|x| x.is_finite()).map(|&x| x).collect();
+    result.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    result
+}
+
+pub fn compute(data: &Vec<f64>) -> ColumnStats {
+    let sorted = sorted_values(data);
+    let n = sorted.len();
+
+    let mean = sorted.iter().fold(0f64, |a, &b| a + b) / n as f64;
+    let var = sorted.iter().fold(0f64, |a, &b| a + (b - mean) * (b - mean)) / (n as f64 - 1f64);
+
+    // the data is sorted for the quantiles anyway, so counting distinct values is cheap and exact
+    let mut distinct = 0u;
+    for i in range(0, n) {
+        if i == 0 || sorted[i] != sorted[i - 1] {
+            distinct += 1;
+        }
+    }
+
+    let median = quantile(sorted.as_slice(), 0.5f64);
+    let mut deviations: Vec<f64> = sorted.iter().map(|&x| (x - median).abs()).collect();
+    deviations.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    ColumnStats {
+        count: n,
+        nanCount: data.len() - n,
+        min: if n > 0 { sorted[0] } else { Float::nan() },
+        max: if n > 0 { sorted[n - 1] } else { Float::nan() },
+        mean: mean,
+        std: var.sqrt(),
+        quantiles: QUANTILES.iter().map(|&q| quantile(sorted.as_slice(), q)).collect(),
+        distinct: distinct,
+        mad: quantile(deviations.as_slice(), 0.5f64),
+        percentiles: if n > 0 {
+            range(0, PERCENTILE_STEPS + 1).map(|i| quantile(sorted.as_slice(), i as f64 / PERCENTILE_STEPS as f64)).collect()
+        } else {
+            Vec::new()
+        },
+    }
+}
+
+fn format_value(x: f64, kind: data::ColumnKind) -> String {
+    match kind {
+        data::KindTime => datetime::format_iso8601(x),
+        data::KindNumeric | data::KindCategorical => f64::to_str_digits(x, 6),
+    }
+}
+
+/// Label and value pairs for printing, values of time columns are formatted as dates.
+pub fn describe(stats: &ColumnStats, kind: data::ColumnKind) -> Vec<(String, String)> {
+    let mut rows = vec![
+        ("count".to_string(), stats.count.to_string()),
+        ("nan".to_string(), stats.nanCount.to_string()),
+        ("min".to_string(), format_value(stats.min, kind)),
+        ("max".to_string(), format_value(stats.max, kind)),
+        ("mean".to_string(), format_value(stats.mean, kind)),
+        ("std".to_string(), f64::to_str_digits(stats.std, 6)),
+        ("mad".to_string(), f64::to_str_digits(stats.mad, 6)),
+    ];
+    for (&q, &x) in QUANTILES.iter().zip(stats.quantiles.iter()) {
+        rows.push((format!("p{}", (q * 100f64).round() as uint), format_value(x, kind)));
+    }
+    rows.push(("distinct".to_string(), stats.distinct.to_string()));
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::Float;
+    use super::{compute, parse_range_mode, quantile, RangeFull, RangeMad, RangePercentile};
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn quantiles_interpolate() {
+        let sorted = [1f64, 2f64, 3f64, 4f64];
+        assert_eq!(quantile(sorted, 0f64), 1f64);
+        assert_eq!(quantile(sorted, 0.5f64), 2.5f64);
+        assert_eq!(quantile(sorted, 1f64), 4f64);
+        assert!(quantile([], 0.5f64).is_nan());
+    }
+
+    #[test]
+    fn column_statistics() {
+        let mut data: Vec<f64> = range(0u, 101).map(|i| i as f64).rev().collect();
+        data.push(Float::nan());
+        data.push(Float::infinity());
+        let stats = compute(&data);
+        assert_eq!(stats.count, 101);
+        assert_eq!(stats.nanCount, 2);
+        assert_eq!(stats.distinct, 101);
+        assert_eq!((stats.min, stats.max), (0f64, 100f64));
+        assert!(close(stats.mean, 50f64));
+        assert!(close(stats.std, (101f64 * 102f64 / 12f64).sqrt()));
+        assert!(close(stats.quantiles[0], 1f64));
+        assert!(close(stats.quantiles[3], 50f64));
+        assert!(close(stats.mad, 25f64));
+        assert!(close(stats.percentile(0.25f64), 25f64));
+    }
+
+    #[test]
+    fn ranges() {
+        let data: Vec<f64> = range(0u, 101).map(|i| i as f64).collect();
+        let stats = compute(&data);
+        assert_eq!(stats.range(RangeFull), (0f64, 100f64));
+        let (lo, hi) = stats.range(RangePercentile(5f64));
+        assert!(close(lo, 5f64) && close(hi, 95f64));
+        let (lo, hi) = stats.range(RangeMad(1f64));
+        assert!(close(lo, 50f64 - 1.4826f64 * 25f64) && close(hi, 50f64 + 1.4826f64 * 25f64));
+        // ranges never exceed the data
+        assert_eq!(stats.range(RangeMad(10f64)), (0f64, 100f64));
+        // a MAD of 0 would cut everything
+        assert_eq!(compute(&vec![1f64, 1f64, 1f64, 1f64, 5f64]).range(RangeMad(5f64)), (1f64, 5f64));
+    }
+
+    #[test]
+    fn range_modes() {
+        assert_eq!(parse_range_mode("full"), Ok(RangeFull));
+        assert_eq!(parse_range_mode(" percentile "), Ok(RangePercentile(0.5f64)));
+        assert_eq!(parse_range_mode("percentile:2"), Ok(RangePercentile(2f64)));
+        assert_eq!(parse_range_mode("mad:3"), Ok(RangeMad(3f64)));
+        for s in ["percentile:50", "full:1", "mad:-1", "mad:x", "minmax"].iter() {
+            assert!(parse_range_mode(*s).is_err(), "{} should fail", s);
+        }
+        for mode in [RangeFull, RangePercentile(1.5f64), RangeMad(2f64)].iter() {
+            assert_eq!(parse_range_mode(mode.spec().as_slice()), Ok(mode.clone()));
+        }
+        assert_eq!(RangeFull.cycle().cycle().cycle(), RangeFull);
+    }
+}
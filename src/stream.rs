@@ -0,0 +1,478 @@
+use data;
+use datetime;
+use dialect;
+use libc;
+use loader;
+use serialize::json;
+use std::cmp;
+use std::collections::TreeSet;
+use std::comm;
+use std::fmt;
+use std::io;
+use std::io::{Acceptor, Listener};
+use std::io::net::tcp::{TcpListener, TcpStream};
+use std::io::net::unix::{UnixListener, UnixStream};
+use std::iter::FromIterator;
+use std::num::Float;
+use std::path::Path;
+use std::time;
+
+static BUFFER_SIZE: uint = 64 * 1024;
+
+// reading tasks wake up this often to notice when the renderer is gone
+pub static POLL_MS: i32 = 100;
+
+// received rows are added to the table at most this often
+static BATCH_S: f64 = 0.25f64;
+
+pub enum Address {
+    AddrTcp(String, u16),
+    AddrUnix(Path),
+    // also works for regular files that are appended to
+    AddrFifo(Path),
+}
+
+impl fmt::Show for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AddrTcp(ref host, port) => write!(f, "tcp:{}:{}", host, port),
+            AddrUnix(ref path) => write!(f, "unix:{}", path.display()),
+            AddrFifo(ref path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+/// Parses `tcp:HOST:PORT`, `unix:PATH` or the path of a FIFO.
+pub fn parse_address(s: &str) -> Result<Address, String> {
+    if s.starts_with("tcp:") {
+        let rest = s.slice_from(4);
+        match rest.rfind(':') {
+            Some(i) => match from_str::<u16>(rest.slice_from(i + 1)) {
+                Some(port) => Ok(AddrTcp(rest.slice_to(i).to_string(), port)),
+                None => Err(format!("invalid port {}", rest.slice_from(i + 1)))
+            },
+            None => Err(format!("expected tcp:HOST:PORT, got {}", s))
+        }
+    } else if s.starts_with("unix:") {
+        Ok(AddrUnix(Path::new(s.slice_from(5))))
+    } else if s.is_empty() {
+        Err("empty address".to_string())
+    } else {
+        Ok(AddrFifo(Path::new(s)))
+    }
+}
+
+// connections that time out, so their tasks notice when nobody listens anymore
+trait Connection: Reader + Send {
+    fn set_timeout(&mut self, ms: Option<u64>);
+}
+
+impl Connection for TcpStream {
+    fn set_timeout(&mut self, ms: Option<u64>) {
+        self.set_read_timeout(ms);
+    }
+}
+
+impl Connection for UnixStream {
+    fn set_timeout(&mut self, ms: Option<u64>) {
+        self.set_read_timeout(ms);
+    }
+}
+
+// FIFO opened without blocking, so it stays open while writers come and go
+struct Fifo {
+    fd: libc::c_int,
+}
+
+impl Reader for Fifo {
+    fn read(&mut self, buf: &mut [u8]) -> io::IoResult<uint> {
+        let n = unsafe {
+            libc::read(self.fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len() as libc::size_t)
+        };
+        if n > 0 {
+            return Ok(n as uint);
+        }
+        // no data or no writer yet
+        io::timer::sleep(time::duration::Duration::milliseconds(POLL_MS));
+        Err(io::standard_error(io::TimedOut))
+    }
+}
+
+impl Drop for Fifo {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}
+
+/// Forwards complete lines until the input ends, returns false once nobody listens anymore. Read
+/// timeouts send empty lines, so tasks notice when the receiver is gone.
+pub fn pump<R: Reader>(reader: &mut R, tx: &comm::Sender<String>) -> bool {
+    let mut pending: Vec<u8> = Vec::new();
+    let mut buffer = Vec::from_elem(BUFFER_SIZE, 0u8);
+    loop {
+        match reader.read(buffer.as_mut_slice()) {
+            Ok(n) => {
+                pending.push_all(buffer.slice_to(n));
+                loop {
+                    let end = match pending.iter().position(|&b| b == b'\n') {
+                        Some(i) => i,
+                        None => break
+                    };
+                    let line = String::from_utf8_lossy(pending.slice_to(end)).into_string();
+                    pending = pending.slice_from(end + 1).to_vec();
+                    if tx.send_opt(line).is_err() {
+                        return false;
+                    }
+                }
+            },
+            Err(ref e) if e.kind == io::TimedOut => {
+                // empty lines are skipped, so this only checks the receiver
+                if tx.send_opt(String::new()).is_err() {
+                    return false;
+                }
+            },
+            Err(_) => {
+                if !pending.is_empty() {
+                    let line = String::from_utf8_lossy(pending.as_slice()).into_string();
+                    return tx.send_opt(line).is_ok();
+                }
+                return true;
+            }
+        }
+    }
+}
+
+fn accept<C: Connection, A: Acceptor<C> + Send>(mut acceptor: A, tx: comm::Sender<String>) {
+    loop {
+        match acceptor.accept() {
+            Ok(conn) => {
+                let tx = tx.clone();
+                spawn(proc() {
+                    let mut conn = conn;
+                    conn.set_timeout(Some(POLL_MS as u64));
+                    pump(&mut conn, &tx);
+                });
+            },
+            Err(ref e) if e.kind == io::TimedOut => {
+                if tx.send_opt(String::new()).is_err() {
+                    return;
+                }
+            },
+            Err(_) => return
+        }
+    }
+}
+
+#[deriving(PartialEq)]
+enum Format {
+    FormatCsv,
+    // one JSON object per line
+    FormatJson,
+}
+
+/// Rows received as CSV or NDJSON lines from a socket or FIFO.
+pub struct Stream {
+    address: String,
+    rx: comm::Receiver<String>,
+    options: loader::Options,
+    format: Option<Format>,
+    dialect: Option<dialect::Dialect>,
+    // table columns, empty until the header or first object arrived
+    columns: Vec<String>,
+    // CSV field of each table column
+    positions: Vec<uint>,
+    fields: uint,
+    isTime: Vec<bool>,
+    received: uint,
+    skipped: uint,
+    // rows removed by --where
+    filtered: uint,
+    // only the last rows are kept
+    window: Option<uint>,
+    // row overwritten next once the window is full
+    next: uint,
+    lastPoll: f64,
+}
+
+/// Whether `path` is a Unix socket, std reports sockets as the only unknown file type.
+pub fn is_socket(path: &Path) -> bool {
+    match io::fs::lstat(path) {
+        Ok(stat) => stat.kind == io::TypeUnknown,
+        Err(_) => false
+    }
+}
+
+/// Removes a socket left over from an earlier run, refuses to remove anything else.
+pub fn remove_stale_socket(path: &Path) -> Result<(), String> {
+    if !path.exists() {
+        return Ok(());
+    }
+    if !is_socket(path) {
+        return Err(format!("{} exists and is not a socket", path.display()));
+    }
+    io::fs::unlink(path).map_err(|e| format!("{}: {}", path.display(), e))
+}
+
+impl Stream {
+    /// Starts listening in the background, rows are collected by `first_table` and `poll`.
+    pub fn listen(address: &Address, options: &loader::Options, window: Option<uint>) -> Result<Stream, String> {
+        let (tx, rx) = comm::channel();
+        match *address {
+            AddrTcp(ref host, port) => {
+                let mut acceptor = match TcpListener::bind(host.as_slice(), port).listen() {
+                    Ok(a) => a,
+                    Err(e) => return Err(format!("{}", e))
+                };
+                acceptor.set_timeout(Some(POLL_MS as u64));
+                spawn(proc() {
+                    accept(acceptor, tx);
+                });
+            },
+            AddrUnix(ref path) => {
+                try!(remove_stale_socket(path));
+                let mut acceptor = match UnixListener::bind(path).listen() {
+                    Ok(a) => a,
+                    Err(e) => return Err(format!("{}", e))
+                };
+                acceptor.set_timeout(Some(POLL_MS as u64));
+                spawn(proc() {
+                    accept(acceptor, tx);
+                });
+            },
+            AddrFifo(ref path) => {
+                let fd = path.with_c_str(|p| unsafe {
+                    if !path.exists() {
+                        libc::mkfifo(p, 0o600);
+                    }
+                    libc::open(p, libc::O_RDONLY | libc::O_NONBLOCK, 0)
+                });
+                if fd < 0 {
+                    return Err(format!("cannot open {}", path.display()));
+                }
+                let fifo = Fifo {
+                    fd: fd,
+                };
+                spawn(proc() {
+                    let mut fifo = fifo;
+                    pump(&mut fifo, &tx);
+                });
+            }
+        }
+
+        Ok(Stream {
+            address: format!("{}", address),
+            rx: rx,
+            options: options.clone(),
+            format: None,
+            dialect: None,
+            columns: Vec::new(),
+            positions: Vec::new(),
+            fields: 0,
+            isTime: Vec::new(),
+            received: 0,
+            skipped: 0,
+            filtered: 0,
+            window: window,
+            next: 0,
+            lastPoll: 0f64,
+        })
+    }
+
+    // sets up the columns from the header, returns true if `line` contains data as well
+    fn start(&mut self, line: &str) -> bool {
+        if line.trim_left().starts_with("{") {
+            self.format = Some(FormatJson);
+            let keys: Vec<String> = match json::from_str(line) {
+                Ok(ref j) => match j.as_object() {
+                    Some(o) => o.keys().map(|k| k.clone()).collect(),
+                    None => Vec::new()
+                },
+                Err(_) => Vec::new()
+            };
+            let names = self.options.names.clone().unwrap_or(keys);
+            let columns: TreeSet<String> = FromIterator::from_iter(names.move_iter());
+            self.columns = columns.iter().map(|c| c.clone()).collect();
+            self.isTime = Vec::from_elem(self.columns.len(), false);
+            return true;
+        }
+
+        self.format = Some(FormatCsv);
+        let dialect = dialect::Dialect::sniff(line, &self.options.hints);
+        let first = dialect.split(line);
+        let headers: Vec<String> = match self.options.names {
+            Some(ref names) => names.clone(),
+            None => {
+                if dialect.hasHeader {
+                    first.iter().map(|s| s.as_slice().trim().to_string()).collect()
+                } else {
+                    range(0, first.len()).map(|i| format!("col{}", i)).collect()
+                }
+            }
+        };
+        let columns: TreeSet<String> = FromIterator::from_iter(headers.clone().move_iter());
+        self.columns = columns.iter().map(|c| c.clone()).collect();
+        self.positions = self.columns.iter().map(|c| headers.iter().position(|h| h == c).unwrap()).collect();
+        self.fields = headers.len();
+        self.isTime = Vec::from_elem(self.columns.len(), false);
+        let hasData = !dialect.hasHeader;
+        self.dialect = Some(dialect);
+        hasData
+    }
+
+    fn parse_value(&mut self, i: uint, value: &str) -> f64 {
+        let number = match self.dialect {
+            Some(ref d) => d.parse_number(value),
+            None => from_str::<f64>(value.trim())
+        };
+        match number {
+            Some(v) => v,
+            None => match datetime::parse_iso8601(value) {
+                Some(t) => {
+                    *self.isTime.get_mut(i) = true;
+                    t
+                },
+                None => Float::nan()
+            }
+        }
+    }
+
+    // the row of a data line in column order, None for lines that are skipped, fails like the
+    // file loader if --where cannot be evaluated
+    fn parse(&mut self, line: &str) -> Result<Option<Vec<f64>>, String> {
+        if line.trim().is_empty() {
+            return Ok(None);
+        }
+        if self.format.is_none() && !self.start(line) {
+            return Ok(None);
+        }
+        self.received += 1;
+
+        let row: Vec<f64> = if self.format == Some(FormatJson) {
+            let object = match json::from_str(line) {
+                Ok(json::Object(o)) => o,
+                _ => {
+                    self.skipped += 1;
+                    return Ok(None);
+                }
+            };
+            let mut row = Vec::new();
+            for i in range(0, self.columns.len()) {
+                let value = match object.find(&self.columns[i]) {
+                    Some(&json::String(ref s)) => self.parse_value(i, s.as_slice()),
+                    Some(&json::Boolean(b)) => if b { 1f64 } else { 0f64 },
+                    Some(j) => j.as_f64().unwrap_or(Float::nan()),
+                    None => Float::nan()
+                };
+                row.push(value);
+            }
+            row
+        } else {
+            let split = {
+                let dialect = self.dialect.as_ref().unwrap();
+                if dialect.is_comment(line) {
+                    return Ok(None);
+                }
+                dialect.split(line.trim_right_chars('\r'))
+            };
+            if split.len() != self.fields {
+                self.skipped += 1;
+                return Ok(None);
+            }
+            let mut row = Vec::new();
+            for i in range(0, self.columns.len()) {
+                let value = split[self.positions[i]].clone();
+                row.push(self.parse_value(i, value.as_slice()));
+            }
+            row
+        };
+
+        if try!(loader::keep_row(&self.options, &self.columns, &row)) {
+            Ok(Some(row))
+        } else {
+            self.filtered += 1;
+            Ok(None)
+        }
+    }
+
+    fn update_kinds(&self, table: &mut data::Table) {
+        for (name, &time) in self.columns.iter().zip(self.isTime.iter()) {
+            if time || self.options.timeColumns.contains(name) {
+                table.set_kind(name, data::KindTime);
+            }
+        }
+    }
+
+    /// Waits until the columns and the first row are known.
+    pub fn first_table(&mut self) -> Result<data::Table, String> {
+        loop {
+            let line = match self.rx.recv_opt() {
+                Ok(l) => l,
+                Err(()) => return Err(format!("{} closed before any data arrived", self.address))
+            };
+            match try!(self.parse(line.as_slice())) {
+                Some(row) => {
+                    if self.columns.is_empty() {
+                        return Err("the first object has no fields".to_string());
+                    }
+                    let columns: TreeSet<String> = FromIterator::from_iter(self.columns.clone().move_iter());
+                    let mut table = data::Table::new(self.address.clone(), columns);
+                    table.push(&row);
+                    self.update_kinds(&mut table);
+                    table.compute_stats();
+                    return Ok(table);
+                },
+                None => ()
+            }
+        }
+    }
+
+    /// Adds the rows received since the last call, at most every BATCH_S seconds of `now`. Once
+    /// the window is full, each new row overwrites the oldest one in place. Returns the first row
+    /// that changed, None if nothing was added.
+    pub fn poll(&mut self, table: &mut data::Table, now: f64) -> Option<uint> {
+        if now - self.lastPoll < BATCH_S {
+            return None;
+        }
+        self.lastPoll = now;
+        let mut from = table.len();
+        let mut changed = false;
+        loop {
+            let line = match self.rx.try_recv() {
+                Ok(l) => l,
+                Err(_) => break
+            };
+            // the columns are known since `first_table`, so --where cannot fail anymore
+            let row = match self.parse(line.as_slice()) {
+                Ok(Some(row)) => row,
+                _ => continue
+            };
+            match self.window {
+                Some(n) if table.len() >= n => {
+                    from = cmp::min(from, self.next);
+                    table.set_row_named(self.next, &self.columns, &row);
+                    self.next = (self.next + 1) % n;
+                },
+                _ => table.push_named(&self.columns, &row)
+            }
+            changed = true;
+        }
+        if !changed {
+            return None;
+        }
+        self.update_kinds(table);
+        Some(from)
+    }
+
+    pub fn status(&self) -> String {
+        let mut status = format!("stream {}: {} rows", self.address, self.received);
+        if self.skipped > 0 {
+            status.push_str(format!(", {} skipped", self.skipped).as_slice());
+        }
+        if self.filtered > 0 {
+            status.push_str(format!(", {} removed by --where", self.filtered).as_slice());
+        }
+        status
+    }
+}
@@ -27,12 +27,21 @@ struct Character {
 pub struct TextDrawer {
     freetype: freetype::Library,
     fontface: freetype::Face,
+    // pixel size of the glyphs
     size: u32,
+    // glyphs are rendered this much larger and drawn scaled down, for frames larger than the window
+    scale: f64,
     characterBuffer: collections::hashmap::HashMap<char, Character>,
 }
 
 impl TextDrawer {
     pub fn new(fontdata: &[u8], size: u32) -> TextDrawer {
+        TextDrawer::with_scale(fontdata, size, 1f64)
+    }
+
+    /// Text of `size` pixels for a context zoomed by `scale`, sharp at the zoomed resolution.
+    pub fn with_scale(fontdata: &[u8], size: u32, scale: f64) -> TextDrawer {
+        let size = (size as f64 * scale).round() as u32;
         let freetype = freetype::Library::init().unwrap();
         let fontface = freetype.new_memory_face(fontdata, 0).unwrap();
         fontface.set_pixel_sizes(0, size).unwrap();
@@ -41,6 +50,7 @@ impl TextDrawer {
             freetype: freetype,
             fontface: fontface,
             size: size,
+            scale: scale,
             characterBuffer: collections::hashmap::HashMap::new(),
         }
     }
@@ -64,7 +74,9 @@ impl TextDrawer {
         });
     }
 
+    // returns width and height in glyph pixels
     fn render_raw(&mut self, c: &graphics::Context<(),[f32, ..4]>, gl2d: &mut opengl_graphics::Gl, text: &String, draw: bool) -> (i32, i32) {
+        let c = c.zoom(1f64 / self.scale);
         let mut x = 0;
         let mut y = 0;
         let mut width = 0i32;
@@ -117,15 +129,16 @@ impl TextDrawer {
 
     pub fn render(&mut self, c: &graphics::Context<(),[f32, ..4]>, gl2d: &mut opengl_graphics::Gl, text: &String, hor: AnchorHor, vert: AnchorVert) {
         let (width, height) = self.render_raw(c, gl2d, text, false);
+        let (width, height) = (width as f64 / self.scale, height as f64 / self.scale);
         let dx = match hor {
             Left => 0f64,
-            Center => (-width as f64 / 2f64).floor(),
-            Right => (-width as f64).floor()
+            Center => (-width / 2f64).floor(),
+            Right => (-width).floor()
         };
         let dy = match vert {
             Bottom => 0f64,
-            Middle => -(height as f64 / 2f64).floor(),
-            Top => -height as f64
+            Middle => -(height / 2f64).floor(),
+            Top => -height
         };
         self.render_raw(&c.trans(dx, dy), gl2d, text, true);
     }
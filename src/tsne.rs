@@ -0,0 +1,573 @@
+use data;
+use std::comm;
+use std::f64;
+use std::num::Float;
+use std::rand::{Rng, SeedableRng, XorShiftRng};
+use vptree;
+
+static DEFAULT_PERPLEXITY: f64 = 30f64;
+static DEFAULT_ITERATIONS: uint = 1000;
+static DEFAULT_THETA: f64 = 0.5f64;
+static DEFAULT_SEED: u32 = 1;
+
+static LEARNING_RATE: f64 = 200f64;
+static EXAGGERATION: f64 = 12f64;
+static EXAGGERATION_ITERATIONS: uint = 250;
+static PERPLEXITY_STEPS: uint = 200;
+static QUADTREE_MAX_DEPTH: uint = 50;
+
+// a snapshot is sent to the viewer every this many iterations
+static SNAPSHOT_INTERVAL: uint = 10;
+
+pub static COLUMN_X: &'static str = "tSNE1";
+pub static COLUMN_Y: &'static str = "tSNE2";
+
+#[deriving(Clone, Show)]
+pub struct Spec {
+    // empty for all numeric columns
+    pub columns: Vec<String>,
+    pub perplexity: f64,
+    pub iterations: uint,
+    pub theta: f64,
+    pub seed: u32,
+    pub standardize: bool,
+}
+
+/// Parses a comma-separated list of column names and options, e.g. `perplexity=50, seed=7, std, a, b, c`.
+pub fn parse_spec(s: &str) -> Result<Spec, String> {
+    let mut spec = Spec {
+        columns: Vec::new(),
+        perplexity: DEFAULT_PERPLEXITY,
+        iterations: DEFAULT_ITERATIONS,
+        theta: DEFAULT_THETA,
+        seed: DEFAULT_SEED,
+        standardize: false,
+    };
+    for part in s.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let kv: Vec<&str> = part.splitn('=', 1).map(|x| x.trim()).collect();
+        if kv.len() == 1 {
+            if part == "std" || part == "standardize" {
+                spec.standardize = true;
+            } else {
+                spec.columns.push(part.to_string());
+            }
+            continue;
+        }
+
+        let invalid = format!("invalid value for {}: {}", kv[0], kv[1]);
+        match kv[0] {
+            "perplexity" => match from_str::<f64>(kv[1]) {
+                Some(p) if p > 0f64 => spec.perplexity = p,
+                _ => return Err(invalid)
+            },
+            "iter" | "iterations" => match from_str::<uint>(kv[1]) {
+                Some(n) => spec.iterations = n,
+                None => return Err(invalid)
+            },
+            "theta" => match from_str::<f64>(kv[1]) {
+                Some(t) if t >= 0f64 => spec.theta = t,
+                _ => return Err(invalid)
+            },
+            "seed" => match from_str::<u32>(kv[1]) {
+                Some(s) => spec.seed = s,
+                None => return Err(invalid)
+            },
+            _ => return Err(format!("unknown option {}", kv[0]))
+        }
+    }
+    Ok(spec)
+}
+
+pub enum Message {
+    // iteration and the current embedding
+    TsneProgress(uint, Vec<f64>, Vec<f64>),
+    TsneDone(Vec<f64>, Vec<f64>),
+    TsneFailed(String),
+}
+
+/// Columns and data copied from the table, so the computation can run in a background task.
+pub struct Input {
+    spec: Spec,
+    data: Vec<Vec<f64>>,
+}
+
+pub fn prepare(spec: &Spec, table: &data::Table) -> Result<Input, String> {
+    let names: Vec<String> = if spec.columns.is_empty() {
+        table.columns().iter()
+            .filter(|c| table.kind(*c) == data::KindNumeric)
+            .filter(|c| c.as_slice() != COLUMN_X && c.as_slice() != COLUMN_Y)
+            .map(|c| c.clone())
+            .collect()
+    } else {
+        spec.columns.clone()
+    };
+    if names.is_empty() {
+        return Err("no columns to embed".to_string());
+    }
+
+    let mut data = Vec::new();
+    for name in names.iter() {
+        match table.get(name) {
+            Some(d) => data.push(d.clone()),
+            None => return Err(format!("unknown column {}", name))
+        }
+    }
+    Ok(Input {
+        spec: spec.clone(),
+        data: data,
+    })
+}
+
+// conditional probabilities for the squared neighbor distances `d2` with a bandwidth matching
+// `perplexity`
+fn conditional_probabilities(d2: &Vec<f64>, perplexity: f64) -> Vec<f64> {
+    let target = perplexity.ln();
+    // shifting by the smallest distance keeps exp() from underflowing, the entropy does not change
+    let shift = d2.iter().fold(Float::infinity(), |a: f64, &b| a.min(b));
+    let mut beta = 1f64;
+    let mut betaMin = Float::neg_infinity();
+    let mut betaMax = Float::infinity();
+    let mut p: Vec<f64> = Vec::from_elem(d2.len(), 0f64);
+
+    for _ in range(0, PERPLEXITY_STEPS) {
+        let mut sum = 0f64;
+        let mut weighted = 0f64;
+        for (pj, &d) in p.mut_iter().zip(d2.iter()) {
+            *pj = (-beta * (d - shift)).exp();
+            sum += *pj;
+            weighted += beta * (d - shift) * *pj;
+        }
+        let entropy = weighted / sum + sum.ln();
+        for pj in p.mut_iter() {
+            *pj /= sum;
+        }
+
+        let diff = entropy - target;
+        if diff.abs() < 1e-5f64 {
+            break;
+        }
+        if diff > 0f64 {
+            betaMin = beta;
+            beta = if betaMax.is_infinite() { beta * 2f64 } else { (beta + betaMax) / 2f64 };
+        } else {
+            betaMax = beta;
+            beta = if betaMin.is_infinite() { beta / 2f64 } else { (beta + betaMin) / 2f64 };
+        }
+    }
+    p
+}
+
+// symmetric joint probabilities as sparse rows of (index, p)
+fn joint_probabilities<R: Rng>(points: &[f64], dim: uint, perplexity: f64, rng: &mut R) -> Vec<Vec<(uint, f64)>> {
+    let n = points.len() / dim;
+    let k = ((3f64 * perplexity) as uint).min(n - 1);
+    let tree = vptree::VpTree::new(points, dim, rng);
+
+    let mut rows: Vec<Vec<(uint, f64)>> = Vec::from_fn(n, |_| Vec::new());
+    for i in range(0, n) {
+        let neighbors = tree.search(i, k);
+        let d2: Vec<f64> = neighbors.iter().map(|&(d, _)| d * d).collect();
+        let p = conditional_probabilities(&d2, perplexity);
+        for (&(_, j), &pj) in neighbors.iter().zip(p.iter()) {
+            rows.get_mut(i).push((j, pj));
+            rows.get_mut(j).push((i, pj));
+        }
+    }
+
+    // merge both directions and normalize
+    let total = 2f64 * n as f64;
+    for row in rows.mut_iter() {
+        row.sort_by(|a, b| a.val0().cmp(&b.val0()));
+        let mut merged: Vec<(uint, f64)> = Vec::with_capacity(row.len());
+        for &(j, p) in row.iter() {
+            let last = merged.len();
+            if last > 0 && merged[last - 1].val0() == j {
+                let sum = merged[last - 1].val1();
+                *merged.get_mut(last - 1) = (j, sum + p / total);
+            } else {
+                merged.push((j, p / total));
+            }
+        }
+        *row = merged;
+    }
+    rows
+}
+
+struct QuadNode {
+    // cell center and half size
+    cx: f64,
+    cy: f64,
+    half: f64,
+    // center of mass
+    mx: f64,
+    my: f64,
+    count: uint,
+    // set for leaves, several points only if they are too close to split
+    point: Option<uint>,
+    // index of the first of four children
+    children: Option<uint>,
+}
+
+impl QuadNode {
+    fn new(cx: f64, cy: f64, half: f64) -> QuadNode {
+        QuadNode {
+            cx: cx,
+            cy: cy,
+            half: half,
+            mx: 0f64,
+            my: 0f64,
+            count: 0,
+            point: None,
+            children: None,
+        }
+    }
+
+    fn quadrant(&self, x: f64, y: f64) -> uint {
+        (if x >= self.cx { 1 } else { 0 }) + (if y >= self.cy { 2 } else { 0 })
+    }
+}
+
+// Barnes-Hut quadtree over the embedding
+struct QuadTree {
+    nodes: Vec<QuadNode>,
+}
+
+impl QuadTree {
+    fn new(y: &Vec<f64>) -> QuadTree {
+        let n = y.len() / 2;
+        let mut min = [Float::infinity(), Float::infinity()];
+        let mut max = [Float::neg_infinity(), Float::neg_infinity()];
+        for i in range(0, n) {
+            for k in range(0, 2u) {
+                min[k] = min[k].min(y[2 * i + k]);
+                max[k] = max[k].max(y[2 * i + k]);
+            }
+        }
+        let half = ((max[0] - min[0]).max(max[1] - min[1]) / 2f64).max(1e-5f64) * 1.0001f64;
+        let mut tree = QuadTree {
+            nodes: vec![QuadNode::new((min[0] + max[0]) / 2f64, (min[1] + max[1]) / 2f64, half)],
+        };
+        for i in range(0, n) {
+            tree.insert(i, y[2 * i], y[2 * i + 1], y);
+        }
+        tree
+    }
+
+    fn insert(&mut self, i: uint, x: f64, y: f64, all: &Vec<f64>) {
+        let mut node = 0u;
+        let mut depth = 0u;
+        loop {
+            {
+                let n = self.nodes.get_mut(node);
+                n.mx = (n.mx * n.count as f64 + x) / (n.count + 1) as f64;
+                n.my = (n.my * n.count as f64 + y) / (n.count + 1) as f64;
+                n.count += 1;
+            }
+
+            if self.nodes[node].children.is_none() {
+                let existing = match self.nodes[node].point {
+                    None => {
+                        self.nodes.get_mut(node).point = Some(i);
+                        return;
+                    },
+                    Some(e) => e
+                };
+                let (ex, ey) = (all[2 * existing], all[2 * existing + 1]);
+                if (ex == x && ey == y) || depth >= QUADTREE_MAX_DEPTH {
+                    return;
+                }
+
+                // split and move the existing point into its child
+                let (cx, cy, half) = (self.nodes[node].cx, self.nodes[node].cy, self.nodes[node].half / 2f64);
+                let first = self.nodes.len();
+                for q in range(0, 4u) {
+                    let dx = if q & 1 == 1 { half } else { -half };
+                    let dy = if q & 2 == 2 { half } else { -half };
+                    self.nodes.push(QuadNode::new(cx + dx, cy + dy, half));
+                }
+                let child = first + self.nodes[node].quadrant(ex, ey);
+                let moved = self.nodes[node].count - 1;
+                {
+                    let c = self.nodes.get_mut(child);
+                    c.mx = ex;
+                    c.my = ey;
+                    c.count = moved;
+                    c.point = Some(existing);
+                }
+                let n = self.nodes.get_mut(node);
+                n.point = None;
+                n.children = Some(first);
+            }
+
+            node = self.nodes[node].children.unwrap() + self.nodes[node].quadrant(x, y);
+            depth += 1;
+        }
+    }
+
+    // adds the repulsive force on point `i` to (fx, fy) and its contribution to the normalization
+    fn repulsion(&self, node: uint, i: uint, x: f64, y: f64, theta: f64, fx: &mut f64, fy: &mut f64, sumQ: &mut f64) {
+        let n = &self.nodes[node];
+        if n.count == 0 {
+            return;
+        }
+        let dx = x - n.mx;
+        let dy = y - n.my;
+        let d2 = dx * dx + dy * dy;
+
+        match n.children {
+            Some(first) if 2f64 * n.half >= theta * d2.sqrt() => {
+                for q in range(0, 4u) {
+                    self.repulsion(first + q, i, x, y, theta, fx, fy, sumQ);
+                }
+            },
+            _ => {
+                let mult = (if n.point == Some(i) { n.count - 1 } else { n.count }) as f64;
+                if mult == 0f64 {
+                    return;
+                }
+                let q = 1f64 / (1f64 + d2);
+                *sumQ += mult * q;
+                *fx += mult * q * q * dx;
+                *fy += mult * q * q * dy;
+            }
+        }
+    }
+}
+
+fn gaussian<R: Rng>(rng: &mut R) -> f64 {
+    // Box-Muller
+    let u1 = rng.gen::<f64>().max(1e-300f64);
+    let u2 = rng.gen::<f64>();
+    (-2f64 * u1.ln()).sqrt() * (2f64 * f64::consts::PI * u2).cos()
+}
+
+fn expand(rows: &Vec<uint>, nrows: uint, y: &Vec<f64>, k: uint) -> Vec<f64> {
+    let mut result = Vec::from_elem(nrows, Float::nan());
+    for (i, &r) in rows.iter().enumerate() {
+        *result.get_mut(r) = y[2 * i + k];
+    }
+    result
+}
+
+fn run(input: Input, tx: &comm::Sender<Message>) -> Result<(Vec<f64>, Vec<f64>), String> {
+    let Input{spec, data} = input;
+    let dim = data.len();
+    let nrows = data[0].len();
+
+    // rows with NaN values are not embedded
+    let rows: Vec<uint> = range(0, nrows).filter(|&r| data.iter().all(|c| c[r].is_finite())).collect();
+    let n = rows.len();
+    if n < 2 {
+        return Err("not enough rows without NaN values".to_string());
+    }
+    if ((n - 1) as f64) < 3f64 * spec.perplexity {
+        return Err(format!("perplexity {} is too large for {} rows", spec.perplexity, n));
+    }
+
+    let mut points = Vec::with_capacity(n * dim);
+    for &r in rows.iter() {
+        for column in data.iter() {
+            points.push(column[r]);
+        }
+    }
+    for k in range(0, dim) {
+        let mean = range(0, n).fold(0f64, |a, i| a + points[i * dim + k]) / n as f64;
+        let var = range(0, n).fold(0f64, |a, i| a + (points[i * dim + k] - mean).powi(2)) / n as f64;
+        let scale = if spec.standardize && var > 0f64 { var.sqrt() } else { 1f64 };
+        for i in range(0, n) {
+            *points.get_mut(i * dim + k) = (points[i * dim + k] - mean) / scale;
+        }
+    }
+
+    let mut rng: XorShiftRng = SeedableRng::from_seed([spec.seed, 0x9e3779b9, 0x243f6a88, 0xb7e15162]);
+    let p = joint_probabilities(points.as_slice(), dim, spec.perplexity, &mut rng);
+
+    let mut y: Vec<f64> = range(0, 2 * n).map(|_| gaussian(&mut rng) * 1e-4f64).collect();
+    let mut update = Vec::from_elem(2 * n, 0f64);
+    let mut gains = Vec::from_elem(2 * n, 1f64);
+    let mut grad = Vec::from_elem(2 * n, 0f64);
+
+    for iter in range(0, spec.iterations) {
+        let (exaggeration, momentum) = if iter < EXAGGERATION_ITERATIONS {
+            (EXAGGERATION, 0.5f64)
+        } else {
+            (1f64, 0.8f64)
+        };
+
+        let tree = QuadTree::new(&y);
+        let mut sumQ = 0f64;
+        for i in range(0, n) {
+            let (mut fx, mut fy) = (0f64, 0f64);
+            tree.repulsion(0, i, y[2 * i], y[2 * i + 1], spec.theta, &mut fx, &mut fy, &mut sumQ);
+            *grad.get_mut(2 * i) = -fx;
+            *grad.get_mut(2 * i + 1) = -fy;
+        }
+        for i in range(0, n) {
+            *grad.get_mut(2 * i) /= sumQ;
+            *grad.get_mut(2 * i + 1) /= sumQ;
+            for &(j, pij) in p[i].iter() {
+                let dx = y[2 * i] - y[2 * j];
+                let dy = y[2 * i + 1] - y[2 * j + 1];
+                let q = 1f64 / (1f64 + dx * dx + dy * dy);
+                *grad.get_mut(2 * i) += exaggeration * pij * q * dx;
+                *grad.get_mut(2 * i + 1) += exaggeration * pij * q * dy;
+            }
+        }
+
+        for k in range(0, 2 * n) {
+            let gain = if grad[k].signum() != update[k].signum() { gains[k] + 0.2f64 } else { gains[k] * 0.8f64 };
+            *gains.get_mut(k) = gain.max(0.01f64);
+            *update.get_mut(k) = momentum * update[k] - LEARNING_RATE * gains[k] * grad[k];
+            *y.get_mut(k) += update[k];
+        }
+        for k in range(0, 2u) {
+            let mean = range(0, n).fold(0f64, |a, i| a + y[2 * i + k]) / n as f64;
+            for i in range(0, n) {
+                *y.get_mut(2 * i + k) -= mean;
+            }
+        }
+
+        if iter % SNAPSHOT_INTERVAL == 0 {
+            let snapshot = TsneProgress(iter, expand(&rows, nrows, &y, 0), expand(&rows, nrows, &y, 1));
+            if tx.send_opt(snapshot).is_err() {
+                return Err("cancelled".to_string());
+            }
+        }
+    }
+
+    Ok((expand(&rows, nrows, &y, 0), expand(&rows, nrows, &y, 1)))
+}
+
+/// Runs the embedding in a background task that reports progress after every few iterations. Dropping
+/// the receiver stops the task.
+pub fn start(input: Input) -> comm::Receiver<Message> {
+    let (tx, rx) = comm::channel();
+    spawn(proc() {
+        let message = match run(input, &tx) {
+            Ok((x, y)) => TsneDone(x, y),
+            Err(e) => TsneFailed(e)
+        };
+        let _ = tx.send_opt(message);
+    });
+    rx
+}
+
+/// Adds or replaces the embedding columns.
+pub fn apply(x: Vec<f64>, y: Vec<f64>, table: &mut data::Table) {
+    table.add_column(&COLUMN_X.to_string(), x);
+    table.add_column(&COLUMN_Y.to_string(), y);
+}
+
+#[cfg(test)]
+mod test {
+    use std::comm;
+    use std::num::Float;
+    use std::rand::{SeedableRng, XorShiftRng};
+    use super::{conditional_probabilities, gaussian, parse_spec, run, Input, QuadTree};
+
+    fn random_embedding(n: uint) -> Vec<f64> {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([1, 2, 3, 4]);
+        range(0, 2 * n).map(|_| gaussian(&mut rng)).collect()
+    }
+
+    // repulsive forces divided by the normalization, from the tree with `theta`
+    fn repulsion(y: &Vec<f64>, theta: f64) -> Vec<f64> {
+        let tree = QuadTree::new(y);
+        let mut forces = Vec::new();
+        let mut sumQ = 0f64;
+        for i in range(0, y.len() / 2) {
+            let (mut fx, mut fy) = (0f64, 0f64);
+            tree.repulsion(0, i, y[2 * i], y[2 * i + 1], theta, &mut fx, &mut fy, &mut sumQ);
+            forces.push(fx);
+            forces.push(fy);
+        }
+        forces.iter().map(|f| f / sumQ).collect()
+    }
+
+    // the same forces summed over every pair of points
+    fn exact_repulsion(y: &Vec<f64>) -> Vec<f64> {
+        let n = y.len() / 2;
+        let mut forces = Vec::from_elem(2 * n, 0f64);
+        let mut sumQ = 0f64;
+        for i in range(0, n) {
+            for j in range(0, n) {
+                if i == j {
+                    continue;
+                }
+                let dx = y[2 * i] - y[2 * j];
+                let dy = y[2 * i + 1] - y[2 * j + 1];
+                let q = 1f64 / (1f64 + dx * dx + dy * dy);
+                sumQ += q;
+                *forces.get_mut(2 * i) += q * q * dx;
+                *forces.get_mut(2 * i + 1) += q * q * dy;
+            }
+        }
+        forces.iter().map(|f| f / sumQ).collect()
+    }
+
+    // length of the difference relative to the length of `expected`
+    fn relative_error(actual: &Vec<f64>, expected: &Vec<f64>) -> f64 {
+        let diff = actual.iter().zip(expected.iter()).fold(0f64, |a, (x, y)| a + (x - y).powi(2));
+        let norm = expected.iter().fold(0f64, |a, x| a + x * x);
+        (diff / norm).sqrt()
+    }
+
+    #[test]
+    fn perplexity_calibration() {
+        let d2: Vec<f64> = range(1u, 90).map(|i| (i as f64 * 0.37f64) % 5f64 + 100f64).collect();
+        for &perplexity in [5f64, 30f64].iter() {
+            let p = conditional_probabilities(&d2, perplexity);
+            assert!((p.iter().fold(0f64, |a, &x| a + x) - 1f64).abs() < 1e-9);
+            let entropy = p.iter().filter(|&&x| x > 0f64).fold(0f64, |a, &x| a - x * x.ln());
+            assert!((entropy - perplexity.ln()).abs() < 1e-4, "entropy {} for perplexity {}", entropy, perplexity);
+        }
+    }
+
+    #[test]
+    fn barnes_hut_gradient() {
+        let y = random_embedding(100);
+        let exact = exact_repulsion(&y);
+        // without approximation every cell is opened down to single points
+        assert!(relative_error(&repulsion(&y, 0f64), &exact) < 1e-9);
+        assert!(relative_error(&repulsion(&y, 0.5f64), &exact) < 0.05f64);
+    }
+
+    #[test]
+    fn separated_blobs() {
+        // two blobs of 30 points in three dimensions, far apart
+        let mut rng: XorShiftRng = SeedableRng::from_seed([5, 6, 7, 8]);
+        let n = 60u;
+        let data: Vec<Vec<f64>> = range(0, 3u).map(|k| {
+            range(0, n).map(|i| (if i < n / 2 || k > 0 { 0f64 } else { 20f64 }) + gaussian(&mut rng)).collect()
+        }).collect();
+        let spec = parse_spec("perplexity=5, iter=300").unwrap();
+        let (tx, _rx) = comm::channel();
+        let (x, y) = run(Input { spec: spec, data: data }, &tx).unwrap();
+        assert!(x.iter().chain(y.iter()).all(|v| v.is_finite()));
+
+        // the nearest neighbor of every point is in the same blob
+        for i in range(0, n) {
+            let mut nearest = (Float::infinity(), i);
+            for j in range(0, n) {
+                let d2 = (x[i] - x[j]).powi(2) + (y[i] - y[j]).powi(2);
+                if j != i && d2 < nearest.val0() {
+                    nearest = (d2, j);
+                }
+            }
+            assert_eq!(nearest.val1() < n / 2, i < n / 2);
+        }
+        // the blobs are further apart than they are wide on average
+        let centroid = |lo: uint, hi: uint| {
+            let count = (hi - lo) as f64;
+            (range(lo, hi).fold(0f64, |a, i| a + x[i]) / count, range(lo, hi).fold(0f64, |a, i| a + y[i]) / count)
+        };
+        let (ax, ay) = centroid(0, n / 2);
+        let (bx, by) = centroid(n / 2, n);
+        let between = ((ax - bx).powi(2) + (ay - by).powi(2)).sqrt();
+        let width = range(0, n).map(|i| {
+            let (cx, cy) = if i < n / 2 { (ax, ay) } else { (bx, by) };
+            ((x[i] - cx).powi(2) + (y[i] - cy).powi(2)).sqrt()
+        }).fold(0f64, |a, r| a + r) / n as f64;
+        assert!(between > 2f64 * width, "blobs {} apart, {} wide", between, width);
+    }
+}
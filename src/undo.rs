@@ -0,0 +1,182 @@
+use session;
+
+/// Steps kept in the history, older ones are dropped.
+pub static LIMIT: uint = 100u;
+
+// view changes closer together than this are undone at once
+static VIEW_COALESCE_S: f64 = 0.5f64;
+
+/// Undo and redo stacks of whole sessions.
+pub struct History {
+    undoStack: Vec<session::Session>,
+    redoStack: Vec<session::Session>,
+    // state before the current drag, recorded when it ends
+    dragStart: Option<session::Session>,
+    // time of the last view change of the current series, 0 if there is none
+    lastViewChange: f64,
+}
+
+impl History {
+    pub fn new() -> History {
+        History {
+            undoStack: Vec::new(),
+            redoStack: Vec::new(),
+            dragStart: None,
+            lastViewChange: 0f64,
+        }
+    }
+
+    /// Adds a step leading back to `before`.
+    pub fn record(&mut self, before: session::Session) {
+        if self.undoStack.len() == LIMIT {
+            self.undoStack.remove(0);
+        }
+        self.undoStack.push(before);
+        self.redoStack.clear();
+    }
+
+    /// Records the change from `before` to `after` at time `now` in seconds. A quick series of
+    /// view changes (scrolling, key repeats, resizing, ...) becomes a single step, anything that
+    /// changes the columns always gets its own.
+    pub fn change(&mut self, before: session::Session, after: &session::Session, now: f64) {
+        if before == *after {
+            return;
+        }
+        if !before.same_columns(after) {
+            self.lastViewChange = 0f64;
+            self.record(before);
+            return;
+        }
+        if now - self.lastViewChange < VIEW_COALESCE_S {
+            // the step on top of the stack already leads back to the start of the series
+            self.redoStack.clear();
+        } else {
+            self.record(before);
+        }
+        self.lastViewChange = now;
+    }
+
+    /// Keeps the state before a drag, so the whole drag becomes one step once it ends.
+    pub fn start_drag(&mut self, before: session::Session) {
+        self.dragStart = Some(before);
+    }
+
+    /// The state before the current drag, if there is one.
+    pub fn take_drag(&mut self) -> Option<session::Session> {
+        self.dragStart.take()
+    }
+
+    /// The state to go back to, `current` becomes the redo step.
+    pub fn undo(&mut self, current: session::Session) -> Option<session::Session> {
+        let state = self.undoStack.pop();
+        if state.is_some() {
+            self.redoStack.push(current);
+            self.lastViewChange = 0f64;
+        }
+        state
+    }
+
+    /// The state undone last, `current` becomes the undo step.
+    pub fn redo(&mut self, current: session::Session) -> Option<session::Session> {
+        let state = self.redoStack.pop();
+        if state.is_some() {
+            self.undoStack.push(current);
+            self.lastViewChange = 0f64;
+        }
+        state
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use session;
+    use super::{History, LIMIT};
+
+    fn axis(column: &str, lo: f64, hi: f64) -> session::AxisState {
+        session::AxisState {
+            column: column.to_string(),
+            lo: lo,
+            hi: hi,
+        }
+    }
+
+    // a view of the columns a, b and c where X shows lo..lo+1
+    fn state(x: &str, lo: f64) -> session::Session {
+        session::Session {
+            file: None,
+            x: axis(x, lo, lo + 1f64),
+            y: axis("b", 0f64, 1f64),
+            z: axis("c", 0f64, 1f64),
+            pointScale: 8f64,
+            alphaScale: 1f64,
+            rangeMode: "full".to_string(),
+            hideOutliers: false,
+            equalAspect: false,
+            filter: String::new(),
+            definitions: Vec::new(),
+            normalizations: Vec::new(),
+            selection: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut history = History::new();
+        history.change(state("a", 0f64), &state("b", 0f64), 1f64);
+        history.change(state("b", 0f64), &state("c", 0f64), 1.1f64);
+        assert!(history.undo(state("c", 0f64)) == Some(state("b", 0f64)));
+        assert!(history.undo(state("b", 0f64)) == Some(state("a", 0f64)));
+        assert!(history.undo(state("a", 0f64)).is_none());
+        assert!(history.redo(state("a", 0f64)) == Some(state("b", 0f64)));
+
+        // a new change drops the redo steps
+        history.change(state("b", 0f64), &state("a", 5f64), 3f64);
+        assert!(history.redo(state("a", 5f64)).is_none());
+        // unchanged states are not recorded
+        history.change(state("a", 5f64), &state("a", 5f64), 4f64);
+        assert!(history.undo(state("a", 5f64)) == Some(state("b", 0f64)));
+    }
+
+    #[test]
+    fn view_changes_coalesce() {
+        let mut history = History::new();
+        history.change(state("a", 0f64), &state("a", 1f64), 1f64);
+        history.change(state("a", 1f64), &state("a", 2f64), 1.2f64);
+        history.change(state("a", 2f64), &state("a", 3f64), 1.4f64);
+        // a pause starts a new series
+        history.change(state("a", 3f64), &state("a", 4f64), 3f64);
+        assert!(history.undo(state("a", 4f64)) == Some(state("a", 3f64)));
+        assert!(history.undo(state("a", 3f64)) == Some(state("a", 0f64)));
+        assert!(history.undo(state("a", 0f64)).is_none());
+    }
+
+    #[test]
+    fn column_changes_end_a_series() {
+        let mut history = History::new();
+        history.change(state("a", 0f64), &state("a", 1f64), 1f64);
+        history.change(state("a", 1f64), &state("b", 1f64), 1.1f64);
+        history.change(state("b", 1f64), &state("b", 2f64), 1.2f64);
+        assert!(history.undo(state("b", 2f64)) == Some(state("b", 1f64)));
+        assert!(history.undo(state("b", 1f64)) == Some(state("a", 1f64)));
+        assert!(history.undo(state("a", 1f64)) == Some(state("a", 0f64)));
+    }
+
+    #[test]
+    fn limited() {
+        let mut history = History::new();
+        for i in range(0u, LIMIT + 10) {
+            history.record(state("a", i as f64));
+        }
+        let mut n = 0u;
+        let mut current = state("a", -1f64);
+        loop {
+            match history.undo(current.clone()) {
+                Some(s) => current = s,
+                None => break
+            }
+            n += 1;
+        }
+        assert_eq!(n, LIMIT);
+        assert!(current == state("a", 10f64));
+    }
+}
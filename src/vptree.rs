@@ -0,0 +1,198 @@
+use std::num::Float;
+use std::rand::Rng;
+
+pub fn distance(points: &[f64], dim: uint, a: uint, b: uint) -> f64 {
+    let mut sum = 0f64;
+    for k in range(0, dim) {
+        let d = points[a * dim + k] - points[b * dim + k];
+        sum += d * d;
+    }
+    sum.sqrt()
+}
+
+struct VpNode {
+    index: uint,
+    // points closer than this are in the left subtree
+    threshold: f64,
+    left: Option<uint>,
+    right: Option<uint>,
+}
+
+/// Vantage point tree over `dim`-dimensional points stored row by row, for nearest neighbor and
+/// radius queries.
+pub struct VpTree<'a> {
+    points: &'a [f64],
+    dim: uint,
+    nodes: Vec<VpNode>,
+    root: Option<uint>,
+}
+
+impl<'a> VpTree<'a> {
+    pub fn new<R: Rng>(points: &'a [f64], dim: uint, rng: &mut R) -> VpTree<'a> {
+        let n = points.len() / dim;
+        let mut items: Vec<uint> = range(0, n).collect();
+        let mut nodes = Vec::new();
+        let root = VpTree::build(points, dim, &mut nodes, &mut items, 0, n, rng);
+        VpTree {
+            points: points,
+            dim: dim,
+            nodes: nodes,
+            root: root,
+        }
+    }
+
+    fn build<R: Rng>(points: &[f64], dim: uint, nodes: &mut Vec<VpNode>, items: &mut Vec<uint>, lo: uint, hi: uint, rng: &mut R) -> Option<uint> {
+        if lo >= hi {
+            return None;
+        }
+
+        let pick = lo + rng.gen_range(0, hi - lo);
+        items.as_mut_slice().swap(lo, pick);
+        let vantage = items[lo];
+        let mid = (lo + 1 + hi) / 2;
+        let mut threshold = 0f64;
+        if hi - lo > 1 {
+            items.mut_slice(lo + 1, hi).sort_by(|&a, &b| {
+                distance(points, dim, vantage, a).partial_cmp(&distance(points, dim, vantage, b)).unwrap()
+            });
+            threshold = distance(points, dim, vantage, items[mid]);
+        }
+
+        let node = nodes.len();
+        nodes.push(VpNode {
+            index: vantage,
+            threshold: threshold,
+            left: None,
+            right: None,
+        });
+        let left = VpTree::build(points, dim, nodes, items, lo + 1, mid, rng);
+        let right = VpTree::build(points, dim, nodes, items, mid, hi, rng);
+        nodes.get_mut(node).left = left;
+        nodes.get_mut(node).right = right;
+        Some(node)
+    }
+
+    /// `k` nearest neighbors of point `target` (excluding itself) as (distance, index), closest first
+    pub fn search(&self, target: uint, k: uint) -> Vec<(f64, uint)> {
+        let mut result = Vec::new();
+        let mut tau = Float::infinity();
+        match self.root {
+            Some(root) => self.search_node(root, target, k, &mut result, &mut tau),
+            None => ()
+        }
+        result
+    }
+
+    fn search_node(&self, node: uint, target: uint, k: uint, result: &mut Vec<(f64, uint)>, tau: &mut f64) {
+        let n = &self.nodes[node];
+        let d = distance(self.points, self.dim, n.index, target);
+        if n.index != target && d < *tau {
+            let pos = result.iter().position(|&(other, _)| other > d).unwrap_or(result.len());
+            result.insert(pos, (d, n.index));
+            if result.len() > k {
+                result.pop();
+            }
+            if result.len() == k {
+                *tau = result[k - 1].val0();
+            }
+        }
+
+        if d < n.threshold {
+            if d - *tau <= n.threshold {
+                n.left.map(|l| self.search_node(l, target, k, result, tau));
+            }
+            if d + *tau >= n.threshold {
+                n.right.map(|r| self.search_node(r, target, k, result, tau));
+            }
+        } else {
+            if d + *tau >= n.threshold {
+                n.right.map(|r| self.search_node(r, target, k, result, tau));
+            }
+            if d - *tau <= n.threshold {
+                n.left.map(|l| self.search_node(l, target, k, result, tau));
+            }
+        }
+    }
+
+    /// Indices of all points within `radius` of point `target`, including itself.
+    pub fn within(&self, target: uint, radius: f64) -> Vec<uint> {
+        let mut result = Vec::new();
+        match self.root {
+            Some(root) => self.within_node(root, target, radius, &mut result),
+            None => ()
+        }
+        result
+    }
+
+    fn within_node(&self, node: uint, target: uint, radius: f64, result: &mut Vec<uint>) {
+        let n = &self.nodes[node];
+        let d = distance(self.points, self.dim, n.index, target);
+        if d <= radius {
+            result.push(n.index);
+        }
+        if d - radius <= n.threshold {
+            n.left.map(|l| self.within_node(l, target, radius, result));
+        }
+        if d + radius >= n.threshold {
+            n.right.map(|r| self.within_node(r, target, radius, result));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::rand::{Rng, SeedableRng, XorShiftRng};
+    use super::{distance, VpTree};
+
+    fn random_points(n: uint, dim: uint) -> Vec<f64> {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([1, 2, 3, 4]);
+        range(0, n * dim).map(|_| rng.gen::<f64>()).collect()
+    }
+
+    // the `k` nearest neighbors of `target` by comparing it with every other point
+    fn brute_force(points: &[f64], dim: uint, target: uint, k: uint) -> Vec<(f64, uint)> {
+        let mut all: Vec<(f64, uint)> = range(0, points.len() / dim)
+            .filter(|&j| j != target)
+            .map(|j| (distance(points, dim, target, j), j))
+            .collect();
+        all.sort_by(|a, b| a.val0().partial_cmp(&b.val0()).unwrap());
+        all.truncate(k);
+        all
+    }
+
+    #[test]
+    fn nearest_neighbors() {
+        let points = random_points(200, 3);
+        let mut rng: XorShiftRng = SeedableRng::from_seed([5, 6, 7, 8]);
+        let tree = VpTree::new(points.as_slice(), 3, &mut rng);
+        for i in range(0u, 200) {
+            assert_eq!(tree.search(i, 7), brute_force(points.as_slice(), 3, i, 7));
+        }
+        // asking for more neighbors than there are returns all others
+        assert_eq!(tree.search(0, 500).len(), 199);
+    }
+
+    #[test]
+    fn within_radius() {
+        let points = random_points(200, 2);
+        let mut rng: XorShiftRng = SeedableRng::from_seed([5, 6, 7, 8]);
+        let tree = VpTree::new(points.as_slice(), 2, &mut rng);
+        for i in range(0u, 200) {
+            let mut found = tree.within(i, 0.1f64);
+            found.sort();
+            let expected: Vec<uint> = range(0u, 200).filter(|&j| distance(points.as_slice(), 2, i, j) <= 0.1f64).collect();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn empty_and_single() {
+        let mut rng: XorShiftRng = SeedableRng::from_seed([5, 6, 7, 8]);
+        let none: Vec<f64> = Vec::new();
+        assert!(VpTree::new(none.as_slice(), 2, &mut rng).within(0, 1f64).is_empty());
+        let one = vec![1f64, 2f64];
+        let tree = VpTree::new(one.as_slice(), 2, &mut rng);
+        assert!(tree.search(0, 3).is_empty());
+        assert_eq!(tree.within(0, 0f64), vec![0u]);
+    }
+}
@@ -0,0 +1,88 @@
+use libc;
+use std::path::Path;
+
+static IN_NONBLOCK: libc::c_int = 0o4000;
+static IN_MODIFY: u32 = 0x2;
+static IN_CLOSE_WRITE: u32 = 0x8;
+static IN_MOVED_TO: u32 = 0x80;
+static IN_CREATE: u32 = 0x100;
+
+// wd, mask, cookie and len, followed by len bytes of the name
+static EVENT_HEADER: uint = 16;
+static BUFFER_SIZE: uint = 4096;
+
+extern {
+    fn inotify_init1(flags: libc::c_int) -> libc::c_int;
+    fn inotify_add_watch(fd: libc::c_int, pathname: *const libc::c_char, mask: u32) -> libc::c_int;
+}
+
+/// Notices changes of a file using inotify. Its directory is watched, so files that are replaced
+/// by renaming a new version over them are followed as well.
+pub struct Watcher {
+    fd: libc::c_int,
+    name: Vec<u8>,
+}
+
+impl Watcher {
+    pub fn new(path: &Path) -> Result<Watcher, String> {
+        let name = match path.filename() {
+            Some(n) => n.to_vec(),
+            None => return Err(format!("{} is not a file", path.display()))
+        };
+        let dir = path.dir_path();
+
+        let fd = unsafe { inotify_init1(IN_NONBLOCK) };
+        if fd < 0 {
+            return Err("inotify is not available".to_string());
+        }
+        let wd = dir.with_c_str(|p| unsafe {
+            inotify_add_watch(fd, p, IN_MODIFY | IN_CLOSE_WRITE | IN_MOVED_TO | IN_CREATE)
+        });
+        if wd < 0 {
+            unsafe {
+                libc::close(fd);
+            }
+            return Err(format!("cannot watch {}", dir.display()));
+        }
+
+        Ok(Watcher {
+            fd: fd,
+            name: name,
+        })
+    }
+
+    /// Returns true if the file changed since the last call, never blocks.
+    pub fn changed(&mut self) -> bool {
+        let mut changed = false;
+        let mut buffer = [0u8, ..BUFFER_SIZE];
+        loop {
+            let n = unsafe {
+                libc::read(self.fd, buffer.as_mut_ptr() as *mut libc::c_void, BUFFER_SIZE as libc::size_t)
+            };
+            if n <= 0 {
+                break;
+            }
+
+            let mut pos = 0u;
+            while pos + EVENT_HEADER <= n as uint {
+                let len = unsafe { *(buffer.as_ptr().offset((pos + 12) as int) as *const u32) } as uint;
+                let start = pos + EVENT_HEADER;
+                // the name is padded with NUL bytes
+                let name: Vec<u8> = buffer.slice(start, start + len).iter().take_while(|&&b| b != 0).map(|&b| b).collect();
+                if name == self.name {
+                    changed = true;
+                }
+                pos = start + len;
+            }
+        }
+        changed
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.fd);
+        }
+    }
+}